@@ -17,6 +17,6 @@ fn main() {
     log.write_all(b"Hello, world!\n").unwrap();
     // Write a bunch of lines
     for _ in 2..=100 {
-        writeln!(log, "{}", Utc::now().to_string()).unwrap();
+        writeln!(log, "{}", Utc::now()).unwrap();
     }
 }
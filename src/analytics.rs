@@ -0,0 +1,6 @@
+pub mod bar_builder;
+pub mod book_analytics;
+pub mod book_features;
+pub mod consolidated_book;
+pub mod data_quality;
+pub mod indicators;
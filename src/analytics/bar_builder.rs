@@ -0,0 +1,402 @@
+use log::debug;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use time::{Duration, OffsetDateTime};
+
+use crate::datamodels::enums::MarketData;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::marketdata::{Bar, MarketTrade};
+use crate::datamodels::types::Qty;
+use crate::feedhandler::textwriter::TextWriter;
+use crate::feedhandler::tickreader::TickReader;
+use crate::monitor::metrics;
+
+/// How trades are grouped into bars.
+///
+/// Parsed from `time:1m`, `tick:100`, `volume:10` or `dollar:1000000`.
+/// Time bars accept `s`, `m`, `h` and `d` units from 1s up to 1d.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BarSpec {
+    Time(Duration),
+    Tick(u64),
+    Volume(Qty),
+    Dollar(f64),
+}
+
+impl FromStr for BarSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid bar spec {}", s);
+        let (kind, value) = s.split_once(':').ok_or_else(invalid)?;
+        match kind {
+            "time" => {
+                let unit_seconds = [('s', 1), ('m', 60), ('h', 3600), ('d', 86400)];
+                let (count, seconds) = unit_seconds
+                    .iter()
+                    .find_map(|(unit, seconds)| Some((value.strip_suffix(*unit)?, *seconds)))
+                    .ok_or_else(invalid)?;
+                let count: i64 = count.parse().map_err(|_| invalid())?;
+                let duration = count
+                    .checked_mul(seconds)
+                    .map(Duration::seconds)
+                    .ok_or_else(|| format!("bar duration out of 1s..1d: {}", s))?;
+                if duration < Duration::SECOND || duration > Duration::DAY {
+                    return Err(format!("bar duration out of 1s..1d: {}", s));
+                }
+                Ok(BarSpec::Time(duration))
+            }
+            "tick" => match value.parse() {
+                Ok(count) if count > 0 => Ok(BarSpec::Tick(count)),
+                _ => Err(invalid()),
+            },
+            "volume" => match value.parse::<f64>() {
+                Ok(volume) if volume > 0.0 => Ok(BarSpec::Volume(Qty::from_f64(volume))),
+                _ => Err(invalid()),
+            },
+            "dollar" => match value.parse::<f64>() {
+                Ok(notional) if notional > 0.0 => Ok(BarSpec::Dollar(notional)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for BarSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BarSpec::Time(duration) => {
+                let seconds = duration.whole_seconds();
+                let (count, unit) = [(86400, "d"), (3600, "h"), (60, "m")]
+                    .into_iter()
+                    .find(|(size, _)| seconds % size == 0)
+                    .map(|(size, unit)| (seconds / size, unit))
+                    .unwrap_or((seconds, "s"));
+                write!(f, "time:{}{}", count, unit)
+            }
+            BarSpec::Tick(count) => write!(f, "tick:{}", count),
+            BarSpec::Volume(volume) => write!(f, "volume:{}", volume),
+            BarSpec::Dollar(notional) => write!(f, "dollar:{}", notional),
+        }
+    }
+}
+
+/// Aggregates `MarketTrade`s into `Bar`s per venue and sym.
+///
+/// Time bars are aligned to the unix epoch on `market_created_timestamp` and are closed by the
+/// first trade past their end; intervals without trades produce no bar.
+/// Tick, volume and dollar bars close on the trade reaching the threshold, which is not split.
+/// Trades older than the latest trade of their venue and sym are dropped and counted.
+pub struct BarBuilder {
+    spec: BarSpec,
+    bar_type: String,
+    bars: HashMap<(String, String), Bar>,
+    last_trade: HashMap<(String, String), OffsetDateTime>,
+    late_trades: u64,
+    ids: IdSource,
+}
+
+impl BarBuilder {
+    pub fn new(spec: BarSpec) -> Self {
+        Self {
+            bar_type: spec.to_string(),
+            spec,
+            bars: HashMap::new(),
+            last_trade: HashMap::new(),
+            late_trades: 0,
+            ids: IdSource::default(),
+        }
+    }
+
+    /// Source of the bar ids, random by default.
+    pub fn set_ids(&mut self, ids: IdSource) {
+        self.ids = ids;
+    }
+
+    pub fn spec(&self) -> &BarSpec {
+        &self.spec
+    }
+
+    /// Out-of-order trades dropped so far.
+    pub fn late_trades(&self) -> u64 {
+        self.late_trades
+    }
+
+    /// Bars completed by `data`. Only trades move the clock of the time bars: boards and rates
+    /// may carry estimated exchange times running ahead of the trades.
+    pub fn next(&mut self, data: &MarketData) -> Vec<Bar> {
+        let trade = match data {
+            MarketData::MarketTrade(trade) => trade,
+            _ => return vec![],
+        };
+        let key = (trade.venue.clone(), trade.sym.clone());
+        let timestamp = trade.market_created_timestamp;
+        let last_trade = self.last_trade.entry(key.clone()).or_insert(timestamp);
+        if timestamp < *last_trade {
+            self.late_trades += 1;
+            metrics::BAR_LATE_TRADES
+                .with_label_values(&[&key.0, &key.1])
+                .inc();
+            debug!(
+                "Dropped out-of-order trade {} of {} at {}, latest {}",
+                trade.trade_id, key.1, timestamp, last_trade
+            );
+            return vec![];
+        }
+        *last_trade = timestamp;
+
+        let mut completed = vec![];
+        if let BarSpec::Time(duration) = self.spec {
+            let expired = self
+                .bars
+                .get(&key)
+                .is_some_and(|bar| bar.open_timestamp + duration <= timestamp);
+            if expired {
+                let mut bar = self.bars.remove(&key).unwrap();
+                bar.market_created_timestamp = bar.open_timestamp + duration;
+                bar.timestamp = OffsetDateTime::now_utc();
+                completed.push(bar);
+            }
+        }
+        self.add(key, trade, &mut completed);
+        completed
+    }
+
+    fn add(&mut self, key: (String, String), trade: &MarketTrade, completed: &mut Vec<Bar>) {
+        let open_timestamp = match self.spec {
+            BarSpec::Time(duration) => {
+                let nanos = trade.market_created_timestamp.unix_timestamp_nanos();
+                let start = nanos - nanos.rem_euclid(duration.whole_nanoseconds());
+                OffsetDateTime::from_unix_timestamp_nanos(start).unwrap()
+            }
+            _ => trade.market_created_timestamp,
+        };
+        let bar = self
+            .bars
+            .entry(key.clone())
+            .and_modify(|bar| bar.add(trade))
+            .or_insert_with(|| Bar::open(trade, &self.bar_type, open_timestamp, &self.ids));
+
+        let is_complete = match self.spec {
+            BarSpec::Time(_) => false,
+            BarSpec::Tick(count) => bar.trade_count >= count,
+            BarSpec::Volume(volume) => bar.volume >= volume,
+            BarSpec::Dollar(notional) => bar.notional >= notional,
+        };
+        if is_complete {
+            let mut bar = self.bars.remove(&key).unwrap();
+            bar.timestamp = OffsetDateTime::now_utc();
+            completed.push(bar);
+        }
+    }
+
+    /// Bars still open, marked `partial` in `misc`.
+    pub fn flush(&mut self) -> Vec<Bar> {
+        self.bars
+            .drain()
+            .map(|(_, mut bar)| {
+                bar.timestamp = OffsetDateTime::now_utc();
+                bar.misc = "partial".to_string();
+                bar
+            })
+            .collect()
+    }
+
+    /// Feed callback forwarding every input to `callback`, followed by the bars it completes.
+    pub fn into_callback<F: FnMut(MarketData) + Send>(
+        mut self,
+        mut callback: F,
+    ) -> impl FnMut(MarketData) + Send {
+        move |data: MarketData| {
+            let bars = self.next(&data);
+            callback(data);
+            for bar in bars {
+                callback(MarketData::Bar(bar));
+            }
+        }
+    }
+
+    /// Build bars over recorded trades, e.g. `TickReader::new("MarketTrade/bitflyer")`,
+    /// and write them to `writer`. Returns the number of bars written.
+    pub fn replay(&mut self, reader: &TickReader, writer: &mut TextWriter) -> usize {
+        let mut count = 0;
+        for trade in reader.records::<MarketTrade>() {
+            for bar in self.next(&MarketData::MarketTrade(trade)) {
+                writer.write(serde_json::to_string(&bar).unwrap().as_str());
+                count += 1;
+            }
+        }
+        for bar in self.flush() {
+            writer.write(serde_json::to_string(&bar).unwrap().as_str());
+            count += 1;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodels::marketdata::Rate;
+    use crate::datamodels::types::{Price, Side};
+
+    fn trade(secs: i64, price: f64, amount: f64) -> MarketData {
+        let timestamp = OffsetDateTime::UNIX_EPOCH + Duration::seconds(secs);
+        MarketData::MarketTrade(MarketTrade {
+            timestamp,
+            market_created_timestamp: timestamp,
+            received_timestamp: timestamp,
+            sym: "FXBTCJPY".to_string(),
+            venue: "bitflyer".to_string(),
+            category: "lightning".to_string(),
+            side: Side::Buy,
+            price: Price::from_f64(price),
+            amount: Qty::from_f64(amount),
+            trade_id: secs.to_string(),
+            order_ids: "".to_string(),
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            process_id: "".to_string(),
+            data_center: "".to_string(),
+        })
+    }
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        for spec in [
+            "time:1s",
+            "time:5m",
+            "time:1h",
+            "time:1d",
+            "tick:100",
+            "volume:10",
+        ] {
+            assert_eq!(spec.parse::<BarSpec>().unwrap().to_string(), spec);
+        }
+        assert_eq!(
+            "time:90s".parse::<BarSpec>().unwrap(),
+            BarSpec::Time(Duration::seconds(90))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_specs_without_panicking() {
+        for spec in [
+            "time:",
+            "time:m",
+            "time:1",
+            "time:1w",
+            "time:1é",
+            "time:é",
+            "time:0s",
+            "time:2d",
+            "time:-1m",
+            "time:9223372036854775807d",
+            "time:9223372036854775807s",
+            "tick:0",
+            "volume:-1",
+            "dollar:x",
+            "range:1",
+            "time",
+        ] {
+            assert!(spec.parse::<BarSpec>().is_err(), "{} parsed", spec);
+        }
+    }
+
+    #[test]
+    fn time_bars_close_on_the_first_trade_past_their_end() {
+        let mut builder = BarBuilder::new("time:1m".parse().unwrap());
+        assert!(builder.next(&trade(1, 100.0, 1.0)).is_empty());
+        assert!(builder.next(&trade(30, 110.0, 1.0)).is_empty());
+        assert!(builder.next(&trade(59, 90.0, 2.0)).is_empty());
+
+        let bars = builder.next(&trade(60, 105.0, 1.0));
+        assert_eq!(bars.len(), 1);
+        let bar = &bars[0];
+        assert_eq!(bar.open_timestamp, OffsetDateTime::UNIX_EPOCH);
+        assert_eq!(
+            bar.market_created_timestamp,
+            OffsetDateTime::UNIX_EPOCH + Duration::MINUTE
+        );
+        assert_eq!(bar.open, Price::from_f64(100.0));
+        assert_eq!(bar.high, Price::from_f64(110.0));
+        assert_eq!(bar.low, Price::from_f64(90.0));
+        assert_eq!(bar.close, Price::from_f64(90.0));
+        assert_eq!(bar.volume, Qty::from_f64(4.0));
+        assert_eq!(bar.vwap, 390.0 / 4.0);
+        assert_eq!(bar.trade_count, 3);
+
+        let partial = builder.flush();
+        assert_eq!(partial.len(), 1);
+        assert_eq!(partial[0].misc, "partial");
+    }
+
+    #[test]
+    fn out_of_order_trades_are_dropped_and_counted() {
+        let mut builder = BarBuilder::new("time:1m".parse().unwrap());
+        builder.next(&trade(10, 100.0, 1.0));
+        builder.next(&trade(20, 101.0, 1.0));
+        assert!(builder.next(&trade(15, 200.0, 1.0)).is_empty());
+        // same timestamp is not out of order
+        builder.next(&trade(20, 102.0, 1.0));
+        assert_eq!(builder.late_trades(), 1);
+
+        let bars = builder.next(&trade(61, 100.0, 1.0));
+        // late trade of the closed bar
+        builder.next(&trade(59, 300.0, 1.0));
+        assert_eq!(builder.late_trades(), 2);
+        assert_eq!(bars[0].high, Price::from_f64(102.0));
+        assert_eq!(bars[0].trade_count, 3);
+        assert_eq!(builder.flush()[0].trade_count, 1);
+    }
+
+    #[test]
+    fn tick_bars_close_on_the_threshold() {
+        let mut builder = BarBuilder::new("tick:2".parse().unwrap());
+        assert!(builder.next(&trade(1, 100.0, 1.0)).is_empty());
+        let bars = builder.next(&trade(2, 101.0, 1.0));
+        assert_eq!(bars.len(), 1);
+        assert_eq!(bars[0].trade_count, 2);
+        assert!(builder.flush().is_empty());
+    }
+
+    #[test]
+    fn early_board_time_does_not_split_the_interval() {
+        let mut builder = BarBuilder::new("time:1m".parse().unwrap());
+        builder.next(&trade(50, 100.0, 1.0));
+        // a board whose estimated exchange time runs ahead of the trades
+        let ahead = OffsetDateTime::UNIX_EPOCH + Duration::seconds(65);
+        let rate = MarketData::Rate(Rate {
+            timestamp: ahead,
+            market_created_timestamp: ahead,
+            received_timestamp: ahead,
+            sym: "FXBTCJPY".to_string(),
+            venue: "bitflyer".to_string(),
+            category: "lightning".to_string(),
+            best_bid: 100.0,
+            best_ask: 101.0,
+            mid_price: 100.5,
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        });
+        assert!(builder.next(&rate).is_empty());
+        assert!(builder.next(&trade(55, 101.0, 1.0)).is_empty());
+
+        let bars = builder.next(&trade(61, 102.0, 1.0));
+        assert_eq!(bars.len(), 1);
+        assert_eq!(bars[0].open_timestamp, OffsetDateTime::UNIX_EPOCH);
+        assert_eq!(bars[0].trade_count, 2);
+        assert_eq!(bars[0].close, Price::from_f64(101.0));
+        let partial = builder.flush();
+        assert_eq!(partial.len(), 1);
+        assert_eq!(
+            partial[0].open_timestamp,
+            OffsetDateTime::UNIX_EPOCH + Duration::MINUTE
+        );
+        assert_eq!(builder.late_trades(), 0);
+    }
+}
@@ -0,0 +1,358 @@
+use serde::{Deserialize, Serialize};
+
+use crate::datamodels::marketdata::MarketBook;
+use crate::datamodels::types::{Price, Qty, Side};
+
+/// Result of sweeping one side of the book, see `MarketBook::cost_to_trade`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Sweep {
+    pub filled: Qty,
+    pub notional: f64,
+    pub vwap: f64,
+    /// Last level touched.
+    pub worst_price: Price,
+    /// `vwap` against the mid, positive when paying up.
+    pub slippage_bps: f64,
+    pub levels: usize,
+}
+
+/// Size distribution over the first levels of one side.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct LevelStats {
+    pub count: usize,
+    pub total: Qty,
+    pub mean: f64,
+    pub max: Qty,
+    pub min: Qty,
+}
+
+/// Analytics over the `bids` / `asks` ladders.
+///
+/// `Side::Buy` designates the bids and `Side::Sell` the asks, except for `cost_to_trade`
+/// where the side is the aggressor's. Ladders are walked from the touch, so every method
+/// only visits the levels it needs.
+impl MarketBook {
+    /// Levels of one side from the touch outwards.
+    pub fn levels(&self, side: Side) -> Box<dyn Iterator<Item = (&Price, &Qty)> + '_> {
+        match side {
+            Side::Buy => Box::new(self.bids.iter().rev()),
+            Side::Sell => Box::new(self.asks.iter()),
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<(Price, Qty)> {
+        self.bids.iter().next_back().map(|(p, q)| (*p, *q))
+    }
+
+    pub fn best_ask(&self) -> Option<(Price, Qty)> {
+        self.asks.iter().next().map(|(p, q)| (*p, *q))
+    }
+
+    pub fn mid_price(&self) -> Option<f64> {
+        let (bid, _) = self.best_bid()?;
+        let (ask, _) = self.best_ask()?;
+        Some((bid.to_f64() + ask.to_f64()) / 2.0)
+    }
+
+    pub fn spread(&self) -> Option<f64> {
+        let (bid, _) = self.best_bid()?;
+        let (ask, _) = self.best_ask()?;
+        Some(ask.to_f64() - bid.to_f64())
+    }
+
+    pub fn spread_bps(&self) -> Option<f64> {
+        Some(self.spread()? / self.mid_price()? * 10000.0)
+    }
+
+    /// Top of book mid weighted by the opposite sizes, leaning towards the thinner side.
+    pub fn microprice(&self) -> Option<f64> {
+        let (bid, bid_size) = self.best_bid()?;
+        let (ask, ask_size) = self.best_ask()?;
+        let total = bid_size.to_f64() + ask_size.to_f64();
+        if total <= 0.0 {
+            return self.mid_price();
+        }
+        Some((bid.to_f64() * ask_size.to_f64() + ask.to_f64() * bid_size.to_f64()) / total)
+    }
+
+    /// Average of the size-weighted prices of the first `levels` bids and asks.
+    pub fn weighted_mid(&self, levels: usize) -> Option<f64> {
+        let vwap = |side: Side| {
+            let (notional, size) = self
+                .levels(side)
+                .take(levels)
+                .fold((0.0, 0.0), |(n, s), (p, q)| {
+                    (n + q.notional(*p), s + q.to_f64())
+                });
+            if size > 0.0 {
+                Some(notional / size)
+            } else {
+                None
+            }
+        };
+        Some((vwap(Side::Buy)? + vwap(Side::Sell)?) / 2.0)
+    }
+
+    /// Size resting within `bps` of the mid on one side.
+    pub fn depth_within_bps(&self, side: Side, bps: f64) -> Qty {
+        let mid = match self.mid_price() {
+            Some(mid) => mid,
+            None => return Qty::ZERO,
+        };
+        let distance = mid * bps / 10000.0;
+        self.levels(side)
+            .take_while(|(p, _)| (p.to_f64() - mid).abs() <= distance)
+            .map(|(_, q)| *q)
+            .sum()
+    }
+
+    /// Size resting within `ticks` ticks of the touch on one side, the touch included.
+    pub fn depth_within_ticks(&self, side: Side, ticks: u32, tick_size: Price) -> Qty {
+        let touch = match self.levels(side).next() {
+            Some((p, _)) => *p,
+            None => return Qty::ZERO,
+        };
+        let distance = Price::from_units(tick_size.units() * ticks as i64);
+        self.levels(side)
+            .take_while(|(p, _)| (**p - touch).abs() <= distance)
+            .map(|(_, q)| *q)
+            .sum()
+    }
+
+    /// `(bid - ask) / (bid + ask)` over the first `levels`, in [-1, 1].
+    pub fn imbalance(&self, levels: usize) -> Option<f64> {
+        let bid: Qty = self.levels(Side::Buy).take(levels).map(|(_, q)| *q).sum();
+        let ask: Qty = self.levels(Side::Sell).take(levels).map(|(_, q)| *q).sum();
+        Self::normalized_difference(bid.to_f64(), ask.to_f64())
+    }
+
+    /// `(bid - ask) / (bid + ask)` over the depth within `bps` of the mid.
+    pub fn imbalance_within_bps(&self, bps: f64) -> Option<f64> {
+        Self::normalized_difference(
+            self.depth_within_bps(Side::Buy, bps).to_f64(),
+            self.depth_within_bps(Side::Sell, bps).to_f64(),
+        )
+    }
+
+    /// Inverse-distance weighted imbalance of the first `levels`. Size near the mid
+    /// counts more than size deep in the book.
+    pub fn book_pressure(&self, levels: usize) -> Option<f64> {
+        let mid = self.mid_price()?;
+        let pressure = |side: Side| -> f64 {
+            self.levels(side)
+                .take(levels)
+                .map(|(p, q)| q.to_f64() / ((p.to_f64() - mid).abs() / mid * 10000.0 + 1.0))
+                .sum()
+        };
+        Self::normalized_difference(pressure(Side::Buy), pressure(Side::Sell))
+    }
+
+    /// Price of taking `amount` with a market order on `side`: `Buy` sweeps the asks.
+    /// `filled` is below `amount` when the book is too thin.
+    pub fn cost_to_trade(&self, side: Side, amount: Qty) -> Option<Sweep> {
+        let mid = self.mid_price()?;
+        let mut remaining = amount;
+        let mut notional = 0.0;
+        let mut worst_price = None;
+        let mut levels = 0;
+        for (price, size) in self.levels(side.opposite()) {
+            if remaining <= Qty::ZERO {
+                break;
+            }
+            let take = (*size).min(remaining);
+            notional += take.notional(*price);
+            remaining -= take;
+            worst_price = Some(*price);
+            levels += 1;
+        }
+        let filled = amount - remaining;
+        if filled <= Qty::ZERO {
+            return None;
+        }
+        let vwap = notional / filled.to_f64();
+        Some(Sweep {
+            filled,
+            notional,
+            vwap,
+            worst_price: worst_price?,
+            slippage_bps: side.sign() as f64 * (vwap - mid) / mid * 10000.0,
+            levels,
+        })
+    }
+
+    pub fn level_count(&self, side: Side) -> usize {
+        match side {
+            Side::Buy => self.bids.len(),
+            Side::Sell => self.asks.len(),
+        }
+    }
+
+    pub fn level_stats(&self, side: Side, levels: usize) -> LevelStats {
+        let sizes: Vec<Qty> = self.levels(side).take(levels).map(|(_, q)| *q).collect();
+        let total: Qty = sizes.iter().copied().sum();
+        LevelStats {
+            count: sizes.len(),
+            total,
+            mean: if sizes.is_empty() {
+                0.0
+            } else {
+                total.to_f64() / sizes.len() as f64
+            },
+            max: sizes.iter().copied().max().unwrap_or_default(),
+            min: sizes.iter().copied().min().unwrap_or_default(),
+        }
+    }
+
+    fn normalized_difference(bid: f64, ask: f64) -> Option<f64> {
+        if bid + ask > 0.0 {
+            Some((bid - ask) / (bid + ask))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::OffsetDateTime;
+
+    fn book(bids: &[(f64, f64)], asks: &[(f64, f64)]) -> MarketBook {
+        let ladder = |levels: &[(f64, f64)]| {
+            levels
+                .iter()
+                .map(|(p, q)| (Price::from_f64(*p), Qty::from_f64(*q)))
+                .collect()
+        };
+        MarketBook {
+            timestamp: OffsetDateTime::UNIX_EPOCH,
+            market_created_timestamp: OffsetDateTime::UNIX_EPOCH,
+            received_timestamp: OffsetDateTime::UNIX_EPOCH,
+            sym: "FXBTCJPY".to_string(),
+            venue: "bitflyer".to_string(),
+            category: "lightning".to_string(),
+            asks: ladder(asks),
+            bids: ladder(bids),
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        }
+    }
+
+    /// Bids 99 x 1, 98 x 2, 97 x 3 and asks 101 x 0.5, 102 x 1.5, 103 x 4.
+    fn ladder() -> MarketBook {
+        book(
+            &[(97.0, 3.0), (98.0, 2.0), (99.0, 1.0)],
+            &[(101.0, 0.5), (102.0, 1.5), (103.0, 4.0)],
+        )
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn top_of_book() {
+        let book = ladder();
+        assert_eq!(
+            book.best_bid(),
+            Some((Price::from_f64(99.0), Qty::from_f64(1.0)))
+        );
+        assert_eq!(
+            book.best_ask(),
+            Some((Price::from_f64(101.0), Qty::from_f64(0.5)))
+        );
+        assert_eq!(book.mid_price(), Some(100.0));
+        assert_eq!(book.spread(), Some(2.0));
+        assert_eq!(book.spread_bps(), Some(200.0));
+        // leans towards the thinner ask
+        assert_close(book.microprice().unwrap(), (99.0 * 0.5 + 101.0) / 1.5);
+        assert_close(
+            book.weighted_mid(2).unwrap(),
+            ((99.0 + 98.0 * 2.0) / 3.0 + (101.0 * 0.5 + 102.0 * 1.5) / 2.0) / 2.0,
+        );
+        let levels: Vec<f64> = book.levels(Side::Buy).map(|(p, _)| p.to_f64()).collect();
+        assert_eq!(levels, vec![99.0, 98.0, 97.0]);
+    }
+
+    #[test]
+    fn depth_and_imbalance_at_n_levels() {
+        let book = ladder();
+        assert_close(book.imbalance(1).unwrap(), 0.5 / 1.5);
+        assert_close(book.imbalance(2).unwrap(), 1.0 / 5.0);
+        assert_close(book.imbalance(3).unwrap(), 0.0);
+        assert_close(book.imbalance(10).unwrap(), 0.0);
+
+        assert_eq!(book.depth_within_bps(Side::Buy, 100.0), Qty::from_f64(1.0));
+        assert_eq!(book.depth_within_bps(Side::Buy, 200.0), Qty::from_f64(3.0));
+        assert_eq!(book.depth_within_bps(Side::Sell, 50.0), Qty::ZERO);
+        assert_close(book.imbalance_within_bps(100.0).unwrap(), 0.5 / 1.5);
+
+        let tick = Price::from_f64(1.0);
+        assert_eq!(
+            book.depth_within_ticks(Side::Sell, 0, tick),
+            Qty::from_f64(0.5)
+        );
+        assert_eq!(
+            book.depth_within_ticks(Side::Sell, 1, tick),
+            Qty::from_f64(2.0)
+        );
+
+        let pressure = book.book_pressure(3).unwrap();
+        assert!(pressure > 0.0 && pressure < 1.0, "{}", pressure);
+
+        let stats = book.level_stats(Side::Sell, 2);
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.total, Qty::from_f64(2.0));
+        assert_eq!(stats.mean, 1.0);
+        assert_eq!(stats.max, Qty::from_f64(1.5));
+        assert_eq!(stats.min, Qty::from_f64(0.5));
+        assert_eq!(book.level_count(Side::Buy), 3);
+    }
+
+    #[test]
+    fn vwap_and_slippage_to_size() {
+        let book = ladder();
+        let sweep = book.cost_to_trade(Side::Buy, Qty::from_f64(1.0)).unwrap();
+        assert_eq!(sweep.filled, Qty::from_f64(1.0));
+        assert_close(sweep.notional, 101.5);
+        assert_close(sweep.vwap, 101.5);
+        assert_eq!(sweep.worst_price, Price::from_f64(102.0));
+        assert_eq!(sweep.levels, 2);
+        assert_close(sweep.slippage_bps, 150.0);
+
+        // sweeping the bids, short of size
+        let sweep = book.cost_to_trade(Side::Sell, Qty::from_f64(10.0)).unwrap();
+        assert_eq!(sweep.filled, Qty::from_f64(6.0));
+        assert_close(sweep.vwap, 586.0 / 6.0);
+        assert_eq!(sweep.worst_price, Price::from_f64(97.0));
+        assert_eq!(sweep.levels, 3);
+        assert_close(sweep.slippage_bps, (100.0 - 586.0 / 6.0) / 100.0 * 10000.0);
+
+        // within the touch
+        let sweep = book.cost_to_trade(Side::Buy, Qty::from_f64(0.2)).unwrap();
+        assert_close(sweep.vwap, 101.0);
+        assert_eq!(sweep.levels, 1);
+    }
+
+    #[test]
+    fn one_sided_and_empty_books() {
+        let bids_only = book(&[(99.0, 1.0)], &[]);
+        assert!(bids_only.mid_price().is_none());
+        assert!(bids_only.microprice().is_none());
+        assert!(bids_only
+            .cost_to_trade(Side::Buy, Qty::from_f64(1.0))
+            .is_none());
+        assert_eq!(bids_only.depth_within_bps(Side::Buy, 100.0), Qty::ZERO);
+        assert_eq!(bids_only.imbalance(1), Some(1.0));
+        let empty = book(&[], &[]);
+        assert!(empty.imbalance(5).is_none());
+        assert_eq!(empty.level_stats(Side::Buy, 5).count, 0);
+    }
+}
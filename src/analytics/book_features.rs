@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+use crate::datamodels::enums::MarketData;
+use crate::datamodels::marketdata::MarketBook;
+use crate::datamodels::types::{Price, Qty, Side};
+
+/// Book features of one `MarketBook` update, see `BookFeatureGenerator`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BookFeatures {
+    #[serde(with = "time::serde::rfc3339")]
+    pub market_created_timestamp: OffsetDateTime,
+    pub sym: String,
+    pub venue: String,
+    pub mid_price: f64,
+    pub microprice: f64,
+    pub weighted_mid: f64,
+    pub spread_bps: f64,
+    /// Imbalance of the first level and of the first `levels`.
+    pub top_imbalance: f64,
+    pub imbalance: f64,
+    pub bid_depth: f64,
+    pub ask_depth: f64,
+    pub book_pressure: f64,
+    /// Top of book order flow imbalance since the previous update.
+    pub order_flow_imbalance: f64,
+    pub mid_return_bps: f64,
+}
+
+struct TopOfBook {
+    bid: (Price, Qty),
+    ask: (Price, Qty),
+    mid: f64,
+}
+
+/// Streaming `BookFeatures` from `MarketData::MarketBook`, keeping only the previous
+/// top of book per venue and sym.
+pub struct BookFeatureGenerator {
+    levels: usize,
+    depth_bps: f64,
+    previous: HashMap<(String, String), TopOfBook>,
+}
+
+impl BookFeatureGenerator {
+    /// `levels` used by the level-based features, `depth_bps` by the depth features.
+    pub fn new(levels: usize, depth_bps: f64) -> Self {
+        Self {
+            levels,
+            depth_bps,
+            previous: HashMap::new(),
+        }
+    }
+
+    /// Features of a book update. `None` for other data and one-sided books.
+    pub fn next(&mut self, data: &MarketData) -> Option<BookFeatures> {
+        match data {
+            MarketData::MarketBook(book) => self.compute(book),
+            _ => None,
+        }
+    }
+
+    fn compute(&mut self, book: &MarketBook) -> Option<BookFeatures> {
+        let top = TopOfBook {
+            bid: book.best_bid()?,
+            ask: book.best_ask()?,
+            mid: book.mid_price()?,
+        };
+        let key = (book.venue.clone(), book.sym.clone());
+        let (order_flow_imbalance, mid_return_bps) = match self.previous.get(&key) {
+            Some(previous) => (
+                Self::order_flow_imbalance(previous, &top),
+                (top.mid - previous.mid) / previous.mid * 10000.0,
+            ),
+            None => (0.0, 0.0),
+        };
+        let features = BookFeatures {
+            market_created_timestamp: book.market_created_timestamp,
+            sym: book.sym.clone(),
+            venue: book.venue.clone(),
+            mid_price: top.mid,
+            microprice: book.microprice()?,
+            weighted_mid: book.weighted_mid(self.levels)?,
+            spread_bps: book.spread_bps()?,
+            top_imbalance: book.imbalance(1).unwrap_or(0.0),
+            imbalance: book.imbalance(self.levels).unwrap_or(0.0),
+            bid_depth: book.depth_within_bps(Side::Buy, self.depth_bps).to_f64(),
+            ask_depth: book.depth_within_bps(Side::Sell, self.depth_bps).to_f64(),
+            book_pressure: book.book_pressure(self.levels).unwrap_or(0.0),
+            order_flow_imbalance,
+            mid_return_bps,
+        };
+        self.previous.insert(key, top);
+        Some(features)
+    }
+
+    /// Cont, Kukanov and Stoikov top of book OFI: size added on the bid minus size added on the ask.
+    fn order_flow_imbalance(previous: &TopOfBook, current: &TopOfBook) -> f64 {
+        let flow = |(prev_price, prev_size): (Price, Qty), (price, size): (Price, Qty), sign| {
+            let improved = if sign > 0.0 {
+                price > prev_price
+            } else {
+                price < prev_price
+            };
+            if price == prev_price {
+                size.to_f64() - prev_size.to_f64()
+            } else if improved {
+                size.to_f64()
+            } else {
+                -prev_size.to_f64()
+            }
+        };
+        flow(previous.bid, current.bid, 1.0) - flow(previous.ask, current.ask, -1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodels::id_generator::IdSource;
+
+    fn book(bid: (f64, f64), ask: (f64, f64)) -> MarketBook {
+        let level = |(p, q): (f64, f64)| [(Price::from_f64(p), Qty::from_f64(q))].into();
+        MarketBook {
+            timestamp: OffsetDateTime::UNIX_EPOCH,
+            market_created_timestamp: OffsetDateTime::UNIX_EPOCH,
+            received_timestamp: OffsetDateTime::UNIX_EPOCH,
+            sym: "FXBTCJPY".to_string(),
+            venue: "bitflyer".to_string(),
+            category: "lightning".to_string(),
+            asks: level(ask),
+            bids: level(bid),
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        }
+    }
+
+    fn update(bid: (f64, f64), ask: (f64, f64)) -> MarketData {
+        MarketData::MarketBook(book(bid, ask))
+    }
+
+    #[test]
+    fn streaming_features_follow_the_top_of_book() {
+        let mut generator = BookFeatureGenerator::new(5, 100.0);
+        let first = generator.next(&update((99.0, 1.0), (101.0, 0.5))).unwrap();
+        assert_eq!(first.mid_price, 100.0);
+        assert_eq!(first.spread_bps, 200.0);
+        assert_eq!(first.bid_depth, 1.0);
+        assert_eq!(first.ask_depth, 0.5);
+        assert!((first.top_imbalance - 0.5 / 1.5).abs() < 1e-9);
+        assert_eq!(first.imbalance, first.top_imbalance);
+        assert_eq!(first.order_flow_imbalance, 0.0);
+        assert_eq!(first.mid_return_bps, 0.0);
+
+        // size added on the bid
+        let second = generator.next(&update((99.0, 2.0), (101.0, 0.5))).unwrap();
+        assert_eq!(second.order_flow_imbalance, 1.0);
+        assert_eq!(second.mid_return_bps, 0.0);
+
+        // bid improves, ask pulled back
+        let third = generator.next(&update((100.0, 0.7), (102.0, 1.0))).unwrap();
+        assert!((third.order_flow_imbalance - (0.7 + 0.5)).abs() < 1e-9);
+        assert_eq!(third.mid_price, 101.0);
+        assert_eq!(third.mid_return_bps, 100.0);
+    }
+
+    #[test]
+    fn other_data_and_one_sided_books_give_no_features() {
+        let mut generator = BookFeatureGenerator::new(5, 100.0);
+        let mut one_sided = book((99.0, 1.0), (101.0, 0.5));
+        one_sided.asks.clear();
+        assert!(generator.next(&MarketData::MarketBook(one_sided)).is_none());
+        let rate = MarketData::Rate(book((99.0, 1.0), (101.0, 0.5)).to_rate(&IdSource::default()));
+        assert!(generator.next(&rate).is_none());
+    }
+}
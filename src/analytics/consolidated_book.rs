@@ -0,0 +1,509 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+
+use crate::constants::constants;
+use crate::datamodels::enums::MarketData;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::marketdata::{MarketBook, Rate};
+use crate::datamodels::types::{Price, Qty, Side};
+use crate::refdata::instrument::{ContractType, InstrumentRegistry};
+
+const VENUE: &str = "consolidated";
+
+/// One price level of the merged ladder, attributed to the venue quoting it.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ConsolidatedLevel {
+    pub price: Price,
+    pub amount: Qty,
+    pub venue: String,
+    pub sym: String,
+}
+
+/// Best bid and offer across venues, prices in the consolidated quote currency.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Bbo {
+    pub bid: ConsolidatedLevel,
+    pub ask: ConsolidatedLevel,
+}
+
+struct VenueBook {
+    book: MarketBook,
+    quote: String,
+}
+
+/// Consolidated view of one asset over every venue recorded.
+///
+/// Keeps the latest `MarketBook` of each instrument with the given `base` and `contract_type`
+/// and converts its prices into `quote`. Books quoted in another currency need a conversion
+/// rate, either from `set_quote_rate` or from the `Rate`s of a `<currency><quote>` instrument.
+/// Books not received within `stale_after` of the latest market data received are dropped.
+/// Staleness is measured on the local clock, exchange times may be estimated or skewed per venue.
+pub struct ConsolidatedBook {
+    sym: String,
+    base: String,
+    quote: String,
+    contract_type: ContractType,
+    stale_after: Duration,
+    instruments: Arc<InstrumentRegistry>,
+    quote_rates: HashMap<String, f64>,
+    books: BTreeMap<(String, String), VenueBook>,
+    market_created_timestamp: OffsetDateTime,
+    received_timestamp: OffsetDateTime,
+    latest_received: OffsetDateTime,
+    last_bbo: Option<(Price, Price)>,
+    ids: IdSource,
+}
+
+impl ConsolidatedBook {
+    pub fn new(
+        instruments: Arc<InstrumentRegistry>,
+        base: &str,
+        quote: &str,
+        contract_type: ContractType,
+        stale_after: Duration,
+    ) -> Self {
+        Self {
+            sym: format!("{}{}", base, quote),
+            base: base.to_string(),
+            quote: quote.to_string(),
+            contract_type,
+            stale_after,
+            instruments,
+            quote_rates: HashMap::new(),
+            books: BTreeMap::new(),
+            market_created_timestamp: OffsetDateTime::UNIX_EPOCH,
+            received_timestamp: OffsetDateTime::UNIX_EPOCH,
+            latest_received: OffsetDateTime::UNIX_EPOCH,
+            last_bbo: None,
+            ids: IdSource::default(),
+        }
+    }
+
+    /// Source of the ids of the consolidated records, random by default.
+    pub fn set_ids(&mut self, ids: IdSource) {
+        self.ids = ids;
+    }
+
+    pub fn sym(&self) -> &str {
+        &self.sym
+    }
+
+    /// Value of one unit of `currency` in the consolidated quote currency.
+    pub fn set_quote_rate(&mut self, currency: &str, rate: f64) {
+        self.quote_rates.insert(currency.to_string(), rate);
+    }
+
+    /// Consolidated BBO `Rate` when `data` moved it. `misc` names the venue of each side.
+    pub fn next(&mut self, data: &MarketData) -> Option<Rate> {
+        self.market_created_timestamp = self
+            .market_created_timestamp
+            .max(data.market_created_timestamp());
+        self.latest_received = self.latest_received.max(data.received_timestamp());
+        match data {
+            MarketData::MarketBook(book) => self.update_book(book),
+            MarketData::Rate(rate) => {
+                self.update_quote_rate(&rate.venue, &rate.sym, rate.mid_price)
+            }
+            MarketData::Ticker(ticker) => {
+                self.update_quote_rate(&ticker.venue, &ticker.sym, ticker.mid_price)
+            }
+            _ => {}
+        }
+        self.drop_stale();
+
+        let bbo = self.bbo();
+        let top = bbo.as_ref().map(|bbo| (bbo.bid.price, bbo.ask.price));
+        if top == self.last_bbo {
+            return None;
+        }
+        self.last_bbo = top;
+        bbo.map(|bbo| self.to_rate(&bbo))
+    }
+
+    fn update_book(&mut self, book: &MarketBook) {
+        let instrument = match self.instruments.get(&book.venue, &book.sym) {
+            Some(instrument) => instrument,
+            None => return,
+        };
+        if instrument.base != self.base || instrument.contract_type != self.contract_type {
+            return;
+        }
+        self.received_timestamp = book.received_timestamp;
+        self.books.insert(
+            (book.venue.clone(), book.sym.clone()),
+            VenueBook {
+                book: book.clone(),
+                quote: instrument.quote.clone(),
+            },
+        );
+    }
+
+    fn update_quote_rate(&mut self, venue: &str, sym: &str, mid_price: f64) {
+        if let Some(instrument) = self.instruments.get(venue, sym) {
+            if instrument.quote == self.quote
+                && instrument.base != self.base
+                && mid_price.is_finite()
+            {
+                self.quote_rates.insert(instrument.base.clone(), mid_price);
+            }
+        }
+    }
+
+    fn drop_stale(&mut self) {
+        let cutoff = self.latest_received - self.stale_after;
+        self.books.retain(|(venue, sym), venue_book| {
+            let fresh = venue_book.book.received_timestamp >= cutoff;
+            if !fresh {
+                info!("Drop stale {} book of {} from {}", sym, venue, VENUE);
+            }
+            fresh
+        });
+    }
+
+    fn quote_rate(&self, currency: &str) -> Option<f64> {
+        if currency == self.quote {
+            Some(1.0)
+        } else {
+            self.quote_rates.get(currency).copied()
+        }
+    }
+
+    fn normalize(price: Price, rate: f64) -> Price {
+        if rate == 1.0 {
+            price
+        } else {
+            Price::from_f64(price.to_f64() * rate)
+        }
+    }
+
+    /// Levels of one side over every venue from the touch outwards. Books without a
+    /// conversion rate are left out.
+    pub fn levels(&self, side: Side) -> Vec<ConsolidatedLevel> {
+        let mut levels: Vec<ConsolidatedLevel> = self
+            .books
+            .iter()
+            .filter_map(|(key, venue_book)| {
+                Some((key, venue_book, self.quote_rate(&venue_book.quote)?))
+            })
+            .flat_map(|((venue, sym), venue_book, rate)| {
+                venue_book
+                    .book
+                    .levels(side)
+                    .map(move |(price, amount)| ConsolidatedLevel {
+                        price: Self::normalize(*price, rate),
+                        amount: *amount,
+                        venue: venue.clone(),
+                        sym: sym.clone(),
+                    })
+            })
+            .collect();
+        match side {
+            Side::Buy => levels.sort_by_key(|level| std::cmp::Reverse(level.price)),
+            Side::Sell => levels.sort_by_key(|level| level.price),
+        }
+        levels
+    }
+
+    pub fn bbo(&self) -> Option<Bbo> {
+        let best = |side: Side| -> Option<ConsolidatedLevel> {
+            self.books
+                .iter()
+                .filter_map(|((venue, sym), venue_book)| {
+                    let rate = self.quote_rate(&venue_book.quote)?;
+                    let (price, amount) = venue_book.book.levels(side).next()?;
+                    Some(ConsolidatedLevel {
+                        price: Self::normalize(*price, rate),
+                        amount: *amount,
+                        venue: venue.clone(),
+                        sym: sym.clone(),
+                    })
+                })
+                .reduce(|best, level| {
+                    let better = match side {
+                        Side::Buy => level.price > best.price,
+                        Side::Sell => level.price < best.price,
+                    };
+                    if better {
+                        level
+                    } else {
+                        best
+                    }
+                })
+        };
+        Some(Bbo {
+            bid: best(Side::Buy)?,
+            ask: best(Side::Sell)?,
+        })
+    }
+
+    /// Depth of every venue summed per normalized price.
+    pub fn merged_book(&self) -> MarketBook {
+        let ladder = |side: Side| -> BTreeMap<Price, Qty> {
+            let mut ladder = BTreeMap::new();
+            for level in self.levels(side) {
+                *ladder.entry(level.price).or_insert(Qty::ZERO) += level.amount;
+            }
+            ladder
+        };
+        MarketBook {
+            timestamp: OffsetDateTime::now_utc(),
+            market_created_timestamp: self.market_created_timestamp,
+            received_timestamp: self.received_timestamp,
+            sym: self.sym.clone(),
+            venue: VENUE.to_string(),
+            category: format!("{:?}", self.contract_type).to_lowercase(),
+            asks: ladder(Side::Sell),
+            bids: ladder(Side::Buy),
+            misc: self
+                .books
+                .keys()
+                .map(|(venue, sym)| format!("{}:{}", venue, sym))
+                .collect::<Vec<_>>()
+                .join(";"),
+            universal_id: self.ids.next_id(),
+            data_center: constants::MACHINE_ID.to_string(),
+            process_id: self.ids.runtime_id().to_string(),
+        }
+    }
+
+    fn to_rate(&self, bbo: &Bbo) -> Rate {
+        let best_bid = bbo.bid.price.to_f64();
+        let best_ask = bbo.ask.price.to_f64();
+        Rate {
+            timestamp: OffsetDateTime::now_utc(),
+            market_created_timestamp: self.market_created_timestamp,
+            received_timestamp: self.received_timestamp,
+            sym: self.sym.clone(),
+            venue: VENUE.to_string(),
+            category: format!("{:?}", self.contract_type).to_lowercase(),
+            best_bid,
+            best_ask,
+            mid_price: (best_bid + best_ask) / 2.0,
+            misc: format!(
+                "bid={}:{};ask={}:{}",
+                bbo.bid.venue, bbo.bid.sym, bbo.ask.venue, bbo.ask.sym
+            ),
+            universal_id: self.ids.next_id(),
+            data_center: constants::MACHINE_ID.to_string(),
+            process_id: self.ids.runtime_id().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodels::marketdata::Ticker;
+
+    const INSTRUMENTS: &str = r#"
+        [[instrument]]
+        venue = "bitflyer"
+        native_symbol = "BTC_JPY"
+        sym = "BTCJPY"
+        base = "BTC"
+        quote = "JPY"
+        tick_size = 1.0
+        lot_size = 0.00000001
+        min_size = 0.001
+        contract_type = "Spot"
+
+        [[instrument]]
+        venue = "coincheck"
+        native_symbol = "btc_jpy"
+        sym = "BTCJPY"
+        base = "BTC"
+        quote = "JPY"
+        tick_size = 1.0
+        lot_size = 0.00000001
+        min_size = 0.001
+        contract_type = "Spot"
+
+        [[instrument]]
+        venue = "binance"
+        native_symbol = "BTCUSDT"
+        sym = "BTCUSDT"
+        base = "BTC"
+        quote = "USDT"
+        tick_size = 0.01
+        lot_size = 0.00001
+        min_size = 0.00001
+        contract_type = "Spot"
+
+        [[instrument]]
+        venue = "bitflyer"
+        native_symbol = "FX_BTC_JPY"
+        sym = "FXBTCJPY"
+        base = "BTC"
+        quote = "JPY"
+        tick_size = 1.0
+        lot_size = 0.00000001
+        min_size = 0.01
+        contract_type = "Fx"
+
+        [[instrument]]
+        venue = "fxvenue"
+        native_symbol = "USDT_JPY"
+        sym = "USDTJPY"
+        base = "USDT"
+        quote = "JPY"
+        tick_size = 0.001
+        lot_size = 1.0
+        min_size = 1.0
+        contract_type = "Spot"
+    "#;
+
+    fn consolidated() -> ConsolidatedBook {
+        let instruments = InstrumentRegistry::from_toml(INSTRUMENTS).unwrap();
+        ConsolidatedBook::new(
+            Arc::new(instruments),
+            "BTC",
+            "JPY",
+            ContractType::Spot,
+            Duration::seconds(5),
+        )
+    }
+
+    fn at(secs: i64) -> OffsetDateTime {
+        OffsetDateTime::UNIX_EPOCH + Duration::seconds(secs)
+    }
+
+    /// Book received at `received` seconds whose exchange time is `created` seconds.
+    fn book(venue: &str, sym: &str, bid: f64, ask: f64, received: i64, created: i64) -> MarketData {
+        let level = |price: f64| [(Price::from_f64(price), Qty::from_f64(1.0))].into();
+        MarketData::MarketBook(MarketBook {
+            timestamp: at(received),
+            market_created_timestamp: at(created),
+            received_timestamp: at(received),
+            sym: sym.to_string(),
+            venue: venue.to_string(),
+            category: "".to_string(),
+            asks: level(ask),
+            bids: level(bid),
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        })
+    }
+
+    fn rate(venue: &str, sym: &str, mid_price: f64, received: i64) -> MarketData {
+        MarketData::Rate(Rate {
+            timestamp: at(received),
+            market_created_timestamp: at(received),
+            received_timestamp: at(received),
+            sym: sym.to_string(),
+            venue: venue.to_string(),
+            category: "".to_string(),
+            best_bid: mid_price,
+            best_ask: mid_price,
+            mid_price,
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        })
+    }
+
+    #[test]
+    fn bbo_sides_are_attributed_to_their_venue() {
+        let mut consolidated = consolidated();
+        let first = consolidated
+            .next(&book("bitflyer", "BTCJPY", 100.0, 102.0, 0, 0))
+            .unwrap();
+        assert_eq!(first.misc, "bid=bitflyer:BTCJPY;ask=bitflyer:BTCJPY");
+        let rate = consolidated
+            .next(&book("coincheck", "BTCJPY", 101.0, 102.0, 1, 1))
+            .unwrap();
+        assert_eq!(rate.venue, "consolidated");
+        assert_eq!(rate.sym, "BTCJPY");
+        assert_eq!((rate.best_bid, rate.best_ask), (101.0, 102.0));
+        assert_eq!(rate.misc, "bid=coincheck:BTCJPY;ask=bitflyer:BTCJPY");
+        // BBO unchanged
+        assert!(consolidated
+            .next(&book("bitflyer", "BTCJPY", 99.0, 102.0, 2, 2))
+            .is_none());
+
+        let bids = consolidated.levels(Side::Buy);
+        let venues: Vec<&str> = bids.iter().map(|l| l.venue.as_str()).collect();
+        assert_eq!(venues, vec!["coincheck", "bitflyer"]);
+        let merged = consolidated.merged_book();
+        assert_eq!(merged.asks[&Price::from_f64(102.0)], Qty::from_f64(2.0));
+        assert_eq!(merged.misc, "bitflyer:BTCJPY;coincheck:BTCJPY");
+
+        // other contract types and unknown instruments are ignored
+        assert!(consolidated
+            .next(&book("bitflyer", "FXBTCJPY", 110.0, 111.0, 3, 3))
+            .is_none());
+        assert!(consolidated
+            .next(&book("unknown", "BTCJPY", 110.0, 111.0, 3, 3))
+            .is_none());
+    }
+
+    #[test]
+    fn foreign_quotes_are_normalized() {
+        let mut consolidated = consolidated();
+        consolidated.next(&book("bitflyer", "BTCJPY", 100.0, 110.0, 0, 0));
+        // no USDT rate yet, left out
+        assert!(consolidated
+            .next(&book("binance", "BTCUSDT", 0.7, 0.8, 0, 0))
+            .is_none());
+        assert_eq!(consolidated.levels(Side::Buy).len(), 1);
+
+        consolidated.set_quote_rate("USDT", 150.0);
+        let bbo_rate = consolidated.next(&rate("bitflyer", "BTCJPY", 1.0, 0));
+        assert!(bbo_rate.is_some());
+        let bbo = consolidated.bbo().unwrap();
+        assert_eq!(bbo.bid.price, Price::from_f64(105.0));
+        assert_eq!(bbo.bid.venue, "binance");
+        assert_eq!(bbo.ask.price, Price::from_f64(110.0));
+
+        // the rates of a USDTJPY instrument update the conversion
+        let bbo_rate = consolidated
+            .next(&rate("fxvenue", "USDTJPY", 130.0, 1))
+            .unwrap();
+        assert_eq!(bbo_rate.best_bid, 100.0);
+        assert_eq!(bbo_rate.best_ask, 104.0);
+        let ticker = Ticker {
+            timestamp: at(2),
+            market_created_timestamp: at(2),
+            received_timestamp: at(2),
+            sym: "USDTJPY".to_string(),
+            venue: "fxvenue".to_string(),
+            category: "".to_string(),
+            best_bid: 159.9,
+            best_ask: 160.1,
+            best_bid_size: 1.0,
+            best_ask_size: 1.0,
+            total_bid_depth: 1.0,
+            total_ask_depth: 1.0,
+            mid_price: 160.0,
+            ltp: 160.0,
+            volume: 0.0,
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        };
+        let bbo_rate = consolidated.next(&MarketData::Ticker(ticker)).unwrap();
+        assert_eq!(bbo_rate.best_bid, 112.0);
+        assert_eq!(bbo_rate.best_ask, 110.0);
+    }
+
+    #[test]
+    fn staleness_is_measured_on_receive_time() {
+        let mut consolidated = consolidated();
+        // bitFlyer board time estimated a minute ahead
+        consolidated.next(&book("bitflyer", "BTCJPY", 100.0, 103.0, 0, 60));
+        consolidated.next(&book("coincheck", "BTCJPY", 101.0, 102.0, 1, 1));
+        assert_eq!(consolidated.levels(Side::Buy).len(), 2);
+
+        let rate = consolidated
+            .next(&book("coincheck", "BTCJPY", 101.0, 104.0, 6, 6))
+            .unwrap();
+        assert_eq!(rate.misc, "bid=coincheck:BTCJPY;ask=coincheck:BTCJPY");
+        assert_eq!(consolidated.levels(Side::Sell).len(), 1);
+    }
+}
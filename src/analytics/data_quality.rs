@@ -0,0 +1,640 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use time::{Date, Duration, OffsetDateTime, UtcOffset};
+
+use crate::datamodels::enums::MarketData;
+use crate::refdata::calendar::MaintenanceCalendar;
+
+/// Thresholds of `DataQuality`.
+#[derive(Clone, Debug)]
+pub struct QualityConfig {
+    /// Silences of a channel longer than this many seconds are gaps.
+    pub gap_secs: f64,
+    /// Prices further than this many percent from the median of the previous ones are outliers.
+    pub outlier_pct: f64,
+    /// Number of previous prices of the median.
+    pub outlier_window: usize,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            gap_secs: 60.0,
+            outlier_pct: 5.0,
+            outlier_window: 20,
+        }
+    }
+}
+
+/// Silence of a channel between two records.
+#[derive(Serialize, Clone, Debug)]
+pub struct Gap {
+    #[serde(with = "time::serde::rfc3339")]
+    pub start: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub end: OffsetDateTime,
+    pub seconds: f64,
+    /// The gap overlaps a maintenance window of the venue.
+    pub maintenance: bool,
+}
+
+/// Quality of a (kind, venue, sym) stream over a day, by `market_created_timestamp`.
+#[derive(Serialize, Clone, Debug)]
+pub struct ChannelQuality {
+    pub kind: String,
+    pub venue: String,
+    pub sym: String,
+    pub records: u64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub first: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last: OffsetDateTime,
+    pub gaps: Vec<Gap>,
+    /// Records older than a previous record of the channel.
+    pub non_monotonic: u64,
+    /// `MarketTrade` only, duplicates are detected within the day.
+    pub duplicate_trade_ids: u64,
+    /// Books, rates and tickers with a best bid at or above the best ask.
+    pub crossed_books: u64,
+    pub outlier_prices: u64,
+}
+
+impl ChannelQuality {
+    fn new(data: &MarketData, timestamp: OffsetDateTime) -> Self {
+        Self {
+            kind: data.kind().to_string(),
+            venue: data.venue().to_string(),
+            sym: data.sym().to_string(),
+            records: 0,
+            first: timestamp,
+            last: timestamp,
+            gaps: vec![],
+            non_monotonic: 0,
+            duplicate_trade_ids: 0,
+            crossed_books: 0,
+            outlier_prices: 0,
+        }
+    }
+
+    /// Gaps not explained by maintenance.
+    pub fn unexpected_gaps(&self) -> impl Iterator<Item = &Gap> {
+        self.gaps.iter().filter(|gap| !gap.maintenance)
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.unexpected_gaps().next().is_none()
+            && self.non_monotonic == 0
+            && self.duplicate_trade_ids == 0
+            && self.crossed_books == 0
+            && self.outlier_prices == 0
+    }
+}
+
+/// Usable and maintenance time of a venue over a day.
+#[derive(Serialize, Clone, Debug)]
+pub struct VenueDay {
+    pub venue: String,
+    pub usable_secs: f64,
+    pub maintenance_secs: f64,
+    pub maintenance: Vec<Interval>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DayReport {
+    /// UTC date, serialized as `YYYY-MM-DD`.
+    #[serde(serialize_with = "serialize_date")]
+    pub date: Date,
+    pub venues: Vec<VenueDay>,
+    pub channels: Vec<ChannelQuality>,
+}
+
+fn serialize_date<S: serde::Serializer>(date: &Date, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(date)
+}
+
+impl DayReport {
+    pub fn is_clean(&self) -> bool {
+        self.channels.iter().all(ChannelQuality::is_clean)
+    }
+}
+
+/// Closed interval `[start, end]`.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    #[serde(with = "time::serde::rfc3339")]
+    pub start: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub end: OffsetDateTime,
+}
+
+impl Interval {
+    pub fn contains(&self, timestamp: OffsetDateTime) -> bool {
+        self.start <= timestamp && timestamp <= self.end
+    }
+
+    pub fn seconds(&self) -> f64 {
+        (self.end - self.start).as_seconds_f64()
+    }
+
+    fn intersect(&self, start: OffsetDateTime, end: OffsetDateTime) -> Option<Interval> {
+        let interval = Interval {
+            start: self.start.max(start),
+            end: self.end.min(end),
+        };
+        (interval.start < interval.end).then_some(interval)
+    }
+}
+
+/// Time ranges per venue where the recording is complete, written by the `quality` command
+/// and honored by `replay` and `backtest`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct UsableIntervals {
+    pub venues: BTreeMap<String, Vec<Interval>>,
+}
+
+impl UsableIntervals {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// Venues without intervals have no usable data.
+    pub fn contains(&self, venue: &str, timestamp: OffsetDateTime) -> bool {
+        self.venues
+            .get(venue)
+            .is_some_and(|intervals| intervals.iter().any(|i| i.contains(timestamp)))
+    }
+}
+
+#[derive(Default)]
+struct ChannelState {
+    last: Option<OffsetDateTime>,
+    day: Option<Date>,
+    trade_ids: HashSet<String>,
+    prices: VecDeque<f64>,
+}
+
+/// Data quality of recorded market data, fed in the order of `MarketDataReplay`.
+///
+/// Checks every (kind, venue, sym) channel for gaps, non monotonic timestamps, duplicate
+/// trade ids, crossed books and outlier prices. Trades come at the pace of the market, so
+/// only gaps of the other kinds, and maintenance windows, are cut out of the usable intervals.
+pub struct DataQuality {
+    config: QualityConfig,
+    calendar: Arc<MaintenanceCalendar>,
+    start: Option<OffsetDateTime>,
+    end: Option<OffsetDateTime>,
+    states: BTreeMap<(String, String, String), ChannelState>,
+    days: BTreeMap<Date, BTreeMap<(String, String, String), ChannelQuality>>,
+    /// First and last record per venue.
+    ranges: BTreeMap<String, (OffsetDateTime, OffsetDateTime)>,
+    /// Gaps per venue, cut out of the usable intervals.
+    cuts: BTreeMap<String, Vec<Interval>>,
+}
+
+impl DataQuality {
+    pub fn new(config: QualityConfig, calendar: Arc<MaintenanceCalendar>) -> Self {
+        Self {
+            config,
+            calendar,
+            start: None,
+            end: None,
+            states: BTreeMap::new(),
+            days: BTreeMap::new(),
+            ranges: BTreeMap::new(),
+            cuts: BTreeMap::new(),
+        }
+    }
+
+    /// Expected range of the data, silences from `start` to the first record and from the
+    /// last record to `end` are gaps too.
+    pub fn between(mut self, start: Option<OffsetDateTime>, end: Option<OffsetDateTime>) -> Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    fn gap(&self, venue: &str, start: OffsetDateTime, end: OffsetDateTime) -> Option<Gap> {
+        let seconds = (end - start).as_seconds_f64();
+        if seconds <= self.config.gap_secs {
+            return None;
+        }
+        Some(Gap {
+            start,
+            end,
+            seconds,
+            maintenance: !self.calendar.windows_between(venue, start, end).is_empty(),
+        })
+    }
+
+    /// Whether `price` is an outlier against the window, then push it into the window.
+    fn is_outlier(config: &QualityConfig, prices: &mut VecDeque<f64>, price: f64) -> bool {
+        if !price.is_finite() || price <= 0.0 {
+            return false;
+        }
+        let mut sorted: Vec<f64> = prices.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        let outlier = match sorted.get(sorted.len() / 2) {
+            Some(median) if sorted.len() >= config.outlier_window / 2 => {
+                (price - median).abs() / median * 100.0 > config.outlier_pct
+            }
+            _ => false,
+        };
+        prices.push_back(price);
+        if prices.len() > config.outlier_window {
+            prices.pop_front();
+        }
+        outlier
+    }
+
+    pub fn add(&mut self, data: &MarketData) {
+        let timestamp = data.market_created_timestamp().to_offset(UtcOffset::UTC);
+        let date = timestamp.date();
+        let key = (
+            data.kind().to_string(),
+            data.venue().to_string(),
+            data.sym().to_string(),
+        );
+        let previous = self.states.get(&key).and_then(|state| state.last);
+        let gap = match previous {
+            Some(previous) => self.gap(data.venue(), previous, timestamp),
+            None => self
+                .start
+                .and_then(|start| self.gap(data.venue(), start, timestamp)),
+        };
+
+        let (first, last) = self
+            .ranges
+            .entry(key.1.clone())
+            .or_insert((timestamp, timestamp));
+        *first = (*first).min(timestamp);
+        *last = (*last).max(timestamp);
+        if let Some(gap) = &gap {
+            if !matches!(data, MarketData::MarketTrade(_)) {
+                self.cuts.entry(key.1.clone()).or_default().push(Interval {
+                    start: gap.start,
+                    end: gap.end,
+                });
+            }
+        }
+
+        let config = &self.config;
+        let state = self.states.entry(key.clone()).or_default();
+        if state.day != Some(date) {
+            state.day = Some(date);
+            state.trade_ids.clear();
+        }
+        let quality = self
+            .days
+            .entry(date)
+            .or_default()
+            .entry(key)
+            .or_insert_with(|| ChannelQuality::new(data, timestamp));
+        quality.records += 1;
+        quality.first = quality.first.min(timestamp);
+        quality.last = quality.last.max(timestamp);
+        quality.gaps.extend(gap);
+        if previous.is_some_and(|previous| timestamp < previous) {
+            quality.non_monotonic += 1;
+        }
+        state.last = Some(previous.map_or(timestamp, |previous| previous.max(timestamp)));
+
+        let (crossed, price) = match data {
+            MarketData::MarketBook(book) => (
+                matches!(
+                    (book.best_bid(), book.best_ask()),
+                    (Some((bid, _)), Some((ask, _))) if bid >= ask
+                ),
+                book.mid_price(),
+            ),
+            MarketData::Rate(rate) => (rate.best_bid >= rate.best_ask, Some(rate.mid_price)),
+            MarketData::Ticker(ticker) => {
+                (ticker.best_bid >= ticker.best_ask, Some(ticker.mid_price))
+            }
+            MarketData::MarketTrade(trade) => {
+                if !trade.trade_id.is_empty() && !state.trade_ids.insert(trade.trade_id.clone()) {
+                    quality.duplicate_trade_ids += 1;
+                }
+                (false, Some(trade.price.to_f64()))
+            }
+            MarketData::Bar(bar) => (false, Some(bar.close.to_f64())),
+        };
+        if crossed {
+            quality.crossed_books += 1;
+        }
+        if price.is_some_and(|price| Self::is_outlier(config, &mut state.prices, price)) {
+            quality.outlier_prices += 1;
+        }
+    }
+
+    /// Trailing gaps up to `end`, appended to the last day of each channel.
+    fn close(&mut self) {
+        let end = match self.end {
+            Some(end) => end,
+            None => return,
+        };
+        let lasts: Vec<_> = self
+            .states
+            .iter()
+            .filter_map(|(key, state)| Some((key.clone(), state.day?, state.last?)))
+            .collect();
+        for (key, day, last) in lasts {
+            if let Some(gap) = self.gap(&key.1, last, end) {
+                if key.0 != "MarketTrade" {
+                    let interval = Interval {
+                        start: gap.start,
+                        end: gap.end,
+                    };
+                    self.cuts.entry(key.1.clone()).or_default().push(interval);
+                }
+                self.days
+                    .get_mut(&day)
+                    .unwrap()
+                    .get_mut(&key)
+                    .unwrap()
+                    .gaps
+                    .push(gap);
+            }
+        }
+    }
+
+    /// First to last record of each venue, without gaps and maintenance windows.
+    fn usable_intervals(&self) -> UsableIntervals {
+        let mut venues = BTreeMap::new();
+        for (venue, (first, last)) in &self.ranges {
+            let mut cuts = self.cuts.get(venue).cloned().unwrap_or_default();
+            for (start, end) in self.calendar.windows_between(venue, *first, *last) {
+                cuts.push(Interval { start, end });
+            }
+            cuts.sort_by_key(|cut| cut.start);
+
+            let mut intervals = vec![];
+            let mut cursor = *first;
+            for cut in cuts {
+                if cut.start > cursor {
+                    intervals.push(Interval {
+                        start: cursor,
+                        end: cut.start.min(*last),
+                    });
+                }
+                cursor = cursor.max(cut.end);
+            }
+            if cursor < *last {
+                intervals.push(Interval {
+                    start: cursor,
+                    end: *last,
+                });
+            }
+            intervals.retain(|interval| interval.start < interval.end);
+            venues.insert(venue.clone(), intervals);
+        }
+        UsableIntervals { venues }
+    }
+
+    /// Per day reports and the usable intervals.
+    pub fn finish(mut self) -> (Vec<DayReport>, UsableIntervals) {
+        self.close();
+        let usable = self.usable_intervals();
+        let mut reports = vec![];
+        for (date, channels) in std::mem::take(&mut self.days) {
+            let day_start = date.midnight().assume_utc();
+            let day_end = day_start + Duration::days(1);
+            let venues = channels
+                .values()
+                .map(|channel| channel.venue.clone())
+                .collect::<std::collections::BTreeSet<_>>()
+                .into_iter()
+                .map(|venue| {
+                    let usable_secs = usable.venues[&venue]
+                        .iter()
+                        .filter_map(|interval| interval.intersect(day_start, day_end))
+                        .map(|interval| interval.seconds())
+                        .sum();
+                    let maintenance: Vec<_> = self
+                        .calendar
+                        .windows_between(&venue, day_start, day_end)
+                        .into_iter()
+                        .filter_map(|(start, end)| {
+                            Interval { start, end }.intersect(day_start, day_end)
+                        })
+                        .collect();
+                    VenueDay {
+                        venue,
+                        usable_secs,
+                        maintenance_secs: maintenance.iter().map(Interval::seconds).sum(),
+                        maintenance,
+                    }
+                })
+                .collect();
+            reports.push(DayReport {
+                date,
+                venues,
+                channels: channels.into_values().collect(),
+            });
+        }
+        (reports, usable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodels::marketdata::{MarketTrade, Rate};
+    use crate::datamodels::types::{Price, Qty, Side};
+
+    fn at(secs: i64) -> OffsetDateTime {
+        OffsetDateTime::UNIX_EPOCH + Duration::seconds(secs)
+    }
+
+    fn rate(secs: i64, best_bid: f64, best_ask: f64) -> MarketData {
+        MarketData::Rate(Rate {
+            timestamp: at(secs),
+            market_created_timestamp: at(secs),
+            received_timestamp: at(secs),
+            sym: "FXBTCJPY".to_string(),
+            venue: "bitflyer".to_string(),
+            category: "lightning".to_string(),
+            best_bid,
+            best_ask,
+            mid_price: (best_bid + best_ask) / 2.0,
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        })
+    }
+
+    fn trade(secs: i64, trade_id: &str) -> MarketData {
+        MarketData::MarketTrade(MarketTrade {
+            timestamp: at(secs),
+            market_created_timestamp: at(secs),
+            received_timestamp: at(secs),
+            sym: "FXBTCJPY".to_string(),
+            venue: "bitflyer".to_string(),
+            category: "lightning".to_string(),
+            side: Side::Buy,
+            price: Price::from_f64(100.0),
+            amount: Qty::from_f64(0.01),
+            trade_id: trade_id.to_string(),
+            order_ids: "".to_string(),
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        })
+    }
+
+    /// bitflyer maintenance from 00:05 to 00:10 UTC, 300s to 600s after the epoch.
+    fn quality() -> DataQuality {
+        let calendar = MaintenanceCalendar::from_toml(
+            r#"
+            [[venue]]
+            venue = "bitflyer"
+            daily = [{ start = "00:05", end = "00:10" }]
+            "#,
+        )
+        .unwrap();
+        DataQuality::new(QualityConfig::default(), Arc::new(calendar))
+    }
+
+    fn gaps(channel: &ChannelQuality) -> Vec<(i64, i64, bool)> {
+        channel
+            .gaps
+            .iter()
+            .map(|gap| {
+                let secs = |t: OffsetDateTime| (t - at(0)).whole_seconds();
+                (secs(gap.start), secs(gap.end), gap.maintenance)
+            })
+            .collect()
+    }
+
+    fn interval(start: i64, end: i64) -> Interval {
+        Interval {
+            start: at(start),
+            end: at(end),
+        }
+    }
+
+    #[test]
+    fn gaps_include_the_leading_and_trailing_silences() {
+        let mut quality = quality().between(Some(at(3600)), Some(at(7200)));
+        for secs in [3700, 3730, 3790, 3900] {
+            quality.add(&rate(secs, 99.5, 100.5));
+        }
+        let (reports, usable) = quality.finish();
+        assert_eq!(reports.len(), 1);
+        let channel = &reports[0].channels[0];
+        assert_eq!(channel.kind, "Rate");
+        assert_eq!(channel.records, 4);
+        assert_eq!((channel.first, channel.last), (at(3700), at(3900)));
+        assert_eq!(
+            gaps(channel),
+            vec![
+                (3600, 3700, false),
+                (3790, 3900, false),
+                (3900, 7200, false)
+            ]
+        );
+        assert_eq!(channel.gaps[2].seconds, 3300.0);
+        assert!(!reports[0].is_clean());
+        assert_eq!(usable.venues["bitflyer"], vec![interval(3700, 3790)]);
+    }
+
+    #[test]
+    fn gaps_over_maintenance_are_expected() {
+        let mut quality = quality();
+        quality.add(&rate(250, 99.5, 100.5));
+        quality.add(&rate(650, 99.5, 100.5));
+        let (reports, _) = quality.finish();
+        let channel = &reports[0].channels[0];
+        assert_eq!(gaps(channel), vec![(250, 650, true)]);
+        assert_eq!(channel.unexpected_gaps().count(), 0);
+        assert!(reports[0].is_clean());
+        let venue = &reports[0].venues[0];
+        assert_eq!(venue.maintenance, vec![interval(300, 600)]);
+        assert_eq!(venue.maintenance_secs, 300.0);
+    }
+
+    #[test]
+    fn usable_intervals_merge_overlapping_cuts() {
+        let mut quality = quality();
+        for secs in [0, 50, 100, 400, 450, 700, 750, 1000] {
+            quality.add(&rate(secs, 99.5, 100.5));
+        }
+        // trades come at the pace of the market, their gaps are not cut
+        quality.add(&trade(0, "1"));
+        quality.add(&trade(1000, "2"));
+        let (reports, usable) = quality.finish();
+        let rates = reports[0]
+            .channels
+            .iter()
+            .find(|channel| channel.kind == "Rate")
+            .unwrap();
+        assert_eq!(
+            gaps(rates),
+            vec![(100, 400, true), (450, 700, true), (750, 1000, false)]
+        );
+        assert_eq!(
+            usable.venues["bitflyer"],
+            vec![interval(0, 100), interval(700, 750)]
+        );
+        assert_eq!(reports[0].venues[0].usable_secs, 150.0);
+        assert!(usable.contains("bitflyer", at(720)));
+        assert!(!usable.contains("bitflyer", at(500)));
+        assert!(!usable.contains("binance", at(50)));
+    }
+
+    #[test]
+    fn duplicate_trade_ids_are_reset_at_the_day_boundary() {
+        let mut quality = quality();
+        let day = 86_400;
+        for (secs, id) in [
+            (day - 50, "1"),
+            (day - 45, "1"),
+            (day - 40, ""),
+            (day - 30, ""),
+            (day - 35, "2"),
+            (day + 10, "1"),
+            (day + 20, "3"),
+        ] {
+            quality.add(&trade(secs, id));
+        }
+        let (reports, _) = quality.finish();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].date.to_string(), "1970-01-01");
+        assert_eq!(reports[0].channels[0].duplicate_trade_ids, 1);
+        assert_eq!(reports[0].channels[0].non_monotonic, 1);
+        assert_eq!(reports[1].channels[0].duplicate_trade_ids, 0);
+        assert_eq!(reports[1].channels[0].records, 2);
+        assert!(reports[1].is_clean());
+    }
+
+    #[test]
+    fn outliers_wait_for_half_a_window_of_prices() {
+        let mut quality = quality();
+        let mut secs = 0;
+        let mut add = |quality: &mut DataQuality, bid: f64, ask: f64| {
+            secs += 1;
+            quality.add(&rate(secs, bid, ask));
+        };
+        for _ in 0..9 {
+            add(&mut quality, 99.5, 100.5);
+        }
+        // 9 prices, below half of the window of 20
+        add(&mut quality, 199.5, 200.5);
+        add(&mut quality, 199.5, 200.5);
+        add(&mut quality, 103.5, 104.5);
+        add(&mut quality, 101.0, 100.0);
+        let (reports, _) = quality.finish();
+        let channel = &reports[0].channels[0];
+        assert_eq!(channel.outlier_prices, 1);
+        assert_eq!(channel.crossed_books, 1);
+        assert_eq!(channel.records, 13);
+    }
+}
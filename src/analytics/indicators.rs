@@ -0,0 +1,733 @@
+use std::collections::VecDeque;
+use std::fmt;
+use time::{Duration, OffsetDateTime};
+
+use crate::datamodels::enums::MarketData;
+
+/// Incremental indicator updated once per observation in O(1) amortized time.
+///
+/// Observations are keyed on `market_created_timestamp` so replays and live runs agree.
+pub trait Indicator {
+    /// Feed one observation and return the value once the indicator is warmed up.
+    fn update(&mut self, timestamp: OffsetDateTime, value: f64) -> Option<f64>;
+    fn value(&self) -> Option<f64>;
+    fn reset(&mut self);
+
+    fn is_ready(&self) -> bool {
+        self.value().is_some()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndicatorError {
+    /// Span or period of zero observations.
+    ZeroPeriod,
+    /// Half-life that is zero or negative.
+    NonPositiveHalfLife(Duration),
+}
+
+impl fmt::Display for IndicatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndicatorError::ZeroPeriod => write!(f, "period must be at least 1"),
+            IndicatorError::NonPositiveHalfLife(half_life) => {
+                write!(f, "half-life must be positive, got {}", half_life)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IndicatorError {}
+
+/// `(market_created_timestamp, price)` of `data`: the mid of a `Rate` or `Ticker`,
+/// the price of a `MarketTrade` and the close of a `Bar`.
+pub fn observation(data: &MarketData) -> Option<(OffsetDateTime, f64)> {
+    match data {
+        MarketData::Rate(rate) => Some((rate.market_created_timestamp, rate.mid_price)),
+        MarketData::Ticker(ticker) => Some((ticker.market_created_timestamp, ticker.mid_price)),
+        MarketData::MarketTrade(trade) => {
+            Some((trade.market_created_timestamp, trade.price.to_f64()))
+        }
+        MarketData::Bar(bar) => Some((bar.market_created_timestamp, bar.close.to_f64())),
+        MarketData::MarketBook(_) => None,
+    }
+}
+
+/// Observations kept by rolling indicators.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Window {
+    /// Last `n` observations. Ready once `n` observations were seen.
+    Count(usize),
+    /// Observations newer than `duration` before the latest one.
+    /// Ready once `duration` has elapsed since the first observation.
+    Time(Duration),
+}
+
+/// Rolling sum and sum of squares over a `Window`.
+///
+/// Values are shifted by the first observation to keep the sums small, and the sums are
+/// rebuilt from the buffer every `REBUILD_INTERVAL` evictions so rounding does not drift.
+#[derive(Clone, Debug)]
+pub struct RollingWindow {
+    window: Window,
+    values: VecDeque<(OffsetDateTime, f64)>,
+    first_timestamp: Option<OffsetDateTime>,
+    shift: f64,
+    sum: f64,
+    sum_sq: f64,
+    evictions: usize,
+}
+
+const REBUILD_INTERVAL: usize = 4096;
+
+impl RollingWindow {
+    pub fn new(window: Window) -> Self {
+        Self {
+            window,
+            values: VecDeque::new(),
+            first_timestamp: None,
+            shift: 0.0,
+            sum: 0.0,
+            sum_sq: 0.0,
+            evictions: 0,
+        }
+    }
+
+    pub fn push(&mut self, timestamp: OffsetDateTime, value: f64) {
+        if self.first_timestamp.is_none() {
+            self.first_timestamp = Some(timestamp);
+            self.shift = value;
+        }
+        let shifted = value - self.shift;
+        self.values.push_back((timestamp, value));
+        self.sum += shifted;
+        self.sum_sq += shifted * shifted;
+
+        loop {
+            let expired = match self.window {
+                Window::Count(n) => self.values.len() > n,
+                Window::Time(duration) => self
+                    .values
+                    .front()
+                    .is_some_and(|(t, _)| *t <= timestamp - duration),
+            };
+            if !expired {
+                break;
+            }
+            let (_, old) = self.values.pop_front().unwrap();
+            let shifted = old - self.shift;
+            self.sum -= shifted;
+            self.sum_sq -= shifted * shifted;
+            self.evictions += 1;
+        }
+        if self.evictions >= REBUILD_INTERVAL {
+            self.rebuild();
+        }
+    }
+
+    fn rebuild(&mut self) {
+        self.shift = self.values.front().map_or(0.0, |(_, v)| *v);
+        self.sum = 0.0;
+        self.sum_sq = 0.0;
+        for (_, value) in self.values.iter() {
+            let shifted = value - self.shift;
+            self.sum += shifted;
+            self.sum_sq += shifted * shifted;
+        }
+        self.evictions = 0;
+    }
+
+    pub fn is_ready(&self) -> bool {
+        match (self.window, self.first_timestamp, self.values.back()) {
+            (Window::Count(n), _, _) => self.values.len() >= n,
+            (Window::Time(duration), Some(first), Some((last, _))) => *last - first >= duration,
+            _ => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = f64> + '_ {
+        self.values.iter().map(|(_, v)| *v)
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum + self.shift * self.values.len() as f64
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.values.is_empty() {
+            return None;
+        }
+        Some(self.sum / self.values.len() as f64 + self.shift)
+    }
+
+    /// Variance with `ddof` delta degrees of freedom, 0 for population and 1 for sample.
+    pub fn variance(&self, ddof: usize) -> Option<f64> {
+        let n = self.values.len();
+        if n <= ddof {
+            return None;
+        }
+        let mean = self.sum / n as f64;
+        let variance = (self.sum_sq - mean * self.sum) / (n - ddof) as f64;
+        Some(variance.max(0.0))
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new(self.window);
+    }
+}
+
+/// Simple moving average.
+#[derive(Clone, Debug)]
+pub struct Sma {
+    window: RollingWindow,
+}
+
+impl Sma {
+    pub fn new(window: Window) -> Self {
+        Self {
+            window: RollingWindow::new(window),
+        }
+    }
+}
+
+impl Indicator for Sma {
+    fn update(&mut self, timestamp: OffsetDateTime, value: f64) -> Option<f64> {
+        self.window.push(timestamp, value);
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.window.is_ready() {
+            self.window.mean()
+        } else {
+            None
+        }
+    }
+
+    fn reset(&mut self) {
+        self.window.reset();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Decay {
+    /// `alpha` per observation.
+    Count { alpha: f64, span: usize },
+    /// Weight halves every `half_life` of market time.
+    Time { half_life: Duration },
+}
+
+/// Exponential moving average, decaying per observation or over market time.
+#[derive(Clone, Debug)]
+pub struct Ema {
+    decay: Decay,
+    value: Option<f64>,
+    last_timestamp: Option<OffsetDateTime>,
+    count: usize,
+}
+
+impl Ema {
+    /// `alpha = 2 / (span + 1)`, seeded with the first observation like pandas'
+    /// `ewm(span, adjust=False)`. Ready after `span` observations.
+    pub fn with_span(span: usize) -> Result<Self, IndicatorError> {
+        if span == 0 {
+            return Err(IndicatorError::ZeroPeriod);
+        }
+        Ok(Self::with_decay(Decay::Count {
+            alpha: 2.0 / (span as f64 + 1.0),
+            span,
+        }))
+    }
+
+    /// Irregularly spaced observations are weighted by the market time elapsed between them.
+    pub fn with_half_life(half_life: Duration) -> Result<Self, IndicatorError> {
+        if !half_life.is_positive() {
+            return Err(IndicatorError::NonPositiveHalfLife(half_life));
+        }
+        Ok(Self::with_decay(Decay::Time { half_life }))
+    }
+
+    fn with_decay(decay: Decay) -> Self {
+        Self {
+            decay,
+            value: None,
+            last_timestamp: None,
+            count: 0,
+        }
+    }
+}
+
+impl Indicator for Ema {
+    fn update(&mut self, timestamp: OffsetDateTime, value: f64) -> Option<f64> {
+        self.value = Some(match (self.value, self.last_timestamp) {
+            (Some(previous), Some(last)) => {
+                let alpha = match self.decay {
+                    Decay::Count { alpha, .. } => alpha,
+                    Decay::Time { half_life } => {
+                        let elapsed = (timestamp - last).as_seconds_f64().max(0.0);
+                        1.0 - 0.5f64.powf(elapsed / half_life.as_seconds_f64())
+                    }
+                };
+                previous + alpha * (value - previous)
+            }
+            _ => value,
+        });
+        self.last_timestamp = Some(timestamp);
+        self.count += 1;
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        match self.decay {
+            Decay::Count { span, .. } if self.count < span => None,
+            _ => self.value,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::with_decay(self.decay);
+    }
+}
+
+/// Log returns of successive observations.
+#[derive(Clone, Debug, Default)]
+struct LogReturns {
+    last: Option<f64>,
+}
+
+impl LogReturns {
+    fn next(&mut self, value: f64) -> Option<f64> {
+        let log_return = match self.last {
+            Some(last) if last > 0.0 && value > 0.0 => Some((value / last).ln()),
+            _ => None,
+        };
+        self.last = Some(value);
+        log_return
+    }
+}
+
+/// Sample standard deviation of log returns over the window, not annualized.
+#[derive(Clone, Debug)]
+pub struct RollingVolatility {
+    returns: LogReturns,
+    window: RollingWindow,
+}
+
+impl RollingVolatility {
+    pub fn new(window: Window) -> Self {
+        Self {
+            returns: LogReturns::default(),
+            window: RollingWindow::new(window),
+        }
+    }
+}
+
+impl Indicator for RollingVolatility {
+    fn update(&mut self, timestamp: OffsetDateTime, value: f64) -> Option<f64> {
+        if let Some(log_return) = self.returns.next(value) {
+            self.window.push(timestamp, log_return);
+        }
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.window.is_ready() {
+            self.window.variance(1).map(f64::sqrt)
+        } else {
+            None
+        }
+    }
+
+    fn reset(&mut self) {
+        self.returns = LogReturns::default();
+        self.window.reset();
+    }
+}
+
+/// Sum of squared log returns over the window.
+#[derive(Clone, Debug)]
+pub struct RealizedVariance {
+    returns: LogReturns,
+    window: RollingWindow,
+}
+
+impl RealizedVariance {
+    pub fn new(window: Window) -> Self {
+        Self {
+            returns: LogReturns::default(),
+            window: RollingWindow::new(window),
+        }
+    }
+}
+
+impl Indicator for RealizedVariance {
+    fn update(&mut self, timestamp: OffsetDateTime, value: f64) -> Option<f64> {
+        if let Some(log_return) = self.returns.next(value) {
+            self.window.push(timestamp, log_return * log_return);
+        }
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.window.is_ready() {
+            Some(self.window.sum())
+        } else {
+            None
+        }
+    }
+
+    fn reset(&mut self) {
+        self.returns = LogReturns::default();
+        self.window.reset();
+    }
+}
+
+/// Wilder's relative strength index in [0, 100].
+///
+/// Average gain and loss are seeded with the mean of the first `period` changes,
+/// then smoothed with `alpha = 1 / period`.
+#[derive(Clone, Debug)]
+pub struct Rsi {
+    period: usize,
+    count: usize,
+    avg_gain: f64,
+    avg_loss: f64,
+    last: Option<f64>,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Result<Self, IndicatorError> {
+        if period == 0 {
+            return Err(IndicatorError::ZeroPeriod);
+        }
+        Ok(Self {
+            period,
+            count: 0,
+            avg_gain: 0.0,
+            avg_loss: 0.0,
+            last: None,
+        })
+    }
+}
+
+impl Indicator for Rsi {
+    fn update(&mut self, _timestamp: OffsetDateTime, value: f64) -> Option<f64> {
+        if let Some(last) = self.last {
+            let change = value - last;
+            let (gain, loss) = (change.max(0.0), (-change).max(0.0));
+            self.count += 1;
+            if self.count <= self.period {
+                self.avg_gain += gain / self.period as f64;
+                self.avg_loss += loss / self.period as f64;
+            } else {
+                let period = self.period as f64;
+                self.avg_gain = (self.avg_gain * (period - 1.0) + gain) / period;
+                self.avg_loss = (self.avg_loss * (period - 1.0) + loss) / period;
+            }
+        }
+        self.last = Some(value);
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.count < self.period {
+            return None;
+        }
+        if self.avg_gain + self.avg_loss == 0.0 {
+            return Some(50.0);
+        }
+        Some(100.0 * self.avg_gain / (self.avg_gain + self.avg_loss))
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+        self.avg_gain = 0.0;
+        self.avg_loss = 0.0;
+        self.last = None;
+    }
+}
+
+/// Volume weighted average price of trades over the window.
+///
+/// Takes a price and an amount per trade, so it does not implement `Indicator`.
+#[derive(Clone, Debug)]
+pub struct Vwap {
+    notional: RollingWindow,
+    volume: RollingWindow,
+}
+
+impl Vwap {
+    pub fn new(window: Window) -> Self {
+        Self {
+            notional: RollingWindow::new(window),
+            volume: RollingWindow::new(window),
+        }
+    }
+
+    pub fn update(&mut self, timestamp: OffsetDateTime, price: f64, amount: f64) -> Option<f64> {
+        self.notional.push(timestamp, price * amount);
+        self.volume.push(timestamp, amount);
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        let volume = self.volume.sum();
+        if self.volume.is_ready() && volume > 0.0 {
+            Some(self.notional.sum() / volume)
+        } else {
+            None
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.notional.reset();
+        self.volume.reset();
+    }
+}
+
+/// Moving average plus and minus `k` population standard deviations.
+/// `value` is the middle band, see `bands`.
+#[derive(Clone, Debug)]
+pub struct BollingerBands {
+    k: f64,
+    window: RollingWindow,
+}
+
+impl BollingerBands {
+    pub fn new(window: Window, k: f64) -> Self {
+        Self {
+            k,
+            window: RollingWindow::new(window),
+        }
+    }
+
+    /// `(lower, middle, upper)`.
+    pub fn bands(&self) -> Option<(f64, f64, f64)> {
+        if !self.window.is_ready() {
+            return None;
+        }
+        let middle = self.window.mean()?;
+        let width = self.k * self.window.variance(0)?.sqrt();
+        Some((middle - width, middle, middle + width))
+    }
+
+    /// Position of `value` within the bands, 0 at the lower and 1 at the upper band.
+    pub fn percent_b(&self, value: f64) -> Option<f64> {
+        let (lower, _, upper) = self.bands()?;
+        if upper > lower {
+            Some((value - lower) / (upper - lower))
+        } else {
+            None
+        }
+    }
+}
+
+impl Indicator for BollingerBands {
+    fn update(&mut self, timestamp: OffsetDateTime, value: f64) -> Option<f64> {
+        self.window.push(timestamp, value);
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.bands().map(|(_, middle, _)| middle)
+    }
+
+    fn reset(&mut self) {
+        self.window.reset();
+    }
+}
+
+/// `(latest - mean) / std` over the window, population standard deviation.
+#[derive(Clone, Debug)]
+pub struct ZScore {
+    window: RollingWindow,
+    last: Option<f64>,
+}
+
+impl ZScore {
+    pub fn new(window: Window) -> Self {
+        Self {
+            window: RollingWindow::new(window),
+            last: None,
+        }
+    }
+}
+
+impl Indicator for ZScore {
+    fn update(&mut self, timestamp: OffsetDateTime, value: f64) -> Option<f64> {
+        self.window.push(timestamp, value);
+        self.last = Some(value);
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        if !self.window.is_ready() {
+            return None;
+        }
+        let std = self.window.variance(0)?.sqrt();
+        if std > 0.0 {
+            Some((self.last? - self.window.mean()?) / std)
+        } else {
+            Some(0.0)
+        }
+    }
+
+    fn reset(&mut self) {
+        self.window.reset();
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> OffsetDateTime {
+        OffsetDateTime::UNIX_EPOCH + Duration::seconds(secs)
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("indicator is not ready");
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn ema_span_matches_pandas_adjust_false() {
+        // alpha = 0.5: 1 -> 1.5 -> 2.25 -> 3.125
+        let mut ema = Ema::with_span(3).unwrap();
+        assert_eq!(ema.update(at(0), 1.0), None);
+        assert_eq!(ema.update(at(1), 2.0), None);
+        assert_close(ema.update(at(2), 3.0), 2.25);
+        assert_close(ema.update(at(3), 4.0), 3.125);
+
+        ema.reset();
+        assert!(!ema.is_ready());
+    }
+
+    #[test]
+    fn ema_half_life_decays_over_market_time() {
+        let mut ema = Ema::with_half_life(Duration::seconds(10)).unwrap();
+        assert_close(ema.update(at(0), 0.0), 0.0);
+        // one half-life: alpha = 0.5
+        assert_close(ema.update(at(10), 10.0), 5.0);
+        // two half-lives: alpha = 0.75
+        assert_close(ema.update(at(30), 13.0), 11.0);
+        // no time elapsed: unchanged
+        assert_close(ema.update(at(30), 100.0), 11.0);
+    }
+
+    #[test]
+    fn degenerate_parameters_are_rejected() {
+        assert_eq!(Ema::with_span(0).unwrap_err(), IndicatorError::ZeroPeriod);
+        assert_eq!(
+            Ema::with_half_life(Duration::ZERO).unwrap_err(),
+            IndicatorError::NonPositiveHalfLife(Duration::ZERO)
+        );
+        assert!(Ema::with_half_life(Duration::seconds(-1)).is_err());
+        assert_eq!(Rsi::new(0).unwrap_err(), IndicatorError::ZeroPeriod);
+    }
+
+    #[test]
+    fn rsi_seeds_with_mean_then_smooths_like_wilder() {
+        let mut rsi = Rsi::new(3).unwrap();
+        assert_eq!(rsi.update(at(0), 10.0), None);
+        assert_eq!(rsi.update(at(1), 11.0), None);
+        assert_eq!(rsi.update(at(2), 12.0), None);
+        // changes +1 +1 -1: avg gain 2/3, avg loss 1/3
+        assert_close(rsi.update(at(3), 11.0), 100.0 * 2.0 / 3.0);
+        // +2: avg gain (2/3 * 2 + 2) / 3 = 10/9, avg loss (1/3 * 2) / 3 = 2/9
+        assert_close(rsi.update(at(4), 13.0), 100.0 * 10.0 / 12.0);
+    }
+
+    #[test]
+    fn rsi_bounds() {
+        let mut rising = Rsi::new(2).unwrap();
+        let mut flat = Rsi::new(2).unwrap();
+        for (i, price) in [1.0, 2.0, 3.0, 4.0].iter().enumerate() {
+            rising.update(at(i as i64), *price);
+            flat.update(at(i as i64), 1.0);
+        }
+        assert_close(rising.value(), 100.0);
+        assert_close(flat.value(), 50.0);
+    }
+
+    #[test]
+    fn vwap_over_count_window() {
+        let mut vwap = Vwap::new(Window::Count(3));
+        assert_eq!(vwap.update(at(0), 100.0, 1.0), None);
+        assert_eq!(vwap.update(at(1), 102.0, 3.0), None);
+        // (100 * 1 + 102 * 3 + 101 * 2) / 6
+        assert_close(vwap.update(at(2), 101.0, 2.0), 608.0 / 6.0);
+        // first trade leaves the window: (102 * 3 + 101 * 2 + 104 * 2) / 7
+        assert_close(vwap.update(at(3), 104.0, 2.0), 716.0 / 7.0);
+    }
+
+    #[test]
+    fn vwap_over_time_window() {
+        let mut vwap = Vwap::new(Window::Time(Duration::seconds(10)));
+        vwap.update(at(0), 100.0, 1.0);
+        assert_eq!(vwap.update(at(5), 110.0, 1.0), None);
+        // the trade at 0 is not newer than 10s before 10
+        assert_close(vwap.update(at(10), 120.0, 3.0), (110.0 + 360.0) / 4.0);
+    }
+
+    #[test]
+    fn realized_volatility_and_variance_of_log_returns() {
+        let (r1, r2) = ((110.0f64 / 100.0).ln(), (99.0f64 / 110.0).ln());
+
+        let mut volatility = RollingVolatility::new(Window::Count(2));
+        let mut variance = RealizedVariance::new(Window::Count(2));
+        for (i, price) in [100.0, 110.0, 99.0].iter().enumerate() {
+            volatility.update(at(i as i64), *price);
+            variance.update(at(i as i64), *price);
+        }
+        // sample std of two values is |r1 - r2| / sqrt(2)
+        assert_close(volatility.value(), (r1 - r2).abs() / 2f64.sqrt());
+        assert_close(variance.value(), r1 * r1 + r2 * r2);
+    }
+
+    #[test]
+    fn rolling_window_stays_exact_across_rebuilds() {
+        let mut sma = Sma::new(Window::Count(10));
+        let mut zscore = ZScore::new(Window::Count(10));
+        let prices: Vec<f64> = (0..3 * REBUILD_INTERVAL)
+            .map(|i| 5_000_000.0 + (i % 17) as f64 * 0.5)
+            .collect();
+        for (i, price) in prices.iter().enumerate() {
+            sma.update(at(i as i64), *price);
+            zscore.update(at(i as i64), *price);
+        }
+        let last = &prices[prices.len() - 10..];
+        let mean = last.iter().sum::<f64>() / 10.0;
+        let std = (last.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / 10.0).sqrt();
+        assert_close(sma.value(), mean);
+        assert_close(zscore.value(), (last[9] - mean) / std);
+    }
+
+    #[test]
+    fn bollinger_bands_use_population_std() {
+        let mut bands = BollingerBands::new(Window::Count(4), 2.0);
+        for (i, price) in [2.0, 4.0, 4.0, 6.0].iter().enumerate() {
+            bands.update(at(i as i64), *price);
+        }
+        // mean 4, population std sqrt(2)
+        let (lower, middle, upper) = bands.bands().unwrap();
+        assert_close(Some(middle), 4.0);
+        assert_close(Some(upper - middle), 2.0 * 2f64.sqrt());
+        assert_close(Some(middle - lower), 2.0 * 2f64.sqrt());
+        assert_close(bands.percent_b(4.0), 0.5);
+    }
+}
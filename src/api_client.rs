@@ -1,2 +1,6 @@
 pub mod backtest;
+pub mod bitflyer;
 pub mod execution_client;
+#[cfg(test)]
+pub mod mock_server;
+pub mod paper;
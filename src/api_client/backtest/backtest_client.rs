@@ -1,11 +1,20 @@
-use std::collections::HashMap;
+use log::warn;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use std::time::Duration;
 use time::OffsetDateTime;
 
 use crate::api_client::execution_client::ExecutionClient;
 use crate::datamodels::enums::MarketData;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::latency::{Hop, LatencyTracker};
 use crate::datamodels::order::{Order, OrderStatus, OrderType};
 use crate::datamodels::position::Position;
+use crate::datamodels::types::{Price, Qty, Side};
+use crate::monitor::metrics;
+use crate::oms::order_manager::OrderManager;
+use crate::refdata::calendar::MaintenanceCalendar;
+use crate::refdata::instrument::{InstrumentRegistry, OrderValidationError};
 
 pub struct BacktestClient<'a> {
     // このClientにbacktestを全て任せる?
@@ -16,32 +25,83 @@ pub struct BacktestClient<'a> {
     // Runnerを用意して、相互にnextを回すか...?
     // Executionを用意した方がよいか?
     pub venue: &'a str,
-    pending_market_orders: HashMap<String, Order>,
-    pending_limit_orders: HashMap<String, Order>,
-    filled_market_orders: HashMap<String, Order>,
-    filled_limit_orders: HashMap<String, Order>,
+    orders: OrderManager,
+    positions: BTreeMap<(String, String), Position>,
     best_bid: f64,
     best_ask: f64,
     market_order_submit_latency: Duration,
     market_order_receive_latency: Duration,
     limit_order_submit_latency: Duration,
     limit_order_receive_latency: Duration,
+    instruments: Option<Arc<InstrumentRegistry>>,
+    calendar: Option<Arc<MaintenanceCalendar>>,
+    ids: IdSource,
 }
 
 impl<'a> BacktestClient<'a> {
     pub fn new(venue: &'a str) -> Self {
         Self {
-            venue: venue,
-            pending_limit_orders: HashMap::new(),
-            pending_market_orders: HashMap::new(),
-            filled_market_orders: HashMap::new(),
-            filled_limit_orders: HashMap::new(),
+            venue,
+            orders: OrderManager::new(),
+            positions: BTreeMap::new(),
             best_bid: 0.0,
             best_ask: f64::INFINITY,
             market_order_submit_latency: Duration::from_millis(100),
             market_order_receive_latency: Duration::from_millis(1000),
             limit_order_submit_latency: Duration::from_millis(100),
             limit_order_receive_latency: Duration::from_millis(1000),
+            instruments: None,
+            calendar: None,
+            ids: IdSource::default(),
+        }
+    }
+
+    pub fn orders(&self) -> &OrderManager {
+        &self.orders
+    }
+
+    /// Source of the order and position ids, random by default. Seed it for diffable backtests.
+    pub fn set_ids(&mut self, ids: IdSource) {
+        self.ids = ids;
+    }
+
+    pub fn ids(&self) -> &IdSource {
+        &self.ids
+    }
+
+    /// Reject orders violating the tick, lot and min size of `instruments`, like the venue would.
+    pub fn set_instruments(&mut self, instruments: Arc<InstrumentRegistry>) {
+        self.instruments = Some(instruments);
+    }
+
+    /// Reject orders and suppress fills during the venue's maintenance windows.
+    pub fn set_calendar(&mut self, calendar: Arc<MaintenanceCalendar>) {
+        self.calendar = Some(calendar);
+    }
+
+    fn maintenance_end(&self, timestamp: OffsetDateTime) -> Option<OffsetDateTime> {
+        self.calendar
+            .as_ref()?
+            .maintenance_end(self.venue, timestamp)
+    }
+
+    fn validate_order(&self, order: &Order) -> Result<(), OrderValidationError> {
+        if let Some(until) = self.maintenance_end(order.timestamp) {
+            return Err(OrderValidationError::Maintenance {
+                venue: self.venue.to_string(),
+                until,
+            });
+        }
+        match &self.instruments {
+            Some(instruments) => instruments.validate_order(
+                order.timestamp,
+                self.venue,
+                &order.sym,
+                order.price,
+                order.amount,
+                &order.order_type,
+            ),
+            None => Ok(()),
         }
     }
 
@@ -51,103 +111,197 @@ impl<'a> BacktestClient<'a> {
     /// * `market_data` -
     ///
     /// # Returns
+    /// Orders filled by this `market_data`.
     ///
     /// # Examples
-    pub fn next(&mut self, market_data: MarketData) {
-        self.process_market_orders(&market_data);
-        self.process_limit_orders(&market_data);
-        match market_data {
-            MarketData::Rate(rate) => {
-                self.best_bid = rate.best_bid;
-                self.best_ask = rate.best_ask;
-            }
-            _ => {}
+    pub fn next(&mut self, market_data: MarketData) -> Vec<Order> {
+        // the matching engine is down during maintenance, orders wait for the reopen.
+        let in_maintenance = self
+            .maintenance_end(market_data.market_created_timestamp())
+            .is_some();
+        let (filled_market_orders, filled_limit_orders) = if in_maintenance {
+            (vec![], vec![])
+        } else {
+            (
+                self.process_market_orders(&market_data),
+                self.process_limit_orders(&market_data),
+            )
+        };
+        for order in filled_market_orders
+            .iter()
+            .chain(filled_limit_orders.iter())
+        {
+            self.update_position(order);
+        }
+        if let MarketData::Rate(rate) = market_data {
+            self.best_bid = rate.best_bid;
+            self.best_ask = rate.best_ask;
         }
+        filled_market_orders
+            .into_iter()
+            .chain(filled_limit_orders)
+            .collect()
+    }
+
+    fn update_position(&mut self, order: &Order) {
+        let position = self
+            .positions
+            .entry((order.sym.clone(), order.model_id.clone()))
+            .or_insert_with(|| Position::new(&order.sym, self.venue, &order.model_id, &self.ids));
+        let signed_amount = order.executed_amount.signed(order.side);
+        position.amount += signed_amount;
+        position.cost += signed_amount.notional(order.price);
+        position.timestamp = order.received_timestamp;
+        position.latency_tracker = order.latency_tracker.clone();
+        metrics::FILLS
+            .with_label_values(&[&order.model_id, &order.sym])
+            .inc();
     }
 
-    /// [TODO]
-    /// - add order created -> submit -> received latency
-    /// - add order execution -> received latency
     fn process_limit_orders(&mut self, market_data: &MarketData) -> Vec<Order> {
         let mut filled_order_ids: Vec<String> = Vec::new();
-        match market_data {
-            MarketData::MarketTrade(market_trade) => {
-                // check if pending limit orders are filled or not
-                for (order_id, order) in self.pending_limit_orders.iter_mut() {
-                    if order.sym == market_trade.sym && order.venue == market_trade.venue {
-                        if order.side > 0 && market_trade.side < 0 {
-                            if market_trade.price <= order.price
-                                && order.market_created_timestamp
-                                    < market_trade.market_created_timestamp
-                            {
-                                filled_order_ids.push(order_id.clone());
-                            }
-                        } else if order.side < 0 && market_trade.side > 0 {
-                            if order.price <= market_trade.price
-                                && order.market_created_timestamp
-                                    < market_trade.market_created_timestamp
-                            {
-                                filled_order_ids.push(order_id.clone());
-                            }
-                        }
+        if let MarketData::MarketTrade(market_trade) = market_data {
+            // check if pending limit orders are filled or not
+            for order in self.orders.open_orders(Some(&market_trade.sym), None, None) {
+                if order.venue == market_trade.venue
+                    && !matches!(order.order_type, OrderType::Market)
+                {
+                    let is_live =
+                        order.market_created_timestamp < market_trade.market_created_timestamp;
+                    let is_crossed = match (order.side, market_trade.side) {
+                        (Side::Buy, Side::Sell) => market_trade.price <= order.price,
+                        (Side::Sell, Side::Buy) => order.price <= market_trade.price,
+                        _ => false,
+                    };
+                    if is_live && is_crossed {
+                        filled_order_ids.push(order.order_id.clone());
                     }
                 }
             }
-            _ => {}
+            let receive_latency = self.limit_order_receive_latency;
+            for order_id in filled_order_ids.iter() {
+                self.orders.update(order_id, |order| {
+                    let _ = order.fill(
+                        order.amount - order.executed_amount,
+                        market_trade.market_created_timestamp,
+                    );
+                    LatencyTracker::stamp_field(
+                        &mut order.latency_tracker,
+                        Hop::Fill,
+                        market_trade.market_created_timestamp,
+                    );
+                    order.received_timestamp =
+                        market_trade.market_created_timestamp + receive_latency;
+                });
+            }
         }
         filled_order_ids
             .iter()
-            .map(|order_id| self.pending_limit_orders.remove(order_id).unwrap())
+            .filter_map(|order_id| self.orders.get(order_id).cloned())
             .collect()
     }
 
-    /// [TODO]
-    /// - add order created -> submit latency
-    /// - add order execution -> received latency
     fn process_market_orders(&mut self, market_data: &MarketData) -> Vec<Order> {
         let mut filled_order_ids = Vec::new();
-        match market_data {
-            MarketData::Rate(rate) => {
-                for (order_id, order) in self.pending_market_orders.iter_mut() {
-                    if order.sym == rate.sym && order.venue == rate.venue {
-                        if order.side > 0 {
-                            order.price = rate.best_ask;
-                            filled_order_ids.push(order_id.clone());
-                        } else if order.side < 0 {
-                            order.price = rate.best_bid;
-                            order.executed_amount = order.amount;
-                            order.received_timestamp = rate.timestamp;
-                            filled_order_ids.push(order_id.clone());
-                        }
-                    }
+        if let MarketData::Rate(rate) = market_data {
+            for order in self.orders.open_orders(Some(&rate.sym), None, None) {
+                if order.venue == rate.venue
+                    && matches!(order.order_type, OrderType::Market)
+                    && order.market_created_timestamp <= rate.market_created_timestamp
+                {
+                    filled_order_ids.push(order.order_id.clone());
                 }
             }
-            _ => {}
+            let receive_latency = self.market_order_receive_latency;
+            for order_id in filled_order_ids.iter() {
+                self.orders.update(order_id, |order| {
+                    order.price = Price::from_f64(match order.side {
+                        Side::Buy => rate.best_ask,
+                        Side::Sell => rate.best_bid,
+                    });
+                    let _ = order.fill(
+                        order.amount - order.executed_amount,
+                        rate.market_created_timestamp,
+                    );
+                    LatencyTracker::stamp_field(
+                        &mut order.latency_tracker,
+                        Hop::Fill,
+                        rate.market_created_timestamp,
+                    );
+                    order.received_timestamp = rate.market_created_timestamp + receive_latency;
+                });
+            }
         }
         filled_order_ids
             .iter()
-            .map(|order_id| self.pending_market_orders.remove(order_id).unwrap())
+            .filter_map(|order_id| self.orders.get(order_id).cloned())
             .collect()
     }
 }
 
 impl<'a> ExecutionClient for BacktestClient<'a> {
-    // TODO
     fn submit_order(
         &mut self,
         timestamp: OffsetDateTime,
         sym: &str,
-        side: i16,
-        price: f64,
-        amount: f64,
+        side: Side,
+        price: Price,
+        amount: Qty,
         order_type: OrderType,
         model_id: &str,
     ) -> Order {
-        let new_order = Order::new(
-            timestamp, sym, side, price, amount, order_type, self.venue, model_id,
+        self.submit_order_traced(
+            timestamp,
+            sym,
+            side,
+            price,
+            amount,
+            order_type,
+            model_id,
+            LatencyTracker::new(),
+        )
+    }
+
+    /// Stamps are in simulated time: sent at `timestamp`, acked and filled at the venue's time.
+    fn submit_order_traced(
+        &mut self,
+        timestamp: OffsetDateTime,
+        sym: &str,
+        side: Side,
+        price: Price,
+        amount: Qty,
+        order_type: OrderType,
+        model_id: &str,
+        mut tracker: LatencyTracker,
+    ) -> Order {
+        // replayed market data is compared on market_created_timestamp, so the order must live on the same clock.
+        let (submit_latency, receive_latency) = match order_type {
+            OrderType::Market => (
+                self.market_order_submit_latency,
+                self.market_order_receive_latency,
+            ),
+            _ => (
+                self.limit_order_submit_latency,
+                self.limit_order_receive_latency,
+            ),
+        };
+        tracker.stamp(Hop::Send, timestamp);
+        let mut new_order = Order::new(
+            timestamp, sym, side, price, amount, order_type, self.venue, model_id, &self.ids,
         );
-        self.pending_limit_orders
-            .insert(new_order.order_id.clone(), new_order.clone());
+        tracker.stamp(Hop::Ack, timestamp + submit_latency);
+        new_order.latency_tracker = tracker.to_string();
+        if let Err(e) = self.validate_order(&new_order) {
+            warn!("Invalid order : {}", e);
+            let _ = new_order.transition(OrderStatus::Rejected, timestamp);
+            new_order.misc = e.to_string();
+            self.orders.insert(new_order.clone());
+            return new_order;
+        }
+        let _ = new_order.transition(OrderStatus::Submitted, timestamp);
+        new_order.market_created_timestamp = timestamp + submit_latency;
+        new_order.received_timestamp = new_order.market_created_timestamp + receive_latency;
+        self.orders.insert(new_order.clone());
         new_order
     }
 
@@ -162,28 +316,34 @@ impl<'a> ExecutionClient for BacktestClient<'a> {
     //         None => panic!("order not found: {}", order_id),
     //     }
 
-    fn cancel_order(&mut self, timestamp: OffsetDateTime, order_id: &String) -> Order {
-        let order = self.pending_limit_orders.remove(order_id);
-        match order {
-            Some(order) => {
-                let mut o = order;
-                o.order_status = OrderStatus::Canceled;
-                o.timestamp = timestamp;
-                o.market_created_timestamp = timestamp + self.market_order_submit_latency;
-                o.received_timestamp =
-                    o.market_created_timestamp + self.market_order_receive_latency;
-                o
-            }
-            None => panic!("order not found: {}", order_id),
+    fn cancel_order(&mut self, timestamp: OffsetDateTime, order_id: &str) -> Option<Order> {
+        let submit_latency = self.market_order_submit_latency;
+        let receive_latency = self.market_order_receive_latency;
+        let order = self.orders.update(order_id, |o| {
+            o.timestamp = timestamp;
+            o.market_created_timestamp = timestamp + submit_latency;
+            let _ = o.transition(OrderStatus::Canceled, o.market_created_timestamp);
+            o.received_timestamp = o.market_created_timestamp + receive_latency;
+        });
+        if order.is_none() {
+            warn!("Cannot cancel unknown order {}", order_id);
         }
+        order.cloned()
+    }
+
+    fn get_order_status(&mut self, order_id: &str) -> Option<Order> {
+        self.orders.get(order_id).cloned()
     }
 
-    fn get_order_status(&mut self, order_id: &String) -> Option<&Order> {
-        let order = self.pending_limit_orders.get(order_id);
-        return order;
+    fn get_open_orders(&mut self, model_id: Option<&str>) -> Vec<Order> {
+        self.orders
+            .open_orders(None, None, model_id)
+            .into_iter()
+            .cloned()
+            .collect()
     }
 
-    fn get_positions() -> Position {
-        Position::new("BTCUSD", "backtest", "backtest")
+    fn get_positions(&mut self) -> Vec<Position> {
+        self.positions.values().cloned().collect()
     }
 }
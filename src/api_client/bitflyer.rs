@@ -0,0 +1,2 @@
+pub mod bitflyer_client;
+pub mod datamodel;
@@ -0,0 +1,694 @@
+use log::{error, info, warn};
+use reqwest::blocking::{Client, Response};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+use crate::api_client::bitflyer::datamodel::{
+    to_product_code, to_sym, CancelChildOrderRequest, ChildOrder, Collateral, PositionDetail,
+    SendChildOrderRequest, SendChildOrderResponse,
+};
+use crate::api_client::execution_client::ExecutionClient;
+use crate::datamodels::enums::InternalData;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::latency::{Hop, LatencyTracker};
+use crate::datamodels::order::{Order, OrderStatus, OrderType};
+use crate::datamodels::position::Position;
+use crate::datamodels::types::{Price, Qty, Side};
+use crate::feedhandler::bitflyer::auth::{sign, Credentials};
+use crate::oms::order_manager::{OrderManager, ReconcileReport};
+use crate::refdata::calendar::MaintenanceCalendar;
+use crate::refdata::instrument::{InstrumentRegistry, OrderValidationError};
+
+const ENDPOINT: &str = "https://api.bitflyer.com";
+const VENUE: &str = "bitflyer";
+
+#[derive(Debug)]
+pub enum BitFlyerApiError {
+    Http(reqwest::Error),
+    Status(u16, String),
+    Json(serde_json::Error),
+    RateLimited(OffsetDateTime),
+    UnsupportedOrderType(OrderType),
+}
+
+impl fmt::Display for BitFlyerApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitFlyerApiError::Http(e) => write!(f, "http error: {}", e),
+            BitFlyerApiError::Status(status, body) => write!(f, "status {}: {}", status, body),
+            BitFlyerApiError::Json(e) => write!(f, "json error: {}", e),
+            BitFlyerApiError::RateLimited(reset) => write!(f, "rate limited until {}", reset),
+            BitFlyerApiError::UnsupportedOrderType(t) => {
+                write!(f, "unsupported order type: {:?}", t)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BitFlyerApiError {}
+
+impl From<reqwest::Error> for BitFlyerApiError {
+    fn from(e: reqwest::Error) -> Self {
+        BitFlyerApiError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for BitFlyerApiError {
+    fn from(e: serde_json::Error) -> Self {
+        BitFlyerApiError::Json(e)
+    }
+}
+
+/// Latest `X-RateLimit-*` response headers.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimit {
+    pub period: Option<i64>,
+    pub remaining: Option<i64>,
+    pub reset: Option<OffsetDateTime>,
+}
+
+impl RateLimit {
+    fn update(&mut self, response: &Response) {
+        let header = |name: &str| -> Option<i64> {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<i64>().ok())
+        };
+        if let Some(period) = header("X-RateLimit-Period") {
+            self.period = Some(period);
+        }
+        if let Some(remaining) = header("X-RateLimit-Remaining") {
+            self.remaining = Some(remaining);
+        }
+        if let Some(reset) = header("X-RateLimit-Reset") {
+            self.reset = OffsetDateTime::from_unix_timestamp(reset).ok();
+        }
+    }
+
+    /// Returns the reset time while no request is left in the current period.
+    pub fn exhausted_until(&self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        match (self.remaining, self.reset) {
+            (Some(remaining), Some(reset)) if remaining <= 0 && now < reset => Some(reset),
+            _ => None,
+        }
+    }
+}
+
+pub struct BitFlyerClient {
+    base_url: String,
+    credentials: Credentials,
+    http: Client,
+    orders: OrderManager,
+    margin_products: Vec<String>,
+    rate_limit: RateLimit,
+    instruments: Option<Arc<InstrumentRegistry>>,
+    calendar: Option<Arc<MaintenanceCalendar>>,
+    ids: IdSource,
+}
+
+impl BitFlyerClient {
+    pub fn new(api_key: &str, api_secret: &str) -> Self {
+        Self::with_url(ENDPOINT, api_key, api_secret)
+    }
+
+    /// Send requests to another endpoint, e.g. a local mock server.
+    pub fn with_url(url: &str, api_key: &str, api_secret: &str) -> Self {
+        Self {
+            base_url: url.trim_end_matches('/').to_string(),
+            credentials: Credentials::new(api_key, api_secret),
+            http: Client::new(),
+            orders: OrderManager::new(),
+            margin_products: vec!["FX_BTC_JPY".to_string()],
+            rate_limit: RateLimit::default(),
+            instruments: None,
+            calendar: None,
+            ids: IdSource::default(),
+        }
+    }
+
+    /// Source of the order and position ids, random by default.
+    pub fn set_ids(&mut self, ids: IdSource) {
+        self.ids = ids;
+    }
+
+    /// Products queried by `get_positions`. Only margin products have positions on bitFlyer.
+    pub fn set_margin_products(&mut self, product_codes: Vec<String>) {
+        self.margin_products = product_codes;
+    }
+
+    pub fn rate_limit(&self) -> &RateLimit {
+        &self.rate_limit
+    }
+
+    /// Map symbols through `instruments` and reject orders violating their tick, lot and min size.
+    pub fn set_instruments(&mut self, instruments: Arc<InstrumentRegistry>) {
+        self.instruments = Some(instruments);
+    }
+
+    /// Reject orders locally during bitFlyer maintenance instead of sending them.
+    pub fn set_calendar(&mut self, calendar: Arc<MaintenanceCalendar>) {
+        self.calendar = Some(calendar);
+    }
+
+    fn validate_order(&self, order: &Order) -> Result<(), OrderValidationError> {
+        let maintenance_end = self
+            .calendar
+            .as_ref()
+            .and_then(|calendar| calendar.maintenance_end(VENUE, order.timestamp));
+        if let Some(until) = maintenance_end {
+            return Err(OrderValidationError::Maintenance {
+                venue: VENUE.to_string(),
+                until,
+            });
+        }
+        match &self.instruments {
+            Some(instruments) => instruments.validate_order(
+                order.timestamp,
+                VENUE,
+                &order.sym,
+                order.price,
+                order.amount,
+                &order.order_type,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    fn product_code(&self, sym: &str) -> String {
+        self.instruments
+            .as_ref()
+            .and_then(|instruments| instruments.native_symbol(VENUE, sym))
+            .unwrap_or_else(|| to_product_code(sym))
+    }
+
+    fn sym(&self, product_code: &str) -> String {
+        match &self.instruments {
+            Some(instruments) => instruments.sym(VENUE, product_code),
+            None => to_sym(product_code),
+        }
+    }
+
+    pub fn orders(&self) -> &OrderManager {
+        &self.orders
+    }
+
+    /// Align our order book with the active child orders of `sym` at bitFlyer.
+    pub fn reconcile(&mut self, sym: &str) -> Result<ReconcileReport, BitFlyerApiError> {
+        let child_orders = self.get_child_orders(&self.product_code(sym), None, Some("ACTIVE"))?;
+        let now = OffsetDateTime::now_utc();
+        let snapshot = child_orders
+            .iter()
+            .map(|child_order| {
+                // keep the model of orders we sent ourselves
+                let model_id = self
+                    .orders
+                    .get(&child_order.child_order_acceptance_id)
+                    .map(|o| o.model_id.clone())
+                    .unwrap_or_default();
+                child_order.to_order(sym, &model_id, now, &self.ids)
+            })
+            .collect();
+        Ok(self.orders.reconcile(VENUE, sym, snapshot, now))
+    }
+
+    /// Private callback of `BitFlyerSocketIo`, keeping our orders in line with the order events
+    /// between `get_order_status` polls.
+    pub fn on_order_event(&mut self, data: InternalData) {
+        let venue_order = match data {
+            InternalData::Order(order) => order,
+            _ => return,
+        };
+        let order_id = venue_order.order_id.clone();
+        let executed_amount = self.orders.get(&order_id).map(|o| o.executed_amount);
+        let now = OffsetDateTime::now_utc();
+        let mut report = ReconcileReport::default();
+        self.orders.apply(venue_order, now, &mut report);
+        for anomaly in report.anomalies {
+            warn!("Order event not applied : {}", anomaly);
+        }
+        if let Some(executed_amount) = executed_amount {
+            self.orders.update(&order_id, |order| {
+                if order.executed_amount != executed_amount {
+                    LatencyTracker::stamp_field(&mut order.latency_tracker, Hop::Fill, now);
+                }
+            });
+        }
+    }
+
+    fn request<B: Serialize, T: DeserializeOwned>(
+        &mut self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&B>,
+    ) -> Result<Option<T>, BitFlyerApiError> {
+        let now = OffsetDateTime::now_utc();
+        if let Some(reset) = self.rate_limit.exhausted_until(now) {
+            return Err(BitFlyerApiError::RateLimited(reset));
+        }
+
+        // signature covers the path with its query string and the raw body
+        let mut path_with_query = path.to_string();
+        if !query.is_empty() {
+            let qs = query
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<String>>()
+                .join("&");
+            path_with_query = format!("{}?{}", path, qs);
+        }
+        let body = match body {
+            Some(body) => serde_json::to_string(body)?,
+            None => "".to_string(),
+        };
+        let timestamp = now.unix_timestamp().to_string();
+        let signature = sign(
+            &self.credentials.api_secret,
+            &format!(
+                "{}{}{}{}",
+                timestamp,
+                method.as_str(),
+                path_with_query,
+                body
+            ),
+        );
+
+        let response = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path_with_query))
+            .header("ACCESS-KEY", self.credentials.api_key.as_str())
+            .header("ACCESS-TIMESTAMP", timestamp)
+            .header("ACCESS-SIGN", signature)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()?;
+        self.rate_limit.update(&response);
+
+        let status = response.status();
+        let text = response.text()?;
+        if !status.is_success() {
+            return Err(BitFlyerApiError::Status(status.as_u16(), text));
+        }
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&text)?))
+    }
+
+    /// `POST /v1/me/sendchildorder`. Returns `child_order_acceptance_id`.
+    pub fn send_child_order(&mut self, order: &Order) -> Result<String, BitFlyerApiError> {
+        let body = SendChildOrderRequest::from_order(order, &self.product_code(&order.sym))
+            .ok_or_else(|| BitFlyerApiError::UnsupportedOrderType(order.order_type.clone()))?;
+        let response: Option<SendChildOrderResponse> =
+            self.request(Method::POST, "/v1/me/sendchildorder", &[], Some(&body))?;
+        response
+            .map(|r| r.child_order_acceptance_id)
+            .ok_or_else(|| BitFlyerApiError::Status(200, "empty response".to_string()))
+    }
+
+    /// `POST /v1/me/cancelchildorder`.
+    pub fn cancel_child_order(
+        &mut self,
+        product_code: &str,
+        child_order_acceptance_id: &str,
+    ) -> Result<(), BitFlyerApiError> {
+        let body = CancelChildOrderRequest {
+            product_code: product_code.to_string(),
+            child_order_acceptance_id: child_order_acceptance_id.to_string(),
+        };
+        let _: Option<serde_json::Value> =
+            self.request(Method::POST, "/v1/me/cancelchildorder", &[], Some(&body))?;
+        Ok(())
+    }
+
+    /// `GET /v1/me/getchildorders`.
+    pub fn get_child_orders(
+        &mut self,
+        product_code: &str,
+        child_order_acceptance_id: Option<&str>,
+        child_order_state: Option<&str>,
+    ) -> Result<Vec<ChildOrder>, BitFlyerApiError> {
+        let mut query = vec![("product_code", product_code)];
+        if let Some(id) = child_order_acceptance_id {
+            query.push(("child_order_acceptance_id", id));
+        }
+        if let Some(state) = child_order_state {
+            query.push(("child_order_state", state));
+        }
+        let orders: Option<Vec<ChildOrder>> =
+            self.request::<(), _>(Method::GET, "/v1/me/getchildorders", &query, None)?;
+        Ok(orders.unwrap_or_default())
+    }
+
+    /// `GET /v1/me/getpositions`.
+    pub fn get_position_details(
+        &mut self,
+        product_code: &str,
+    ) -> Result<Vec<PositionDetail>, BitFlyerApiError> {
+        let positions: Option<Vec<PositionDetail>> = self.request::<(), _>(
+            Method::GET,
+            "/v1/me/getpositions",
+            &[("product_code", product_code)],
+            None,
+        )?;
+        Ok(positions.unwrap_or_default())
+    }
+
+    /// `GET /v1/me/getcollateral`.
+    pub fn get_collateral(&mut self) -> Result<Collateral, BitFlyerApiError> {
+        let collateral: Option<Collateral> =
+            self.request::<(), _>(Method::GET, "/v1/me/getcollateral", &[], None)?;
+        collateral.ok_or_else(|| BitFlyerApiError::Status(200, "empty response".to_string()))
+    }
+}
+
+impl ExecutionClient for BitFlyerClient {
+    fn submit_order(
+        &mut self,
+        timestamp: OffsetDateTime,
+        sym: &str,
+        side: Side,
+        price: Price,
+        amount: Qty,
+        order_type: OrderType,
+        model_id: &str,
+    ) -> Order {
+        self.submit_order_traced(
+            timestamp,
+            sym,
+            side,
+            price,
+            amount,
+            order_type,
+            model_id,
+            LatencyTracker::new(),
+        )
+    }
+
+    /// `ack` is the REST response, `fill` is stamped when `get_order_status` sees a fill.
+    fn submit_order_traced(
+        &mut self,
+        timestamp: OffsetDateTime,
+        sym: &str,
+        side: Side,
+        price: Price,
+        amount: Qty,
+        order_type: OrderType,
+        model_id: &str,
+        mut tracker: LatencyTracker,
+    ) -> Order {
+        let mut order = Order::new(
+            timestamp, sym, side, price, amount, order_type, VENUE, model_id, &self.ids,
+        );
+        if let Err(e) = self.validate_order(&order) {
+            warn!("Invalid order : {}", e);
+            let _ = order.transition(OrderStatus::Rejected, OffsetDateTime::now_utc());
+            order.misc = e.to_string();
+            order.received_timestamp = OffsetDateTime::now_utc();
+            order.latency_tracker = tracker.to_string();
+            self.orders.insert(order.clone());
+            return order;
+        }
+        tracker.stamp_now(Hop::Send);
+        let result = self.send_child_order(&order);
+        tracker.stamp_now(Hop::Ack);
+        order.latency_tracker = tracker.to_string();
+        match result {
+            Ok(child_order_acceptance_id) => {
+                info!("Sent child order : {}", child_order_acceptance_id);
+                order.order_id = child_order_acceptance_id;
+                let _ = order.transition(OrderStatus::Submitted, OffsetDateTime::now_utc());
+            }
+            Err(e) => {
+                error!("Failed to send child order : {}", e);
+                let _ = order.transition(OrderStatus::Rejected, OffsetDateTime::now_utc());
+                order.misc = e.to_string();
+            }
+        }
+        order.received_timestamp = OffsetDateTime::now_utc();
+        self.orders.insert(order.clone());
+        order
+    }
+
+    fn cancel_order(&mut self, timestamp: OffsetDateTime, order_id: &str) -> Option<Order> {
+        let product_code = match self.orders.get(order_id) {
+            Some(order) => self.product_code(&order.sym),
+            None => {
+                warn!("Cannot cancel unknown order {}", order_id);
+                return None;
+            }
+        };
+        let result = self.cancel_child_order(&product_code, order_id);
+        let order = self.orders.update(order_id, |order| {
+            order.timestamp = timestamp;
+            order.received_timestamp = OffsetDateTime::now_utc();
+            // the cancel is confirmed by child_order_events or getchildorders.
+            match result {
+                Ok(()) => {
+                    let _ = order.transition(OrderStatus::PendingCancel, order.received_timestamp);
+                }
+                Err(e) => {
+                    error!("Failed to cancel child order {} : {}", order.order_id, e);
+                    order.misc = e.to_string();
+                }
+            }
+        });
+        order.cloned()
+    }
+
+    fn get_order_status(&mut self, order_id: &str) -> Option<Order> {
+        let product_code = self.product_code(&self.orders.get(order_id)?.sym);
+        match self.get_child_orders(&product_code, Some(order_id), None) {
+            Ok(child_orders) => {
+                if let Some(child_order) = child_orders.first() {
+                    self.orders.update(order_id, |order| {
+                        let executed_amount = order.executed_amount;
+                        let now = OffsetDateTime::now_utc();
+                        child_order.update_order(order, now);
+                        if order.executed_amount != executed_amount {
+                            LatencyTracker::stamp_field(&mut order.latency_tracker, Hop::Fill, now);
+                        }
+                    });
+                }
+            }
+            Err(e) => warn!("Failed to get child order {} : {}", order_id, e),
+        }
+        self.orders.get(order_id).cloned()
+    }
+
+    fn get_open_orders(&mut self, model_id: Option<&str>) -> Vec<Order> {
+        self.orders
+            .open_orders(None, None, model_id)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    fn get_positions(&mut self) -> Vec<Position> {
+        let mut positions = vec![];
+        for product_code in self.margin_products.clone() {
+            match self.get_position_details(&product_code) {
+                Ok(details) => {
+                    // bitFlyer returns one entry per open lot, net them per product.
+                    let sym = self.sym(&product_code);
+                    let mut lots = details.iter().map(|d| d.to_position(&sym, "", &self.ids));
+                    if let Some(mut position) = lots.next() {
+                        for lot in lots {
+                            position.amount += lot.amount;
+                            position.cost += lot.cost;
+                        }
+                        positions.push(position);
+                    }
+                }
+                Err(e) => error!("Failed to get positions of {} : {}", product_code, e),
+            }
+        }
+        positions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::mock_server::MockServer;
+
+    const CHILD_ORDER: &str = r#"[{"id": 1, "child_order_id": "JOR1", "product_code": "FX_BTC_JPY",
+        "side": "BUY", "child_order_type": "LIMIT", "price": 5000000.0, "average_price": 5000000.0,
+        "size": 0.5, "child_order_state": "CANCELED", "expire_date": "2026-11-18T00:00:00",
+        "child_order_date": "2026-10-19T00:00:00", "child_order_acceptance_id": "JRF1",
+        "outstanding_size": 0.0, "cancel_size": 0.2, "executed_size": 0.3, "total_commission": 0.0}]"#;
+
+    fn submit(client: &mut BitFlyerClient) -> Order {
+        client.submit_order(
+            OffsetDateTime::now_utc(),
+            "FXBTCJPY",
+            Side::Buy,
+            Price::from_f64(5000000.0),
+            Qty::from_f64(0.5),
+            OrderType::Limit,
+            "model",
+        )
+    }
+
+    #[test]
+    fn requests_are_signed_over_timestamp_method_path_and_body() {
+        let server = MockServer::start(vec![
+            (200, vec![], r#"{"child_order_acceptance_id": "JRF1"}"#),
+            (200, vec![], "[]"),
+        ]);
+        let mut client = BitFlyerClient::with_url(&server.url, "key", "secret");
+        let order = submit(&mut client);
+        assert_eq!(order.order_id, "JRF1");
+        assert_eq!(order.order_status, OrderStatus::Submitted);
+        client.get_position_details("FX_BTC_JPY").unwrap();
+
+        let requests = server.requests();
+        let post = &requests[0];
+        assert_eq!(post.method, "POST");
+        assert_eq!(post.path, "/v1/me/sendchildorder");
+        assert_eq!(post.header("ACCESS-KEY"), "key");
+        let body: serde_json::Value = serde_json::from_str(&post.body).unwrap();
+        assert_eq!(body["product_code"], "FX_BTC_JPY");
+        assert_eq!(body["child_order_type"], "LIMIT");
+        assert_eq!(body["side"], "BUY");
+        let message = format!(
+            "{}POST/v1/me/sendchildorder{}",
+            post.header("ACCESS-TIMESTAMP"),
+            post.body
+        );
+        assert_eq!(post.header("ACCESS-SIGN"), sign("secret", &message));
+
+        let get = &requests[1];
+        assert_eq!(get.path, "/v1/me/getpositions?product_code=FX_BTC_JPY");
+        let message = format!("{}GET{}", get.header("ACCESS-TIMESTAMP"), get.path);
+        assert_eq!(get.header("ACCESS-SIGN"), sign("secret", &message));
+    }
+
+    #[test]
+    fn cancel_and_order_status_round_trip() {
+        let server = MockServer::start(vec![
+            (200, vec![], r#"{"child_order_acceptance_id": "JRF1"}"#),
+            (200, vec![], ""),
+            (200, vec![], CHILD_ORDER),
+        ]);
+        let mut client = BitFlyerClient::with_url(&server.url, "key", "secret");
+        submit(&mut client);
+
+        let order = client
+            .cancel_order(OffsetDateTime::now_utc(), "JRF1")
+            .unwrap();
+        assert_eq!(order.order_status, OrderStatus::PendingCancel);
+
+        let order = client.get_order_status("JRF1").unwrap();
+        assert_eq!(order.order_status, OrderStatus::Canceled);
+        assert_eq!(order.executed_amount, Qty::from_f64(0.3));
+        assert_eq!(
+            client.orders().get("JRF1").unwrap().order_status,
+            OrderStatus::Canceled
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests[1].path, "/v1/me/cancelchildorder");
+        let body: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body["product_code"], "FX_BTC_JPY");
+        assert_eq!(body["child_order_acceptance_id"], "JRF1");
+        assert_eq!(
+            requests[2].path,
+            "/v1/me/getchildorders?product_code=FX_BTC_JPY&child_order_acceptance_id=JRF1"
+        );
+    }
+
+    #[test]
+    fn unknown_orders_are_not_sent_to_the_venue() {
+        let server = MockServer::start(vec![]);
+        let mut client = BitFlyerClient::with_url(&server.url, "key", "secret");
+        assert!(client
+            .cancel_order(OffsetDateTime::now_utc(), "unknown")
+            .is_none());
+        assert!(client.get_order_status("unknown").is_none());
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn exhausted_rate_limit_blocks_requests_until_reset() {
+        let reset = OffsetDateTime::now_utc().unix_timestamp() + 60;
+        let server = MockServer::start(vec![(
+            200,
+            vec![
+                ("X-RateLimit-Period", "300".to_string()),
+                ("X-RateLimit-Remaining", "0".to_string()),
+                ("X-RateLimit-Reset", reset.to_string()),
+            ],
+            r#"{"collateral": 1.0, "open_position_pnl": 0.0, "require_collateral": 0.0, "keep_rate": 0.0}"#,
+        )]);
+        let mut client = BitFlyerClient::with_url(&server.url, "key", "secret");
+        assert_eq!(client.get_collateral().unwrap().collateral, 1.0);
+
+        let rate_limit = client.rate_limit();
+        assert_eq!(rate_limit.period, Some(300));
+        assert_eq!(rate_limit.remaining, Some(0));
+        assert_eq!(
+            rate_limit.reset,
+            Some(OffsetDateTime::from_unix_timestamp(reset).unwrap())
+        );
+        match client.get_collateral() {
+            Err(BitFlyerApiError::RateLimited(until)) => assert_eq!(until.unix_timestamp(), reset),
+            other => panic!("expected rate limited, got {:?}", other),
+        }
+        let order = submit(&mut client);
+        assert_eq!(order.order_status, OrderStatus::Rejected);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn error_status_rejects_the_order() {
+        let server = MockServer::start(vec![(
+            400,
+            vec![],
+            r#"{"status": -205, "error_message": "Margin amount is insufficient"}"#,
+        )]);
+        let mut client = BitFlyerClient::with_url(&server.url, "key", "secret");
+        let order = submit(&mut client);
+        assert_eq!(order.order_status, OrderStatus::Rejected);
+        assert!(order.misc.contains("Margin amount is insufficient"));
+    }
+
+    #[test]
+    fn order_events_update_the_tracked_order() {
+        let server = MockServer::start(vec![(
+            200,
+            vec![],
+            r#"{"child_order_acceptance_id": "JRF1"}"#,
+        )]);
+        let mut client = BitFlyerClient::with_url(&server.url, "key", "secret");
+        let mut event = submit(&mut client);
+
+        event.order_status = OrderStatus::Accepted;
+        client.on_order_event(InternalData::Order(event.clone()));
+        event.executed_amount = Qty::from_f64(0.2);
+        event.order_status = OrderStatus::PartiallyFilled;
+        client.on_order_event(InternalData::Order(event.clone()));
+
+        let order = client.orders().get("JRF1").unwrap();
+        assert_eq!(order.order_status, OrderStatus::PartiallyFilled);
+        assert_eq!(order.executed_amount, Qty::from_f64(0.2));
+        let tracker: LatencyTracker = order.latency_tracker.parse().unwrap();
+        assert!(tracker.get(Hop::Fill).is_some());
+
+        // a late event of a terminal order is not applied
+        event.order_status = OrderStatus::Canceled;
+        client.on_order_event(InternalData::Order(event.clone()));
+        event.order_status = OrderStatus::Accepted;
+        client.on_order_event(InternalData::Order(event));
+        assert_eq!(
+            client.orders().get("JRF1").unwrap().order_status,
+            OrderStatus::Canceled
+        );
+    }
+}
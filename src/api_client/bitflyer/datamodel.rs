@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::order::{Order, OrderStatus, OrderType};
+use crate::datamodels::position::Position;
+use crate::datamodels::types::{Price, Qty, Side};
+
+/// `sym` -> bitFlyer `product_code`. e.g. BTCJPY -> BTC_JPY, FXBTCJPY -> FX_BTC_JPY
+/// Fallback for instruments missing from the `InstrumentRegistry`.
+pub fn to_product_code(sym: &str) -> String {
+    let (prefix, pair) = match sym.strip_prefix("FX") {
+        Some(pair) => ("FX_", pair),
+        None => ("", sym),
+    };
+    let (base, quote) = pair.split_at(pair.len().saturating_sub(3));
+    format!("{}{}_{}", prefix, base, quote)
+}
+
+/// bitFlyer `product_code` -> `sym`.
+pub fn to_sym(product_code: &str) -> String {
+    product_code.replace("_", "")
+}
+
+pub fn to_side(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "BUY",
+        Side::Sell => "SELL",
+    }
+}
+
+pub fn from_side(side: &str) -> Side {
+    if side == "BUY" {
+        Side::Buy
+    } else {
+        Side::Sell
+    }
+}
+
+pub fn to_child_order_type(order_type: &OrderType) -> Option<&'static str> {
+    match order_type {
+        OrderType::Limit => Some("LIMIT"),
+        OrderType::Market => Some("MARKET"),
+        // stop orders are parent orders (`sendparentorder`) on bitFlyer.
+        OrderType::StopLimit => None,
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SendChildOrderRequest {
+    pub product_code: String,
+    pub child_order_type: String,
+    pub side: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    pub size: f64,
+    pub time_in_force: String,
+}
+
+impl SendChildOrderRequest {
+    pub fn from_order(order: &Order, product_code: &str) -> Option<Self> {
+        let child_order_type = to_child_order_type(&order.order_type)?;
+        Some(Self {
+            product_code: product_code.to_string(),
+            child_order_type: child_order_type.to_string(),
+            side: to_side(order.side).to_string(),
+            price: match order.order_type {
+                OrderType::Market => None,
+                _ => Some(order.price.to_f64()),
+            },
+            size: order.amount.to_f64(),
+            time_in_force: "GTC".to_string(),
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SendChildOrderResponse {
+    pub child_order_acceptance_id: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CancelChildOrderRequest {
+    pub product_code: String,
+    pub child_order_acceptance_id: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ChildOrder {
+    pub id: u64,
+    pub child_order_id: String,
+    pub product_code: String,
+    pub side: String,
+    pub child_order_type: String,
+    pub price: f64,
+    pub average_price: f64,
+    pub size: f64,
+    pub child_order_state: String,
+    pub expire_date: String,
+    pub child_order_date: String,
+    pub child_order_acceptance_id: String,
+    pub outstanding_size: f64,
+    pub cancel_size: f64,
+    pub executed_size: f64,
+    pub total_commission: f64,
+}
+
+impl ChildOrder {
+    pub fn to_order(
+        &self,
+        sym: &str,
+        model_id: &str,
+        timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> Order {
+        let mut order = Order::new(
+            timestamp,
+            sym,
+            from_side(&self.side),
+            Price::from_f64(self.price),
+            Qty::from_f64(self.size),
+            match self.child_order_type.as_str() {
+                "MARKET" => OrderType::Market,
+                _ => OrderType::Limit,
+            },
+            "bitflyer",
+            model_id,
+            ids,
+        );
+        order.order_id = self.child_order_acceptance_id.clone();
+        let _ = order.transition(OrderStatus::Submitted, timestamp);
+        self.update_order(&mut order, timestamp);
+        order
+    }
+
+    /// Apply the venue snapshot to our order.
+    pub fn update_order(&self, order: &mut Order, timestamp: OffsetDateTime) {
+        let unseen_fill = Qty::from_f64(self.executed_size) - order.executed_amount;
+        if unseen_fill > Qty::ZERO {
+            let _ = order.fill(unseen_fill, timestamp);
+        }
+        let status = match self.child_order_state.as_str() {
+            "ACTIVE" if self.executed_size > 0.0 => OrderStatus::PartiallyFilled,
+            "ACTIVE" => OrderStatus::Accepted,
+            "COMPLETED" => OrderStatus::Filled,
+            "CANCELED" => OrderStatus::Canceled,
+            "EXPIRED" => OrderStatus::Expired,
+            "REJECTED" => OrderStatus::Rejected,
+            _ => return,
+        };
+        if order.order_status != status {
+            let _ = order.transition(status, timestamp);
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PositionDetail {
+    pub product_code: String,
+    pub side: String,
+    pub price: f64,
+    pub size: f64,
+    pub commission: f64,
+    pub swap_point_accumulate: f64,
+    pub require_collateral: f64,
+    pub open_date: String,
+    pub leverage: f64,
+    pub pnl: f64,
+    pub sfd: f64,
+}
+
+impl PositionDetail {
+    pub fn to_position(&self, sym: &str, model_id: &str, ids: &IdSource) -> Position {
+        let amount = Qty::from_f64(self.size).signed(from_side(&self.side));
+        let mut position = Position::new(sym, "bitflyer", model_id, ids);
+        position.amount = amount;
+        position.cost = amount.notional(Price::from_f64(self.price));
+        position
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Collateral {
+    pub collateral: f64,
+    pub open_position_pnl: f64,
+    pub require_collateral: f64,
+    pub keep_rate: f64,
+}
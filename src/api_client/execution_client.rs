@@ -1,21 +1,47 @@
 use time::OffsetDateTime;
 
+use crate::datamodels::latency::{Hop, LatencyTracker};
 use crate::datamodels::order::{Order, OrderType};
 use crate::datamodels::position::Position;
+use crate::datamodels::types::{Price, Qty, Side};
 
 pub trait ExecutionClient {
+    #[allow(clippy::too_many_arguments)]
     fn submit_order(
         &mut self,
         timestamp: OffsetDateTime,
         sym: &str,
-        side: i16,
-        price: f64,
-        amount: f64,
+        side: Side,
+        price: Price,
+        amount: Qty,
         order_type: OrderType,
         model_id: &str,
     ) -> Order;
-    fn cancel_order(&mut self, timestamp: OffsetDateTime, order_id: &String) -> Order;
+    /// `submit_order` continuing the stamps of the tick that triggered it, see `LatencyTracker`.
+    /// Clients append `send` and `ack`, and `fill` when they see the order filled.
+    #[allow(clippy::too_many_arguments)]
+    fn submit_order_traced(
+        &mut self,
+        timestamp: OffsetDateTime,
+        sym: &str,
+        side: Side,
+        price: Price,
+        amount: Qty,
+        order_type: OrderType,
+        model_id: &str,
+        mut tracker: LatencyTracker,
+    ) -> Order {
+        tracker.stamp_now(Hop::Send);
+        let mut order =
+            self.submit_order(timestamp, sym, side, price, amount, order_type, model_id);
+        tracker.stamp_now(Hop::Ack);
+        order.latency_tracker = tracker.to_string();
+        order
+    }
+    /// `None` when `order_id` is unknown to the client.
+    fn cancel_order(&mut self, timestamp: OffsetDateTime, order_id: &str) -> Option<Order>;
     // fn amend_order(&mut self, order_id: &String, price: f64, amount: f64) -> Order;
-    fn get_order_status(&mut self, order_id: &String) -> Option<&Order>;
-    fn get_positions() -> Position;
+    fn get_order_status(&mut self, order_id: &str) -> Option<Order>;
+    fn get_open_orders(&mut self, model_id: Option<&str>) -> Vec<Order>;
+    fn get_positions(&mut self) -> Vec<Position>;
 }
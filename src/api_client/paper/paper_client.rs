@@ -0,0 +1,249 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+use crate::api_client::backtest::backtest_client::BacktestClient;
+use crate::api_client::execution_client::ExecutionClient;
+use crate::datamodels::enums::MarketData;
+use crate::datamodels::execution::Execution;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::latency::LatencyTracker;
+use crate::datamodels::order::{Order, OrderType};
+use crate::datamodels::position::Position;
+use crate::datamodels::types::{Price, Qty, Side};
+use crate::feedhandler::orderlogger::OrderLogger;
+use crate::refdata::calendar::MaintenanceCalendar;
+use crate::refdata::instrument::InstrumentRegistry;
+
+type Shared<T> = Arc<Mutex<T>>;
+
+/// Dry-run `ExecutionClient` on live market data.
+///
+/// Orders are matched by `BacktestClient` against the live feed in wall-clock time,
+/// and orders and fills are logged under `Order/paper/<venue>` and `Execution/paper/<venue>`.
+/// Clones share the same state, so one clone can be registered as a feed callback
+/// while another is handed to the strategy.
+#[derive(Clone)]
+pub struct PaperClient {
+    client: Shared<BacktestClient<'static>>,
+    logger: Shared<OrderLogger>,
+}
+
+impl PaperClient {
+    pub fn new(venue: &'static str) -> Self {
+        Self {
+            client: Arc::new(Mutex::new(BacktestClient::new(venue))),
+            logger: Arc::new(Mutex::new(OrderLogger::new(
+                Path::new("paper").join(venue).to_str().unwrap(),
+            ))),
+        }
+    }
+
+    /// See `BacktestClient::set_instruments`.
+    pub fn set_instruments(&mut self, instruments: Arc<InstrumentRegistry>) {
+        self.client.lock().unwrap().set_instruments(instruments);
+    }
+
+    /// See `BacktestClient::set_calendar`.
+    pub fn set_calendar(&mut self, calendar: Arc<MaintenanceCalendar>) {
+        self.client.lock().unwrap().set_calendar(calendar);
+    }
+
+    /// See `BacktestClient::set_ids`, also used for the execution ids.
+    pub fn set_ids(&mut self, ids: IdSource) {
+        self.client.lock().unwrap().set_ids(ids);
+    }
+
+    /// Feed callback, e.g. `BitFlyerSocketIo::set_callback`.
+    pub fn callback(&mut self, data: MarketData) {
+        let (filled_orders, ids) = {
+            let mut client = self.client.lock().unwrap();
+            (client.next(data), client.ids().clone())
+        };
+        let mut logger = self.logger.lock().unwrap();
+        for order in filled_orders {
+            let mut execution = Execution::new(
+                &order.sym,
+                order.side,
+                order.price,
+                order.executed_amount,
+                &order.venue,
+                order.order_id.clone(),
+                ids.next_id(),
+                &ids,
+            );
+            execution.market_created_timestamp = order.market_created_timestamp;
+            execution.received_timestamp = order.received_timestamp;
+            execution.latency_tracker = order.latency_tracker.clone();
+            logger.log_execution(&execution);
+            logger.log_order(&order);
+        }
+    }
+}
+
+impl ExecutionClient for PaperClient {
+    fn submit_order(
+        &mut self,
+        timestamp: OffsetDateTime,
+        sym: &str,
+        side: Side,
+        price: Price,
+        amount: Qty,
+        order_type: OrderType,
+        model_id: &str,
+    ) -> Order {
+        self.submit_order_traced(
+            timestamp,
+            sym,
+            side,
+            price,
+            amount,
+            order_type,
+            model_id,
+            LatencyTracker::new(),
+        )
+    }
+
+    fn submit_order_traced(
+        &mut self,
+        _timestamp: OffsetDateTime,
+        sym: &str,
+        side: Side,
+        price: Price,
+        amount: Qty,
+        order_type: OrderType,
+        model_id: &str,
+        tracker: LatencyTracker,
+    ) -> Order {
+        // live feed is matched on the wall clock, not on the strategy's timestamp.
+        let order = self.client.lock().unwrap().submit_order_traced(
+            OffsetDateTime::now_utc(),
+            sym,
+            side,
+            price,
+            amount,
+            order_type,
+            model_id,
+            tracker,
+        );
+        self.logger.lock().unwrap().log_order(&order);
+        order
+    }
+
+    fn cancel_order(&mut self, _timestamp: OffsetDateTime, order_id: &str) -> Option<Order> {
+        let order = self
+            .client
+            .lock()
+            .unwrap()
+            .cancel_order(OffsetDateTime::now_utc(), order_id)?;
+        self.logger.lock().unwrap().log_order(&order);
+        Some(order)
+    }
+
+    fn get_order_status(&mut self, order_id: &str) -> Option<Order> {
+        self.client.lock().unwrap().get_order_status(order_id)
+    }
+
+    fn get_open_orders(&mut self, model_id: Option<&str>) -> Vec<Order> {
+        self.client.lock().unwrap().get_open_orders(model_id)
+    }
+
+    fn get_positions(&mut self) -> Vec<Position> {
+        self.client.lock().unwrap().get_positions()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodels::marketdata::{MarketTrade, Rate};
+    use crate::datamodels::order::OrderStatus;
+    use crate::feedhandler::textwriter;
+    use time::Duration;
+
+    fn rate(timestamp: OffsetDateTime, best_bid: f64, best_ask: f64) -> MarketData {
+        MarketData::Rate(Rate {
+            timestamp,
+            market_created_timestamp: timestamp,
+            received_timestamp: timestamp,
+            sym: "FXBTCJPY".to_string(),
+            venue: "paper_test".to_string(),
+            category: "".to_string(),
+            best_bid,
+            best_ask,
+            mid_price: (best_bid + best_ask) / 2.0,
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        })
+    }
+
+    fn trade(timestamp: OffsetDateTime, side: Side, price: f64) -> MarketData {
+        MarketData::MarketTrade(MarketTrade {
+            timestamp,
+            market_created_timestamp: timestamp,
+            received_timestamp: timestamp,
+            sym: "FXBTCJPY".to_string(),
+            venue: "paper_test".to_string(),
+            category: "".to_string(),
+            side,
+            price: Price::from_f64(price),
+            amount: Qty::from_f64(1.0),
+            trade_id: "1".to_string(),
+            order_ids: "".to_string(),
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        })
+    }
+
+    #[test]
+    fn limit_order_fills_on_a_live_trade_through_it() {
+        let root = std::env::temp_dir().join(format!("ralpha-paper-{}", std::process::id()));
+        let _ = textwriter::set_root(&root);
+        let mut paper = PaperClient::new("paper_test");
+        let mut feed = paper.clone();
+
+        let order = paper.submit_order(
+            OffsetDateTime::UNIX_EPOCH,
+            "FXBTCJPY",
+            Side::Buy,
+            Price::from_f64(100.0),
+            Qty::from_f64(0.5),
+            OrderType::Limit,
+            "model",
+        );
+        // submitted on the wall clock, not on the strategy's timestamp
+        assert!(order.timestamp > OffsetDateTime::UNIX_EPOCH);
+        let live = order.market_created_timestamp + Duration::seconds(1);
+        feed.callback(rate(live, 100.0, 101.0));
+        // a sell above the bid does not reach the order
+        feed.callback(trade(live, Side::Sell, 100.5));
+        assert_eq!(paper.get_open_orders(Some("model")).len(), 1);
+        feed.callback(trade(live, Side::Sell, 100.0));
+
+        let filled = paper.get_order_status(&order.order_id).unwrap();
+        assert_eq!(filled.order_status, OrderStatus::Filled);
+        assert_eq!(filled.executed_amount, Qty::from_f64(0.5));
+        assert!(paper.get_open_orders(Some("model")).is_empty());
+        let position = &paper.get_positions()[0];
+        assert_eq!(position.amount, Qty::from_f64(0.5));
+
+        let root = textwriter::root();
+        let executions = std::fs::read_to_string(root.join("Execution/paper/paper_test")).unwrap();
+        let execution: Execution =
+            serde_json::from_str(executions.lines().last().unwrap()).unwrap();
+        assert_eq!(execution.source_order_id, order.order_id);
+        assert_eq!(execution.amount, Qty::from_f64(0.5));
+        let orders = std::fs::read_to_string(root.join("Order/paper/paper_test")).unwrap();
+        let logged: Vec<Order> = orders
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(logged.len(), 2);
+        assert_eq!(logged[0].order_status, OrderStatus::Submitted);
+        assert_eq!(logged[1].order_status, OrderStatus::Filled);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod args;
+pub mod convert;
+pub mod inspect;
+pub mod latency;
+pub mod quality;
+pub mod replay;
+pub mod run;
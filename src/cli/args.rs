@@ -0,0 +1,147 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+pub const DEFAULT_CONFIG: &str = "config/ralpha.toml";
+
+/// Market data recorder, backtester and trading engine.
+#[derive(Parser, Debug)]
+#[command(name = "ralpha", version)]
+pub struct Cli {
+    /// Configuration file.
+    #[arg(short, long, global = true, default_value = DEFAULT_CONFIG)]
+    pub config: PathBuf,
+    /// Override a config value, e.g. `--set risk.max_position=0.2`. Repeatable.
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    pub overrides: Vec<String>,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the configured feed handlers and record their ticks.
+    Record,
+    /// Print recorded ticks of some venues as JSON lines in exchange time order.
+    Replay(ReplayArgs),
+    /// Replay recorded ticks through the configured strategies and the backtest client.
+    Backtest(ReplayArgs),
+    /// Record and run the configured strategies against the paper client.
+    Paper,
+    /// Record and run the configured strategies on the venue.
+    Live,
+    /// Convert a tick stream or file from JSON lines to MessagePack or Parquet.
+    Convert(ConvertArgs),
+    /// Print stats, time gaps and a sample of a tick stream or file.
+    Inspect(InspectArgs),
+    /// Reconstruct the `MarketBook` of a sym at a timestamp from the recorded books.
+    BookAt(BookAtArgs),
+    /// Percentiles of the hop to hop latencies of recorded orders, executions or ticks.
+    Latency(LatencyArgs),
+    /// Per day data quality report of recorded ticks and the intervals usable for backtests.
+    Quality(QualityArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// Venues to replay, defaults to the venues of the configured feeds.
+    #[arg(long = "venue")]
+    pub venues: Vec<String>,
+    /// RFC 3339 start, inclusive.
+    #[arg(long, value_parser = parse_timestamp)]
+    pub start: Option<OffsetDateTime>,
+    /// RFC 3339 end, exclusive.
+    #[arg(long, value_parser = parse_timestamp)]
+    pub end: Option<OffsetDateTime>,
+    /// Only replay data within the intervals of a file written by `quality --output`.
+    #[arg(long)]
+    pub usable: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Format {
+    Msgpack,
+    Parquet,
+}
+
+#[derive(Args, Debug)]
+pub struct ConvertArgs {
+    /// Stream name under the tickerplant root, e.g. `MarketTrade/bitflyer`, or a file.
+    pub input: String,
+    #[arg(short, long, value_enum, default_value_t = Format::Parquet)]
+    pub format: Format,
+    #[arg(short, long)]
+    pub output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct InspectArgs {
+    /// Stream name under the tickerplant root, e.g. `MarketTrade/bitflyer`, or a file.
+    pub input: String,
+    /// Report silences longer than this many seconds.
+    #[arg(long, default_value_t = 60.0)]
+    pub gap_secs: f64,
+    /// Max gaps printed.
+    #[arg(long, default_value_t = 20)]
+    pub max_gaps: usize,
+    /// Records printed from the start of the stream.
+    #[arg(long, default_value_t = 3)]
+    pub sample: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct BookAtArgs {
+    #[arg(long)]
+    pub venue: String,
+    #[arg(long)]
+    pub sym: String,
+    /// RFC 3339 timestamp, matched against `market_created_timestamp`.
+    #[arg(long, value_parser = parse_timestamp)]
+    pub at: OffsetDateTime,
+    /// Levels printed per side.
+    #[arg(long, default_value_t = 10)]
+    pub levels: usize,
+    /// Print the whole book as JSON instead of a ladder.
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct LatencyArgs {
+    /// Stream names under the tickerplant root, e.g. `Order/paper/bitflyer`, or files.
+    #[arg(required = true)]
+    pub inputs: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct QualityArgs {
+    /// Venues to check, defaults to the venues of the configured feeds.
+    #[arg(long = "venue")]
+    pub venues: Vec<String>,
+    /// RFC 3339 start of the expected data, inclusive.
+    #[arg(long, value_parser = parse_timestamp)]
+    pub start: Option<OffsetDateTime>,
+    /// RFC 3339 end of the expected data, exclusive.
+    #[arg(long, value_parser = parse_timestamp)]
+    pub end: Option<OffsetDateTime>,
+    /// Report silences of a channel longer than this many seconds.
+    #[arg(long, default_value_t = 60.0)]
+    pub gap_secs: f64,
+    /// Report prices further than this many percent from the recent median.
+    #[arg(long, default_value_t = 5.0)]
+    pub outlier_pct: f64,
+    /// Max gaps printed per channel.
+    #[arg(long, default_value_t = 10)]
+    pub max_gaps: usize,
+    /// Print the report as JSON instead of text.
+    #[arg(long)]
+    pub json: bool,
+    /// Write the usable intervals to this file, for `backtest --usable`.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+fn parse_timestamp(value: &str) -> Result<OffsetDateTime, String> {
+    OffsetDateTime::parse(value, &Rfc3339).map_err(|e| format!("{}: {}", value, e))
+}
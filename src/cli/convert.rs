@@ -0,0 +1,204 @@
+use parquet::basic::Compression;
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+use parquet::schema::parser::parse_message_type;
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::cli::args::{ConvertArgs, Format};
+use crate::feedhandler::tickreader::TickReader;
+
+const ROW_GROUP_SIZE: usize = 65536;
+
+/// Convert a JSON lines stream, returns the number of records written.
+pub fn convert(args: &ConvertArgs) -> Result<usize, Box<dyn Error>> {
+    let reader = TickReader::from_path_or_name(&args.input);
+    if reader.files().is_empty() {
+        return Err(format!("no tick file for {}", args.input).into());
+    }
+    let records = reader.records::<Value>();
+    match args.format {
+        Format::Msgpack => write_msgpack(records, &args.output),
+        Format::Parquet => write_parquet(records, &args.output),
+    }
+}
+
+/// Records as consecutive MessagePack maps.
+fn write_msgpack<I: Iterator<Item = Value>>(
+    records: I,
+    output: &Path,
+) -> Result<usize, Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(output)?);
+    let mut count = 0;
+    for record in records {
+        rmp_serde::encode::write_named(&mut writer, &record)?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ColumnKind {
+    Boolean,
+    Int64,
+    Double,
+    /// RFC 3339 `*timestamp` fields, stored as UTC nanoseconds.
+    Timestamp,
+    Utf8,
+    /// Nested values such as book ladders, stored as JSON text.
+    Json,
+}
+
+impl ColumnKind {
+    fn of(key: &str, value: &Value) -> ColumnKind {
+        match value {
+            Value::Bool(_) => ColumnKind::Boolean,
+            Value::Number(n) if n.is_f64() => ColumnKind::Double,
+            Value::Number(_) => ColumnKind::Int64,
+            Value::String(s) if key.ends_with("timestamp") && parse_timestamp(s).is_some() => {
+                ColumnKind::Timestamp
+            }
+            Value::Array(_) | Value::Object(_) => ColumnKind::Json,
+            _ => ColumnKind::Utf8,
+        }
+    }
+
+    fn schema(&self, name: &str) -> String {
+        match self {
+            ColumnKind::Boolean => format!("optional boolean {};", name),
+            ColumnKind::Int64 => format!("optional int64 {};", name),
+            ColumnKind::Double => format!("optional double {};", name),
+            ColumnKind::Timestamp => {
+                format!("optional int64 {} (TIMESTAMP(NANOS,true));", name)
+            }
+            ColumnKind::Utf8 | ColumnKind::Json => format!("optional binary {} (UTF8);", name),
+        }
+    }
+}
+
+fn parse_timestamp(value: &str) -> Option<i64> {
+    let timestamp = OffsetDateTime::parse(value, &Rfc3339).ok()?;
+    i64::try_from(timestamp.unix_timestamp_nanos()).ok()
+}
+
+/// Records as one flat Parquet table, with the columns of the first record.
+/// Missing or mistyped values are written as nulls and extra fields are ignored.
+fn write_parquet<I: Iterator<Item = Value>>(
+    records: I,
+    output: &Path,
+) -> Result<usize, Box<dyn Error>> {
+    let mut records = records.filter_map(|record| match record {
+        Value::Object(map) => Some(map),
+        _ => None,
+    });
+    let first = match records.next() {
+        Some(first) => first,
+        None => return Err("no record to convert".into()),
+    };
+    let columns: Vec<(String, ColumnKind)> = first
+        .iter()
+        .map(|(key, value)| (key.clone(), ColumnKind::of(key, value)))
+        .collect();
+    let schema = format!(
+        "message record {{ {} }}",
+        columns
+            .iter()
+            .map(|(key, kind)| kind.schema(key))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = SerializedFileWriter::new(
+        File::create(output)?,
+        Arc::new(parse_message_type(&schema)?),
+        Arc::new(properties),
+    )?;
+
+    let mut count = 0;
+    let mut rows = vec![first];
+    for record in records {
+        rows.push(record);
+        if rows.len() >= ROW_GROUP_SIZE {
+            count += write_row_group(&mut writer, &columns, &rows)?;
+            rows.clear();
+        }
+    }
+    count += write_row_group(&mut writer, &columns, &rows)?;
+    writer.close()?;
+    Ok(count)
+}
+
+fn write_row_group(
+    writer: &mut SerializedFileWriter<File>,
+    columns: &[(String, ColumnKind)],
+    rows: &[Map<String, Value>],
+) -> Result<usize, Box<dyn Error>> {
+    if rows.is_empty() {
+        return Ok(0);
+    }
+    let mut row_group = writer.next_row_group()?;
+    for (key, kind) in columns {
+        let mut column = row_group.next_column()?.ok_or("schema has fewer columns")?;
+        match kind {
+            ColumnKind::Boolean => {
+                write_column::<BoolType, _>(&mut column, rows, key, Value::as_bool)?
+            }
+            ColumnKind::Int64 => write_column::<Int64Type, _>(&mut column, rows, key, |v| {
+                v.as_i64().or_else(|| v.as_f64().map(|f| f as i64))
+            })?,
+            ColumnKind::Double => {
+                write_column::<DoubleType, _>(&mut column, rows, key, Value::as_f64)?
+            }
+            ColumnKind::Timestamp => write_column::<Int64Type, _>(&mut column, rows, key, |v| {
+                parse_timestamp(v.as_str()?)
+            })?,
+            ColumnKind::Utf8 => write_column::<ByteArrayType, _>(&mut column, rows, key, |v| {
+                v.as_str().map(ByteArray::from)
+            })?,
+            ColumnKind::Json => write_column::<ByteArrayType, _>(&mut column, rows, key, |v| {
+                Some(ByteArray::from(v.to_string().as_str()))
+            })?,
+        }
+        column.close()?;
+    }
+    row_group.close()?;
+    Ok(rows.len())
+}
+
+/// Values of `key` in every row, nulls as definition level 0.
+fn write_column<T, F>(
+    column: &mut SerializedColumnWriter,
+    rows: &[Map<String, Value>],
+    key: &str,
+    value: F,
+) -> Result<(), Box<dyn Error>>
+where
+    T: parquet::data_type::DataType,
+    F: Fn(&Value) -> Option<T::T>,
+{
+    let mut values = Vec::with_capacity(rows.len());
+    let mut levels = Vec::with_capacity(rows.len());
+    for row in rows {
+        match row.get(key).and_then(&value) {
+            Some(v) => {
+                values.push(v);
+                levels.push(1);
+            }
+            None => levels.push(0),
+        }
+    }
+    column
+        .typed::<T>()
+        .write_batch(&values, Some(&levels), None)?;
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::cli::args::{BookAtArgs, InspectArgs};
+use crate::datamodels::marketdata::MarketBook;
+use crate::datamodels::types::Side;
+use crate::feedhandler::tickreader::TickReader;
+
+fn timestamp(record: &Value) -> Option<OffsetDateTime> {
+    let value = record
+        .get("market_created_timestamp")
+        .or_else(|| record.get("timestamp"))?;
+    OffsetDateTime::parse(value.as_str()?, &Rfc3339).ok()
+}
+
+/// Record count per sym, time range, silences longer than `gap_secs` and the first records.
+pub fn inspect(args: &InspectArgs) -> Result<(), Box<dyn Error>> {
+    let reader = TickReader::from_path_or_name(&args.input);
+    if reader.files().is_empty() {
+        return Err(format!("no tick file for {}", args.input).into());
+    }
+    for file in reader.files() {
+        println!("file: {}", file.display());
+    }
+
+    let mut count = 0;
+    let mut syms: BTreeMap<String, usize> = BTreeMap::new();
+    let mut first: Option<OffsetDateTime> = None;
+    let mut last: Option<OffsetDateTime> = None;
+    let mut out_of_order = 0;
+    let mut gaps = vec![];
+    let mut sample = vec![];
+    for record in reader.records::<Value>() {
+        count += 1;
+        let sym = record.get("sym").and_then(Value::as_str).unwrap_or("");
+        *syms.entry(sym.to_string()).or_default() += 1;
+        if sample.len() < args.sample {
+            sample.push(record.to_string());
+        }
+        let timestamp = match timestamp(&record) {
+            Some(timestamp) => timestamp,
+            None => continue,
+        };
+        first.get_or_insert(timestamp);
+        if let Some(last) = last {
+            let elapsed = (timestamp - last).as_seconds_f64();
+            if elapsed < 0.0 {
+                out_of_order += 1;
+            } else if elapsed > args.gap_secs {
+                gaps.push((last, timestamp, elapsed));
+            }
+        }
+        last = Some(last.map_or(timestamp, |last| last.max(timestamp)));
+    }
+
+    println!("records: {}", count);
+    if let (Some(first), Some(last)) = (first, last) {
+        let seconds = (last - first).as_seconds_f64();
+        println!("range: {} .. {} ({:.0}s)", first, last, seconds);
+        if seconds > 0.0 {
+            println!("rate: {:.2} records/s", count as f64 / seconds);
+        }
+    }
+    for (sym, count) in &syms {
+        println!("sym {}: {}", sym, count);
+    }
+    println!("out of order: {}", out_of_order);
+    println!("gaps over {}s: {}", args.gap_secs, gaps.len());
+    for (from, to, seconds) in gaps.iter().take(args.max_gaps) {
+        println!("  {} .. {} ({:.1}s)", from, to, seconds);
+    }
+    if gaps.len() > args.max_gaps {
+        println!("  ... {} more", gaps.len() - args.max_gaps);
+    }
+    for record in sample {
+        println!("sample: {}", record);
+    }
+    Ok(())
+}
+
+/// Latest recorded book of the sym at or before `at`.
+pub fn book_at(args: &BookAtArgs) -> Result<(), Box<dyn Error>> {
+    let reader = TickReader::new(&format!("MarketBook/{}", args.venue));
+    if reader.files().is_empty() {
+        return Err(format!("no MarketBook file for {}", args.venue).into());
+    }
+    let book = reader
+        .records::<MarketBook>()
+        .filter(|book| book.sym == args.sym)
+        .take_while(|book| book.market_created_timestamp <= args.at)
+        .last()
+        .ok_or_else(|| format!("no {} book at or before {}", args.sym, args.at))?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&book)?);
+        return Ok(());
+    }
+    println!(
+        "{} {} at {}: book of {} ({} behind) {}",
+        book.sym,
+        book.venue,
+        args.at,
+        book.market_created_timestamp,
+        args.at - book.market_created_timestamp,
+        book.misc
+    );
+    let asks: Vec<_> = book.levels(Side::Sell).take(args.levels).collect();
+    for (price, amount) in asks.into_iter().rev() {
+        println!(
+            "{:>16} {:>16} {:<16}",
+            "",
+            price.to_string(),
+            amount.to_string()
+        );
+    }
+    for (price, amount) in book.levels(Side::Buy).take(args.levels) {
+        println!(
+            "{:>16} {:>16} {:<16}",
+            amount.to_string(),
+            price.to_string(),
+            ""
+        );
+    }
+    if let (Some(mid), Some(spread)) = (book.mid_price(), book.spread()) {
+        println!("mid {} spread {}", mid, spread);
+    }
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::cli::args::LatencyArgs;
+use crate::datamodels::latency::{Hop, LatencyTracker};
+use crate::feedhandler::tickreader::TickReader;
+
+/// `latency_tracker` of orders, executions and positions, or the exchange, receive and parse
+/// timestamps of ticks.
+fn tracker(record: &Value) -> Option<LatencyTracker> {
+    let field = record
+        .get("latency_tracker")
+        .and_then(Value::as_str)
+        .unwrap_or("");
+    if !field.is_empty() {
+        return field.parse().ok();
+    }
+    let timestamp = |key: &str| {
+        let value = record.get(key)?.as_str()?;
+        OffsetDateTime::parse(value, &Rfc3339)
+            .ok()
+            .filter(|t| *t != OffsetDateTime::UNIX_EPOCH)
+    };
+    let mut tracker = LatencyTracker::new();
+    tracker.stamp(Hop::Exchange, timestamp("market_created_timestamp")?);
+    if let Some(received) = timestamp("received_timestamp") {
+        tracker.stamp(Hop::Receive, received);
+    }
+    tracker.stamp(Hop::Parse, timestamp("timestamp")?);
+    Some(tracker)
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Percentiles of every hop to hop latency, in milliseconds.
+pub fn latency(args: &LatencyArgs) -> Result<(), Box<dyn Error>> {
+    for input in &args.inputs {
+        let reader = TickReader::from_path_or_name(input);
+        if reader.files().is_empty() {
+            return Err(format!("no tick file for {}", input).into());
+        }
+
+        let mut records = 0;
+        let mut hops: BTreeMap<(Hop, Hop), Vec<f64>> = BTreeMap::new();
+        let mut totals: BTreeMap<(Hop, Hop), Vec<f64>> = BTreeMap::new();
+        for record in reader.records::<Value>() {
+            let tracker = match tracker(&record) {
+                Some(tracker) => tracker,
+                None => continue,
+            };
+            records += 1;
+            let stamps = tracker.stamps();
+            for pair in stamps.windows(2) {
+                let ((from, from_t), (to, to_t)) = (pair[0], pair[1]);
+                let millis = (to_t - from_t).as_seconds_f64() * 1000.0;
+                hops.entry((from, to)).or_default().push(millis);
+            }
+            if let (Some((first, first_t)), Some((last, last_t))) = (stamps.first(), stamps.last())
+            {
+                if stamps.len() > 2 {
+                    let millis = (*last_t - *first_t).as_seconds_f64() * 1000.0;
+                    totals.entry((*first, *last)).or_default().push(millis);
+                }
+            }
+        }
+
+        println!("{}: {} records with latency stamps", input, records);
+        println!(
+            "{:<24} {:>9} {:>10} {:>10} {:>10} {:>10}",
+            "hop", "count", "p50_ms", "p90_ms", "p99_ms", "max_ms"
+        );
+        let rows = hops
+            .into_iter()
+            .map(|((from, to), values)| (format!("{} -> {}", from, to), values))
+            .chain(
+                totals
+                    .into_iter()
+                    .map(|((from, to), values)| (format!("total {} -> {}", from, to), values)),
+            );
+        for (name, mut values) in rows {
+            values.sort_by(f64::total_cmp);
+            println!(
+                "{:<24} {:>9} {:>10.3} {:>10.3} {:>10.3} {:>10.3}",
+                name,
+                values.len(),
+                percentile(&values, 50.0),
+                percentile(&values, 90.0),
+                percentile(&values, 99.0),
+                values[values.len() - 1]
+            );
+        }
+        println!();
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::analytics::data_quality::{DataQuality, DayReport, QualityConfig};
+use crate::cli::args::QualityArgs;
+use crate::cli::replay::venues;
+use crate::config::app_config::AppConfig;
+use crate::feedhandler::replay::MarketDataReplay;
+
+/// Check the recorded ticks of some venues, print a report per day and write the usable
+/// intervals.
+pub fn quality(config: &AppConfig, args: &QualityArgs) -> Result<(), Box<dyn Error>> {
+    let calendar = Arc::new(config.load_calendar()?);
+    let quality_config = QualityConfig {
+        gap_secs: args.gap_secs,
+        outlier_pct: args.outlier_pct,
+        ..QualityConfig::default()
+    };
+    let mut quality = DataQuality::new(quality_config, calendar).between(args.start, args.end);
+    let venues = venues(config, &args.venues);
+    for data in MarketDataReplay::new(&venues).between(args.start, args.end) {
+        quality.add(&data);
+    }
+    let (reports, usable) = quality.finish();
+    if reports.is_empty() {
+        return Err(format!("no tick data for {:?}", venues).into());
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        for report in &reports {
+            print_report(report, args.max_gaps);
+        }
+    }
+    if let Some(output) = &args.output {
+        usable
+            .to_file(output)
+            .map_err(|e| format!("failed to write {} : {}", output.display(), e))?;
+        let count: usize = usable.venues.values().map(Vec::len).sum();
+        eprintln!("{} usable intervals written to {}", count, output.display());
+    }
+    Ok(())
+}
+
+fn print_report(report: &DayReport, max_gaps: usize) {
+    let status = if report.is_clean() { "clean" } else { "ISSUES" };
+    println!("{} {}", report.date, status);
+    for venue in &report.venues {
+        println!(
+            "  {} usable {:.2}h, maintenance {:.2}h",
+            venue.venue,
+            venue.usable_secs / 3600.0,
+            venue.maintenance_secs / 3600.0
+        );
+        for window in &venue.maintenance {
+            println!("    maintenance {} .. {}", window.start, window.end);
+        }
+    }
+    for channel in &report.channels {
+        println!(
+            "  {}/{} {}: {} records {} .. {}",
+            channel.kind, channel.venue, channel.sym, channel.records, channel.first, channel.last
+        );
+        let gaps: Vec<_> = channel.unexpected_gaps().collect();
+        let issues = [
+            ("gaps", gaps.len() as u64),
+            ("maintenance gaps", (channel.gaps.len() - gaps.len()) as u64),
+            ("non monotonic", channel.non_monotonic),
+            ("duplicate trade ids", channel.duplicate_trade_ids),
+            ("crossed books", channel.crossed_books),
+            ("outlier prices", channel.outlier_prices),
+        ];
+        for (name, count) in issues.iter().filter(|(_, count)| *count > 0) {
+            println!("    {}: {}", name, count);
+        }
+        for gap in gaps.iter().take(max_gaps) {
+            println!("      {} .. {} ({:.1}s)", gap.start, gap.end, gap.seconds);
+        }
+        if gaps.len() > max_gaps {
+            println!("      ... {} more", gaps.len() - max_gaps);
+        }
+    }
+}
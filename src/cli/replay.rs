@@ -0,0 +1,271 @@
+use log::info;
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::analytics::data_quality::UsableIntervals;
+use crate::api_client::backtest::backtest_client::BacktestClient;
+use crate::api_client::execution_client::ExecutionClient;
+use crate::cli::args::ReplayArgs;
+use crate::config::app_config::AppConfig;
+use crate::datamodels::enums::MarketData;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::order::Order;
+use crate::datamodels::position::Position;
+use crate::feedhandler::replay::MarketDataReplay;
+use crate::risk::risk_client::RiskClient;
+use crate::strategy::strategy::{self, Strategy};
+
+/// `venues`, or the venues of the configured feeds when empty.
+pub fn venues<'a>(config: &'a AppConfig, venues: &'a [String]) -> Vec<&'a str> {
+    if venues.is_empty() {
+        config.feeds.iter().map(|feed| feed.venue()).collect()
+    } else {
+        venues.iter().map(String::as_str).collect()
+    }
+}
+
+fn replay_data(config: &AppConfig, args: &ReplayArgs) -> Result<MarketDataReplay, Box<dyn Error>> {
+    let usable = match &args.usable {
+        Some(path) => Some(
+            UsableIntervals::from_file(path)
+                .map_err(|e| format!("failed to read {} : {}", path.display(), e))?,
+        ),
+        None => None,
+    };
+    Ok(MarketDataReplay::new(&venues(config, &args.venues))
+        .between(args.start, args.end)
+        .usable(usable))
+}
+
+/// Print the merged streams as JSON lines.
+pub fn replay(config: &AppConfig, args: &ReplayArgs) -> Result<(), Box<dyn Error>> {
+    for data in replay_data(config, args)? {
+        let line = match data {
+            MarketData::MarketBook(data) => serde_json::to_string(&data)?,
+            MarketData::Rate(data) => serde_json::to_string(&data)?,
+            MarketData::MarketTrade(data) => serde_json::to_string(&data)?,
+            MarketData::Ticker(data) => serde_json::to_string(&data)?,
+            MarketData::Bar(data) => serde_json::to_string(&data)?,
+        };
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Replay the merged streams through the configured strategies, trading on `BacktestClient`
+/// behind the configured risk limits, and print the resulting orders and positions.
+pub fn backtest(config: &AppConfig, args: &ReplayArgs) -> Result<(), Box<dyn Error>> {
+    let instruments = Arc::new(config.load_instruments()?);
+    let calendar = Arc::new(config.load_calendar()?);
+    let mut strategies = config
+        .strategies
+        .iter()
+        .map(strategy::from_config)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut backtest = BacktestClient::new(&config.client.venue);
+    backtest.set_instruments(instruments);
+    backtest.set_calendar(calendar);
+
+    let venues = venues(config, &args.venues);
+    info!(
+        "Backtest {} strategies on {:?} from {:?} to {:?}, usable intervals {:?}",
+        strategies.len(),
+        venues,
+        args.start,
+        args.end,
+        args.usable
+    );
+    let report = run_backtest(
+        config,
+        backtest,
+        &mut strategies,
+        replay_data(config, args)?,
+    );
+
+    println!("records replayed: {}", report.records);
+    println!("orders filled: {}", report.filled_orders);
+    println!("orders rejected by risk: {}", report.rejected_orders);
+    println!("open orders: {}", report.open_orders);
+    for position in report.positions {
+        println!("position: {}", serde_json::to_string(&position)?);
+    }
+    Ok(())
+}
+
+/// Outcome of `run_backtest`.
+pub struct BacktestReport {
+    pub records: usize,
+    pub filled_orders: usize,
+    pub rejected_orders: usize,
+    pub open_orders: usize,
+    /// Every order the backtest client received, in submission order.
+    pub orders: Vec<Order>,
+    pub positions: Vec<Position>,
+}
+
+/// Drive `strategies` with `data` on `backtest` behind the configured risk limits.
+///
+/// Ids come from `[ids.backtest]` and latency stamps from the replayed time, so the same
+/// seed and data give the same report.
+pub fn run_backtest(
+    config: &AppConfig,
+    mut backtest: BacktestClient,
+    strategies: &mut [Box<dyn Strategy>],
+    data: impl IntoIterator<Item = MarketData>,
+) -> BacktestReport {
+    let ids = IdSource::new(config.ids.backtest.build());
+    backtest.set_ids(ids.clone());
+    let mut client = RiskClient::new(backtest, config.risk.clone());
+    client.set_ids(ids);
+    client.set_simulated_time(true);
+
+    let mut records = 0;
+    let mut filled_orders = 0;
+    for data in data {
+        records += 1;
+        filled_orders += client.client().next(data.clone()).len();
+        client.callback(&data);
+        for strategy in strategies.iter_mut() {
+            strategy.on_market_data(&data, &mut client);
+        }
+    }
+
+    let mut orders: Vec<Order> = client.client().orders().orders().cloned().collect();
+    orders.sort_by(|a, b| (a.timestamp, &a.order_id).cmp(&(b.timestamp, &b.order_id)));
+    BacktestReport {
+        records,
+        filled_orders,
+        rejected_orders: client.rejection_count(),
+        open_orders: client.get_open_orders(None).len(),
+        orders,
+        positions: client.get_positions(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::execution_client::ExecutionClient;
+    use crate::constants::constants;
+    use crate::datamodels::marketdata::{MarketTrade, Rate};
+    use crate::datamodels::order::{OrderStatus, OrderType};
+    use crate::datamodels::types::{Price, Qty, Side};
+    use time::{Duration, OffsetDateTime};
+
+    /// Joins the bid on every rate and sells at market on every third.
+    struct JoinBid {
+        rates: usize,
+    }
+
+    impl Strategy for JoinBid {
+        fn model_id(&self) -> &str {
+            "join_bid"
+        }
+
+        fn on_market_data(&mut self, data: &MarketData, client: &mut dyn ExecutionClient) {
+            let rate = match data {
+                MarketData::Rate(rate) => rate,
+                _ => return,
+            };
+            self.rates += 1;
+            let (side, price, order_type) = match self.rates % 3 {
+                0 => (
+                    Side::Sell,
+                    Price::from_f64(rate.best_bid),
+                    OrderType::Market,
+                ),
+                _ => (Side::Buy, Price::from_f64(rate.best_bid), OrderType::Limit),
+            };
+            client.submit_order(
+                rate.market_created_timestamp,
+                &rate.sym,
+                side,
+                price,
+                Qty::from_f64(0.01),
+                order_type,
+                "join_bid",
+            );
+        }
+    }
+
+    /// A rate then a trade through it every second, the market drifting down.
+    fn market_data() -> Vec<MarketData> {
+        let mut data = vec![];
+        for i in 0..30 {
+            let timestamp = OffsetDateTime::UNIX_EPOCH + Duration::seconds(i);
+            let bid = 5_000_000.0 - i as f64 * 100.0;
+            data.push(MarketData::Rate(Rate {
+                timestamp,
+                market_created_timestamp: timestamp,
+                received_timestamp: timestamp,
+                sym: "FXBTCJPY".to_string(),
+                venue: "bitflyer".to_string(),
+                category: "lightning".to_string(),
+                best_bid: bid,
+                best_ask: bid + 100.0,
+                mid_price: bid + 50.0,
+                misc: "".to_string(),
+                universal_id: format!("rate-{}", i),
+                data_center: "".to_string(),
+                process_id: "".to_string(),
+            }));
+            let timestamp = timestamp + Duration::milliseconds(500);
+            data.push(MarketData::MarketTrade(MarketTrade {
+                timestamp,
+                market_created_timestamp: timestamp,
+                received_timestamp: timestamp,
+                sym: "FXBTCJPY".to_string(),
+                venue: "bitflyer".to_string(),
+                category: "lightning".to_string(),
+                side: Side::Sell,
+                price: Price::from_f64(bid - 50.0),
+                amount: Qty::from_f64(0.1),
+                trade_id: i.to_string(),
+                order_ids: "".to_string(),
+                misc: "".to_string(),
+                universal_id: format!("trade-{}", i),
+                data_center: "".to_string(),
+                process_id: "".to_string(),
+            }));
+        }
+        data
+    }
+
+    fn run() -> BacktestReport {
+        let config: AppConfig = toml::from_str("").unwrap();
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![Box::new(JoinBid { rates: 0 })];
+        run_backtest(
+            &config,
+            BacktestClient::new("bitflyer"),
+            &mut strategies,
+            market_data(),
+        )
+    }
+
+    #[test]
+    fn seeded_backtest_is_reproducible() {
+        let first = run();
+        let second = run();
+
+        assert_eq!(first.records, 60);
+        assert_eq!(first.orders.len(), 30);
+        assert!(first.filled_orders > 0);
+        assert!(first
+            .orders
+            .iter()
+            .any(|order| order.order_status == OrderStatus::Filled));
+        assert!(first
+            .orders
+            .iter()
+            .all(|order| order.process_id != *constants::RUNTIME_ID));
+        assert_eq!(
+            serde_json::to_string(&first.orders).unwrap(),
+            serde_json::to_string(&second.orders).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_string(&first.positions).unwrap(),
+            serde_json::to_string(&second.positions).unwrap()
+        );
+    }
+}
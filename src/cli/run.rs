@@ -0,0 +1,279 @@
+use flexi_logger::{Duplicate, FileSpec, Logger};
+use log::{info, warn};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+use crate::analytics::bar_builder::BarBuilder;
+use crate::api_client::bitflyer::bitflyer_client::BitFlyerClient;
+use crate::api_client::execution_client::ExecutionClient;
+use crate::api_client::paper::paper_client::PaperClient;
+use crate::config::app_config::{AppConfig, ConfigError, FeedKind};
+use crate::datamodels::enums::{InternalData, MarketData};
+use crate::datamodels::id_generator::IdSource;
+use crate::feedhandler::binance::binance_websocket::{self, BinanceWebSocket};
+use crate::feedhandler::bitflyer::backfill::ExecutionBackfill;
+use crate::feedhandler::bitflyer::bitflyer_socketio::{self, BitFlyerSocketIo};
+use crate::feedhandler::ticklogger;
+use crate::feedhandler::websocket::WebSocketFeed;
+use crate::monitor::feed_health::FeedHealth;
+use crate::monitor::metrics;
+use crate::refdata::calendar::MaintenanceCalendar;
+use crate::refdata::instrument::InstrumentRegistry;
+use crate::risk::risk_client::RiskClient;
+use crate::strategy::strategy::{self, Strategy};
+
+type Callback = Box<dyn FnMut(MarketData) + Send>;
+type ClientCallback = Arc<Mutex<dyn FnMut(&MarketData) + Send>>;
+type PrivateCallback = Box<dyn FnMut(InternalData) + Send>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientMode {
+    Paper,
+    Live,
+}
+
+/// Log to `logger.directory` as configured.
+pub fn start_logger(config: &AppConfig) -> Result<(), flexi_logger::FlexiLoggerError> {
+    let mut logger = Logger::try_with_str(&config.logger.spec)?
+        .format(flexi_logger::detailed_format)
+        .log_to_file(FileSpec::default().directory(&config.logger.directory))
+        .append()
+        .rotate(
+            flexi_logger::Criterion::Age(flexi_logger::Age::Day),
+            flexi_logger::Naming::Timestamps,
+            flexi_logger::Cleanup::KeepLogFiles(config.logger.keep_files),
+        );
+    if config.logger.duplicate_to_stdout {
+        logger = logger.duplicate_to_stdout(Duplicate::All);
+    }
+    logger.start()?;
+    Ok(())
+}
+
+/// Run the configured feeds and their health monitor forever. `mode` adds an execution client
+/// fed by every feed, `None` only records.
+pub fn run(config: &AppConfig, mode: Option<ClientMode>) -> Result<(), ConfigError> {
+    // set reference data
+    let instruments = Arc::new(config.load_instruments()?);
+    let calendar = Arc::new(config.load_calendar()?);
+    let ids = IdSource::new(config.ids.live.build());
+
+    // set logic
+    let (client_callback, mut private_callback) = match mode {
+        Some(mode) => {
+            let strategies = config
+                .strategies
+                .iter()
+                .map(strategy::from_config)
+                .collect::<Result<Vec<_>, _>>()?;
+            if strategies.is_empty() {
+                warn!("No strategy configured, the {:?} client stays idle", mode);
+            }
+            let (client_callback, private_callback) =
+                build_client(config, mode, strategies, &instruments, &calendar, &ids)?;
+            (Some(client_callback), private_callback)
+        }
+        None => (None, None),
+    };
+
+    // set metrics endpoint
+    if let Some(listen) = config.metrics.listen {
+        metrics::serve(listen)
+            .map_err(|e| ConfigError::Invalid(format!("failed to listen on {}: {}", listen, e)))?;
+    }
+
+    // set feed health
+    let health = FeedHealth::new(config.health.clone(), calendar.clone());
+    let sinks = config
+        .health
+        .sinks
+        .iter()
+        .map(|sink| {
+            sink.build().map_err(|e| {
+                ConfigError::Invalid(format!("failed to open alert sink {:?}: {}", sink, e))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // set feedhandlers
+    for feed in &config.feeds {
+        let mut t_logger = ticklogger::TickLogger::new(feed.venue());
+        let client_callback = client_callback.clone();
+        let mut callback: Callback = Box::new(move |data| {
+            if let Some(client_callback) = &client_callback {
+                (client_callback.lock().unwrap())(&data);
+            }
+            t_logger.callback(data);
+        });
+        for spec in feed.bar_specs()? {
+            let mut bar_builder = BarBuilder::new(spec);
+            bar_builder.set_ids(ids.clone());
+            callback = Box::new(bar_builder.into_callback(callback));
+        }
+        // outermost, so bars are included in the processing time
+        let venue = feed.venue();
+        let mut inner = callback;
+        let callback: Callback = Box::new(move |data| {
+            let kind = data.kind();
+            let latency = data.timestamp() - data.market_created_timestamp();
+            metrics::FEED_LATENCY_SECONDS
+                .with_label_values(&[venue, kind])
+                .observe(latency.as_seconds_f64());
+            let start = Instant::now();
+            inner(data);
+            metrics::CALLBACK_SECONDS
+                .with_label_values(&[venue, kind])
+                .observe(start.elapsed().as_secs_f64());
+        });
+
+        info!("Connect {} feed to {:?}", feed.venue(), feed.channels);
+        match feed.kind {
+            FeedKind::BitflyerSocketio => {
+                let mut bfsocket = match &feed.url {
+                    Some(url) => BitFlyerSocketIo::with_url(url),
+                    None => BitFlyerSocketIo::new(),
+                };
+                bfsocket.set_instruments(instruments.clone());
+                bfsocket.set_calendar(calendar.clone());
+                bfsocket.set_ids(ids.clone());
+                bfsocket.set_health(health.clone());
+                bfsocket.set_dedupe_window(feed.dedupe_window);
+                if feed.backfill {
+                    bfsocket.set_backfill(match &feed.rest_url {
+                        Some(url) => ExecutionBackfill::with_url(url),
+                        None => ExecutionBackfill::new(),
+                    });
+                }
+                bfsocket.set_callback(callback);
+                let mut channels = feed.channels.clone();
+                if let Some(private_callback) = private_callback.take() {
+                    let (api_key, api_secret) = config.client.credentials()?;
+                    bfsocket.set_credentials(&api_key, &api_secret);
+                    bfsocket.set_private_callback(private_callback);
+                    for channel in bitflyer_socketio::PRIVATE_CHANNELS {
+                        if !channels.iter().any(|c| c == channel) {
+                            channels.push(channel.to_string());
+                        }
+                    }
+                }
+                bfsocket.connect(channels);
+            }
+            FeedKind::BinanceWebsocket => {
+                let mut adapter = match &feed.rest_url {
+                    Some(url) => BinanceWebSocket::with_rest_url(url),
+                    None => BinanceWebSocket::new(),
+                };
+                adapter.set_instruments(instruments.clone());
+                adapter.set_ids(ids.clone());
+                let url = feed.url.as_deref().unwrap_or(binance_websocket::ENDPOINT);
+                let mut websocket = WebSocketFeed::new(url, adapter);
+                websocket.set_calendar(calendar.clone());
+                websocket.set_health(health.clone());
+                websocket.set_callback(callback);
+                websocket.connect(feed.channels.clone());
+            }
+        }
+    }
+
+    if private_callback.is_some() {
+        warn!("No bitflyer feed, live orders are only updated by polling");
+    }
+
+    // start loop
+    health.monitor(sinks)
+}
+
+/// Execution client of `mode` behind the configured risk limits, driving `strategies`.
+/// Live clients also return the callback of the venue's private order events.
+fn build_client(
+    config: &AppConfig,
+    mode: ClientMode,
+    mut strategies: Vec<Box<dyn Strategy>>,
+    instruments: &Arc<InstrumentRegistry>,
+    calendar: &Arc<MaintenanceCalendar>,
+    ids: &IdSource,
+) -> Result<(ClientCallback, Option<PrivateCallback>), ConfigError> {
+    match mode {
+        ClientMode::Paper => {
+            let venue: &'static str = Box::leak(config.client.venue.clone().into_boxed_str());
+            let mut paper = PaperClient::new(venue);
+            paper.set_instruments(instruments.clone());
+            paper.set_calendar(calendar.clone());
+            paper.set_ids(ids.clone());
+            let mut risk = RiskClient::new(paper.clone(), config.risk.clone());
+            risk.set_ids(ids.clone());
+            info!("Paper trading on {} with {:?}", venue, risk.limits());
+            let risk = Arc::new(Mutex::new(risk));
+            start_kill_switch(&risk);
+            let client_callback = Arc::new(Mutex::new(move |data: &MarketData| {
+                let mut risk = risk.lock().unwrap();
+                paper.callback(data.clone());
+                risk.callback(data);
+                for strategy in strategies.iter_mut() {
+                    strategy.on_market_data(data, &mut *risk);
+                }
+            }));
+            Ok((client_callback, None))
+        }
+        ClientMode::Live => {
+            if config.client.venue != "bitflyer" {
+                return Err(ConfigError::Invalid(format!(
+                    "no live client for venue {}",
+                    config.client.venue
+                )));
+            }
+            let (api_key, api_secret) = config.client.credentials()?;
+            let mut live = BitFlyerClient::new(&api_key, &api_secret);
+            live.set_instruments(instruments.clone());
+            live.set_calendar(calendar.clone());
+            live.set_ids(ids.clone());
+            let mut risk = RiskClient::new(live, config.risk.clone());
+            risk.set_ids(ids.clone());
+            info!(
+                "Live trading on {} with {:?}",
+                config.client.venue,
+                risk.limits()
+            );
+            let risk = Arc::new(Mutex::new(risk));
+            start_kill_switch(&risk);
+            let order_events = risk.clone();
+            let private_callback: PrivateCallback = Box::new(move |data| {
+                order_events.lock().unwrap().client().on_order_event(data);
+            });
+            let client_callback = Arc::new(Mutex::new(move |data: &MarketData| {
+                let mut risk = risk.lock().unwrap();
+                risk.callback(data);
+                for strategy in strategies.iter_mut() {
+                    strategy.on_market_data(data, &mut *risk);
+                }
+            }));
+            Ok((client_callback, Some(private_callback)))
+        }
+    }
+}
+
+/// Watch the configured flag file and run kill requests on a timer, so that a kill goes out
+/// while the feed is silent.
+fn start_kill_switch<C: ExecutionClient + Send + 'static>(risk: &Arc<Mutex<RiskClient<C>>>) {
+    let (kill_switch, limits) = {
+        let risk = risk.lock().unwrap();
+        (risk.kill_switch(), risk.limits().clone())
+    };
+    let interval = Duration::from_millis(limits.kill_switch_poll_ms);
+    match limits.kill_switch_file {
+        Some(path) => {
+            info!("Kill switch watches {}", path.display());
+            kill_switch.watch_file(path, interval);
+        }
+        None => warn!("No risk.kill_switch_file, the kill switch is only triggered by limits"),
+    }
+    let risk = risk.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        if kill_switch.has_pending() {
+            let mut risk = risk.lock().unwrap();
+            risk.execute_kill_requests(OffsetDateTime::now_utc());
+        }
+    });
+}
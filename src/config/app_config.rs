@@ -0,0 +1,736 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use crate::analytics::bar_builder::BarSpec;
+use crate::datamodels::id_generator::IdGeneratorConfig;
+use crate::feedhandler::bitflyer::bitflyer_socketio;
+use crate::monitor::alert::AlertSinkConfig;
+use crate::monitor::feed_health::HealthConfig;
+use crate::refdata::calendar::MaintenanceCalendar;
+use crate::refdata::instrument::{InstrumentRegistry, RefDataError};
+use crate::risk::risk_limits::RiskLimits;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Toml(PathBuf, toml::de::Error),
+    RefData(PathBuf, RefDataError),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            ConfigError::Toml(path, e) => write!(f, "invalid toml in {}: {}", path.display(), e),
+            ConfigError::RefData(path, e) => {
+                write!(f, "failed to load {}: {}", path.display(), e)
+            }
+            ConfigError::Invalid(message) => write!(f, "invalid config: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LoggerConfig {
+    /// flexi_logger spec, e.g. `info, ralpha::feedhandler=debug`.
+    #[serde(default = "default_log_spec")]
+    pub spec: String,
+    #[serde(default = "default_log_directory")]
+    pub directory: PathBuf,
+    #[serde(default = "default_true")]
+    pub duplicate_to_stdout: bool,
+    /// Rotated daily log files kept.
+    #[serde(default = "default_keep_files")]
+    pub keep_files: usize,
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self {
+            spec: default_log_spec(),
+            directory: default_log_directory(),
+            duplicate_to_stdout: true,
+            keep_files: default_keep_files(),
+        }
+    }
+}
+
+fn default_log_spec() -> String {
+    "info".to_string()
+}
+
+fn default_log_directory() -> PathBuf {
+    PathBuf::from("testlogs")
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_keep_files() -> usize {
+    3
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TickerplantConfig {
+    /// Root of every `TextWriter` stream.
+    #[serde(default = "default_tickerplant_root")]
+    pub root: PathBuf,
+}
+
+impl Default for TickerplantConfig {
+    fn default() -> Self {
+        Self {
+            root: default_tickerplant_root(),
+        }
+    }
+}
+
+fn default_tickerplant_root() -> PathBuf {
+    PathBuf::from("tickerplant")
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Address of the Prometheus `/metrics` endpoint, disabled when omitted.
+    #[serde(default)]
+    pub listen: Option<SocketAddr>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RefDataConfig {
+    #[serde(default = "default_instruments")]
+    pub instruments: PathBuf,
+    #[serde(default = "default_calendar")]
+    pub calendar: PathBuf,
+}
+
+impl Default for RefDataConfig {
+    fn default() -> Self {
+        Self {
+            instruments: default_instruments(),
+            calendar: default_calendar(),
+        }
+    }
+}
+
+fn default_instruments() -> PathBuf {
+    PathBuf::from("config/instruments.toml")
+}
+
+fn default_calendar() -> PathBuf {
+    PathBuf::from("config/calendar.toml")
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedKind {
+    BitflyerSocketio,
+    BinanceWebsocket,
+}
+
+impl FeedKind {
+    pub fn venue(&self) -> &'static str {
+        match self {
+            FeedKind::BitflyerSocketio => "bitflyer",
+            FeedKind::BinanceWebsocket => "binance",
+        }
+    }
+}
+
+/// One market data feed, recorded under `<tickerplant root>/<type>/<venue>`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FeedConfig {
+    pub kind: FeedKind,
+    /// Endpoint override, e.g. a local stand-in server.
+    #[serde(default)]
+    pub url: Option<String>,
+    pub channels: Vec<String>,
+    /// `BarSpec`s built from the trades of this feed, e.g. `time:1m`.
+    #[serde(default)]
+    pub bars: Vec<String>,
+    /// REST endpoint override, e.g. a local mock server.
+    #[serde(default)]
+    pub rest_url: Option<String>,
+    /// `bitflyer_socketio` only, execution ids remembered per sym to drop resent ones.
+    #[serde(default = "default_dedupe_window")]
+    pub dedupe_window: usize,
+    /// `bitflyer_socketio` only, fetch the executions missed across reconnects from the
+    /// REST API.
+    #[serde(default)]
+    pub backfill: bool,
+}
+
+fn default_dedupe_window() -> usize {
+    bitflyer_socketio::DEDUPE_WINDOW
+}
+
+impl FeedConfig {
+    pub fn venue(&self) -> &'static str {
+        self.kind.venue()
+    }
+
+    pub fn bar_specs(&self) -> Result<Vec<BarSpec>, ConfigError> {
+        self.bars
+            .iter()
+            .map(|spec| {
+                spec.parse()
+                    .map_err(|e| ConfigError::Invalid(format!("feed {}: {}", self.venue(), e)))
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ClientConfig {
+    /// Venue of the backtest, paper and live clients.
+    #[serde(default = "default_client_venue")]
+    pub venue: String,
+    /// Environment variables holding the live API credentials, never the keys themselves.
+    #[serde(default = "default_api_key_env")]
+    pub api_key_env: String,
+    #[serde(default = "default_api_secret_env")]
+    pub api_secret_env: String,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            venue: default_client_venue(),
+            api_key_env: default_api_key_env(),
+            api_secret_env: default_api_secret_env(),
+        }
+    }
+}
+
+impl ClientConfig {
+    /// `(api_key, api_secret)` read from the configured environment variables.
+    pub fn credentials(&self) -> Result<(String, String), ConfigError> {
+        let read = |name: &str| {
+            std::env::var(name).map_err(|_| {
+                ConfigError::Invalid(format!(
+                    "live trading requires the environment variable {}",
+                    name
+                ))
+            })
+        };
+        Ok((read(&self.api_key_env)?, read(&self.api_secret_env)?))
+    }
+}
+
+fn default_client_venue() -> String {
+    "bitflyer".to_string()
+}
+
+fn default_api_key_env() -> String {
+    "BITFLYER_API_KEY".to_string()
+}
+
+fn default_api_secret_env() -> String {
+    "BITFLYER_API_SECRET".to_string()
+}
+
+/// Id generators of the `record`, `paper` and `live` commands, and of `backtest`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct IdsConfig {
+    #[serde(default = "default_live_ids")]
+    pub live: IdGeneratorConfig,
+    /// Seeded by default, so two runs of the same backtest give the same ids.
+    #[serde(default = "default_backtest_ids")]
+    pub backtest: IdGeneratorConfig,
+}
+
+impl Default for IdsConfig {
+    fn default() -> Self {
+        Self {
+            live: default_live_ids(),
+            backtest: default_backtest_ids(),
+        }
+    }
+}
+
+fn default_live_ids() -> IdGeneratorConfig {
+    IdGeneratorConfig::Random
+}
+
+fn default_backtest_ids() -> IdGeneratorConfig {
+    IdGeneratorConfig::Seeded { seed: 0 }
+}
+
+/// A model and its parameters, passed as is to the strategy implementing `kind`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct StrategyConfig {
+    pub model_id: String,
+    pub kind: String,
+    pub venue: String,
+    pub sym: String,
+    #[serde(default)]
+    pub params: toml::Table,
+}
+
+/// Deployment configuration of the main binary, see `config/ralpha.toml`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfig {
+    #[serde(default)]
+    pub logger: LoggerConfig,
+    #[serde(default)]
+    pub tickerplant: TickerplantConfig,
+    #[serde(default)]
+    pub refdata: RefDataConfig,
+    #[serde(default)]
+    pub client: ClientConfig,
+    #[serde(default)]
+    pub risk: RiskLimits,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub ids: IdsConfig,
+    #[serde(rename = "feed", default)]
+    pub feeds: Vec<FeedConfig>,
+    #[serde(rename = "strategy", default)]
+    pub strategies: Vec<StrategyConfig>,
+}
+
+impl AppConfig {
+    /// Load and validate a config file. Relative reference data paths are kept as they are,
+    /// i.e. relative to the working directory.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::from_file_with_overrides(path, &[])
+    }
+
+    /// Like `from_file`, with `key.path=value` overrides applied before validation,
+    /// e.g. `risk.max_position=0.2` or `feed.0.bars=["time:5m"]`. Values are parsed as TOML
+    /// and taken as strings otherwise.
+    pub fn from_file_with_overrides<P: AsRef<Path>>(
+        path: P,
+        overrides: &[String],
+    ) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let content =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        Self::from_toml_with_overrides(path, &content, overrides)
+    }
+
+    /// `content` of the config file at `path`, see `from_file_with_overrides`.
+    fn from_toml_with_overrides(
+        path: &Path,
+        content: &str,
+        overrides: &[String],
+    ) -> Result<Self, ConfigError> {
+        let config: AppConfig = Self::parse_with_overrides(path, content, overrides)?
+            .try_into()
+            .map_err(|e| ConfigError::Toml(path.to_path_buf(), e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Only the `[tickerplant]` section, for the offline tools which need no feeds.
+    /// A missing config file gives the default tickerplant.
+    pub fn tickerplant_from_file_with_overrides<P: AsRef<Path>>(
+        path: P,
+        overrides: &[String],
+    ) -> Result<TickerplantConfig, ConfigError> {
+        let path = path.as_ref();
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(ConfigError::Io(path.to_path_buf(), e)),
+        };
+        match Self::parse_with_overrides(path, &content, overrides)?.get("tickerplant") {
+            Some(tickerplant) => tickerplant
+                .clone()
+                .try_into()
+                .map_err(|e| ConfigError::Toml(path.to_path_buf(), e)),
+            None => Ok(TickerplantConfig::default()),
+        }
+    }
+
+    fn parse_with_overrides(
+        path: &Path,
+        content: &str,
+        overrides: &[String],
+    ) -> Result<toml::Value, ConfigError> {
+        let mut root: toml::Value =
+            toml::from_str(content).map_err(|e| ConfigError::Toml(path.to_path_buf(), e))?;
+        for assignment in overrides {
+            Self::apply_override(&mut root, assignment)?;
+        }
+        Ok(root)
+    }
+
+    fn apply_override(root: &mut toml::Value, assignment: &str) -> Result<(), ConfigError> {
+        let invalid =
+            |reason: &str| ConfigError::Invalid(format!("override {}: {}", assignment, reason));
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| invalid("expected key=value"))?;
+        let value = match toml::from_str::<toml::Table>(&format!("value = {}", value)) {
+            Ok(mut parsed) => parsed.remove("value").unwrap(),
+            Err(_) => toml::Value::String(value.to_string()),
+        };
+
+        let mut current = root;
+        for key in key.trim().split('.') {
+            current = match current {
+                toml::Value::Table(table) => table
+                    .entry(key.to_string())
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new())),
+                toml::Value::Array(array) => {
+                    let index: usize = key.parse().map_err(|_| invalid("expected an index"))?;
+                    array
+                        .get_mut(index)
+                        .ok_or_else(|| invalid("index out of range"))?
+                }
+                _ => return Err(invalid("not a table")),
+            };
+        }
+        *current = value;
+        Ok(())
+    }
+
+    pub fn load_instruments(&self) -> Result<InstrumentRegistry, ConfigError> {
+        let path = &self.refdata.instruments;
+        let instruments = InstrumentRegistry::from_file(path)
+            .map_err(|e| ConfigError::RefData(path.clone(), e))?;
+        self.validate_strategies(&instruments)?;
+        Ok(instruments)
+    }
+
+    pub fn load_calendar(&self) -> Result<MaintenanceCalendar, ConfigError> {
+        let path = &self.refdata.calendar;
+        MaintenanceCalendar::from_file(path).map_err(|e| ConfigError::RefData(path.clone(), e))
+    }
+
+    /// Checks not needing the reference data.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: String| Err(ConfigError::Invalid(message));
+
+        if self.feeds.is_empty() {
+            return invalid("at least one [[feed]] is required".to_string());
+        }
+        let mut venues = HashSet::new();
+        for feed in &self.feeds {
+            if !venues.insert(feed.venue()) {
+                return invalid(format!("duplicate feed for venue {}", feed.venue()));
+            }
+            if feed.channels.is_empty() {
+                return invalid(format!("feed {} has no channels", feed.venue()));
+            }
+            feed.bar_specs()?;
+            if feed.backfill && feed.kind != FeedKind::BitflyerSocketio {
+                return invalid(format!("feed {} cannot backfill", feed.venue()));
+            }
+        }
+
+        let mut model_ids = HashSet::new();
+        for strategy in &self.strategies {
+            if strategy.model_id.is_empty() {
+                return invalid(format!("strategy {} has an empty model_id", strategy.kind));
+            }
+            if !model_ids.insert(strategy.model_id.as_str()) {
+                return invalid(format!("duplicate model_id {}", strategy.model_id));
+            }
+        }
+
+        let risk = &self.risk;
+        let limits = [
+            ("risk.max_order_amount", risk.max_order_amount),
+            ("risk.max_order_notional", risk.max_order_notional),
+            ("risk.max_position", risk.max_position),
+            ("risk.price_collar_bps", risk.price_collar_bps),
+            ("risk.max_daily_loss", risk.max_daily_loss),
+        ];
+        for (name, limit) in limits {
+            if limit.is_some_and(|limit| limit <= 0.0 || !limit.is_finite()) {
+                return invalid(format!("{} must be positive", name));
+            }
+        }
+        if risk.order_rate_window_ms == 0 {
+            return invalid("risk.order_rate_window_ms must be positive".to_string());
+        }
+        if risk.kill_switch_poll_ms == 0 {
+            return invalid("risk.kill_switch_poll_ms must be positive".to_string());
+        }
+
+        let health = &self.health;
+        if health.check_interval_secs == 0 {
+            return invalid("health.check_interval_secs must be positive".to_string());
+        }
+        let thresholds = std::iter::once(("health.stale_secs".to_string(), health.stale_secs))
+            .chain(
+                health
+                    .stale_secs_by_channel
+                    .iter()
+                    .map(|(key, secs)| (format!("health.stale_secs_by_channel.{}", key), *secs)),
+            );
+        for (name, secs) in thresholds {
+            if secs <= 0.0 || !secs.is_finite() {
+                return invalid(format!("{} must be positive", name));
+            }
+        }
+        for sink in &health.sinks {
+            if let AlertSinkConfig::Webhook { url, .. } = sink {
+                let scheme = url::Url::parse(url).map(|url| url.scheme().to_string());
+                if !matches!(scheme.as_deref(), Ok("http") | Ok("https")) {
+                    return invalid(format!("health webhook {} is not an http url", url));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_strategies(&self, instruments: &InstrumentRegistry) -> Result<(), ConfigError> {
+        for strategy in &self.strategies {
+            if instruments.get(&strategy.venue, &strategy.sym).is_none() {
+                return Err(ConfigError::Invalid(format!(
+                    "strategy {} trades unknown instrument {} on {}",
+                    strategy.model_id, strategy.sym, strategy.venue
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tickerplant_loads_without_feeds() {
+        let path = std::env::temp_dir().join(format!("ralpha-{}.toml", std::process::id()));
+        std::fs::write(&path, "[tickerplant]\nroot = \"/data/ticks\"\n").unwrap();
+
+        assert!(AppConfig::from_file(&path).is_err());
+        let tickerplant = AppConfig::tickerplant_from_file_with_overrides(&path, &[]).unwrap();
+        assert_eq!(tickerplant.root, PathBuf::from("/data/ticks"));
+        let tickerplant = AppConfig::tickerplant_from_file_with_overrides(
+            &path,
+            &["tickerplant.root=/other".to_string()],
+        )
+        .unwrap();
+        assert_eq!(tickerplant.root, PathBuf::from("/other"));
+        std::fs::remove_file(&path).unwrap();
+
+        let tickerplant = AppConfig::tickerplant_from_file_with_overrides(&path, &[]).unwrap();
+        assert_eq!(tickerplant.root, default_tickerplant_root());
+    }
+
+    const FEED: &str = r#"
+        [[feed]]
+        kind = "bitflyer_socketio"
+        channels = ["lightning_executions_FX_BTC_JPY"]
+    "#;
+
+    fn load(content: &str, overrides: &[&str]) -> Result<AppConfig, ConfigError> {
+        let overrides: Vec<String> = overrides.iter().map(|o| o.to_string()).collect();
+        AppConfig::from_toml_with_overrides(Path::new("test.toml"), content, &overrides)
+    }
+
+    #[test]
+    fn config_ralpha_toml_is_valid() {
+        let config = AppConfig::from_file("config/ralpha.toml").unwrap();
+        assert!(!config.feeds.is_empty());
+        config.load_instruments().unwrap();
+        config.load_calendar().unwrap();
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected_with_a_clear_error() {
+        let binance = r#"
+            [[feed]]
+            kind = "binance_websocket"
+            channels = ["btcusdt@trade"]
+            backfill = true
+        "#;
+        let strategies = r#"
+            [[strategy]]
+            model_id = "a"
+            kind = "example"
+            venue = "bitflyer"
+            sym = "FXBTCJPY"
+
+            [[strategy]]
+            model_id = "a"
+            kind = "example"
+            venue = "bitflyer"
+            sym = "BTCJPY"
+        "#;
+        let cases: Vec<(String, Vec<&str>, &str)> = vec![
+            ("".to_string(), vec![], "at least one [[feed]] is required"),
+            (
+                format!("{}{}", FEED, FEED),
+                vec![],
+                "duplicate feed for venue bitflyer",
+            ),
+            (
+                FEED.to_string(),
+                vec!["feed.0.channels=[]"],
+                "feed bitflyer has no channels",
+            ),
+            (
+                FEED.to_string(),
+                vec!["feed.0.bars=[\"time:1w\"]"],
+                "feed bitflyer: invalid bar spec time:1w",
+            ),
+            (binance.to_string(), vec![], "feed binance cannot backfill"),
+            (
+                format!("{}{}", FEED, strategies),
+                vec![],
+                "duplicate model_id a",
+            ),
+            (
+                format!("{}{}", FEED, strategies),
+                vec!["strategy.0.model_id=\"\""],
+                "strategy example has an empty model_id",
+            ),
+            (
+                FEED.to_string(),
+                vec!["risk.max_order_amount=0"],
+                "risk.max_order_amount must be positive",
+            ),
+            (
+                FEED.to_string(),
+                vec!["risk.max_order_notional=-1"],
+                "risk.max_order_notional must be positive",
+            ),
+            (
+                FEED.to_string(),
+                vec!["risk.max_position=nan"],
+                "risk.max_position must be positive",
+            ),
+            (
+                FEED.to_string(),
+                vec!["risk.price_collar_bps=inf"],
+                "risk.price_collar_bps must be positive",
+            ),
+            (
+                FEED.to_string(),
+                vec!["risk.max_daily_loss=0.0"],
+                "risk.max_daily_loss must be positive",
+            ),
+            (
+                FEED.to_string(),
+                vec!["risk.order_rate_window_ms=0"],
+                "risk.order_rate_window_ms must be positive",
+            ),
+            (
+                FEED.to_string(),
+                vec!["risk.kill_switch_poll_ms=0"],
+                "risk.kill_switch_poll_ms must be positive",
+            ),
+            (
+                FEED.to_string(),
+                vec!["health.check_interval_secs=0"],
+                "health.check_interval_secs must be positive",
+            ),
+            (
+                FEED.to_string(),
+                vec!["health.stale_secs=0"],
+                "health.stale_secs must be positive",
+            ),
+            (
+                FEED.to_string(),
+                vec!["health.stale_secs_by_channel.bitflyer=-5"],
+                "health.stale_secs_by_channel.bitflyer must be positive",
+            ),
+            (
+                FEED.to_string(),
+                vec!["health.sink=[{ kind = \"webhook\", url = \"ftp://alerts\" }]"],
+                "health webhook ftp://alerts is not an http url",
+            ),
+            (
+                FEED.to_string(),
+                vec!["health.sink=[{ kind = \"webhook\", url = \"localhost:8080\" }]"],
+                "is not an http url",
+            ),
+            (
+                FEED.to_string(),
+                vec!["risk.max_postion=1"],
+                "unknown field `max_postion`",
+            ),
+            (
+                FEED.to_string(),
+                vec!["unknown=1"],
+                "unknown field `unknown`",
+            ),
+            (
+                format!("{}speed = 1\n", FEED),
+                vec![],
+                "unknown field `speed`",
+            ),
+            (
+                FEED.to_string(),
+                vec!["risk.max_position"],
+                "override risk.max_position: expected key=value",
+            ),
+            (
+                FEED.to_string(),
+                vec!["feed.first.bars=[]"],
+                "override feed.first.bars=[]: expected an index",
+            ),
+            (
+                FEED.to_string(),
+                vec!["feed.1.bars=[]"],
+                "override feed.1.bars=[]: index out of range",
+            ),
+            (
+                FEED.to_string(),
+                vec!["feed.0.kind.name=x"],
+                "override feed.0.kind.name=x: not a table",
+            ),
+        ];
+        for (content, overrides, expected) in cases {
+            match load(&content, &overrides) {
+                Ok(_) => panic!("{:?} on {} was accepted", overrides, content),
+                Err(e) => assert!(
+                    e.to_string().contains(expected),
+                    "{:?}: expected {}, got {}",
+                    overrides,
+                    expected,
+                    e
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn overrides_are_parsed_as_toml_or_taken_as_strings() {
+        let config = load(
+            FEED,
+            &[
+                "risk.max_position=0.2",
+                "risk.max_open_orders=3",
+                "feed.0.bars=[\"time:5m\", \"tick:100\"]",
+                "client.venue=bitflyer",
+                "health.stale_secs_by_channel.bitflyer=60",
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.risk.max_position, Some(0.2));
+        assert_eq!(config.risk.max_open_orders, Some(3));
+        assert_eq!(config.feeds[0].bar_specs().unwrap().len(), 2);
+        assert_eq!(config.client.venue, "bitflyer");
+        assert_eq!(config.health.stale_secs_by_channel["bitflyer"], 60.0);
+        // later overrides win
+        let config = load(FEED, &["risk.max_position=0.2", "risk.max_position=0.3"]).unwrap();
+        assert_eq!(config.risk.max_position, Some(0.3));
+    }
+}
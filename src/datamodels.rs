@@ -1,5 +1,8 @@
 pub mod enums;
 pub mod execution;
+pub mod id_generator;
+pub mod latency;
 pub mod marketdata;
 pub mod order;
 pub mod position;
+pub mod types;
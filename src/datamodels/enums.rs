@@ -1,17 +1,90 @@
-use crate::datamodels::marketdata::{MarketBook, MarketTrade, Rate};
+use time::OffsetDateTime;
+
+use crate::datamodels::execution::Execution;
+use crate::datamodels::marketdata::{Bar, MarketBook, MarketTrade, Rate, Ticker};
 use crate::datamodels::order::Order;
 use crate::datamodels::position::Position;
 
+#[derive(Clone, Debug)]
 pub enum MarketData {
     MarketBook(MarketBook),
     Rate(Rate),
     MarketTrade(MarketTrade),
+    Ticker(Ticker),
+    Bar(Bar),
+}
+
+impl MarketData {
+    /// Name of the variant, also the tickerplant directory of its stream.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MarketData::MarketBook(_) => "MarketBook",
+            MarketData::Rate(_) => "Rate",
+            MarketData::MarketTrade(_) => "MarketTrade",
+            MarketData::Ticker(_) => "Ticker",
+            MarketData::Bar(_) => "Bar",
+        }
+    }
+
+    pub fn timestamp(&self) -> OffsetDateTime {
+        match self {
+            MarketData::MarketBook(book) => book.timestamp,
+            MarketData::Rate(rate) => rate.timestamp,
+            MarketData::MarketTrade(trade) => trade.timestamp,
+            MarketData::Ticker(ticker) => ticker.timestamp,
+            MarketData::Bar(bar) => bar.timestamp,
+        }
+    }
+
+    pub fn received_timestamp(&self) -> OffsetDateTime {
+        match self {
+            MarketData::MarketBook(book) => book.received_timestamp,
+            MarketData::Rate(rate) => rate.received_timestamp,
+            MarketData::MarketTrade(trade) => trade.received_timestamp,
+            MarketData::Ticker(ticker) => ticker.received_timestamp,
+            MarketData::Bar(bar) => bar.received_timestamp,
+        }
+    }
+
+    pub fn market_created_timestamp(&self) -> OffsetDateTime {
+        match self {
+            MarketData::MarketBook(book) => book.market_created_timestamp,
+            MarketData::Rate(rate) => rate.market_created_timestamp,
+            MarketData::MarketTrade(trade) => trade.market_created_timestamp,
+            MarketData::Ticker(ticker) => ticker.market_created_timestamp,
+            MarketData::Bar(bar) => bar.market_created_timestamp,
+        }
+    }
+
+    pub fn sym(&self) -> &str {
+        match self {
+            MarketData::MarketBook(book) => &book.sym,
+            MarketData::Rate(rate) => &rate.sym,
+            MarketData::MarketTrade(trade) => &trade.sym,
+            MarketData::Ticker(ticker) => &ticker.sym,
+            MarketData::Bar(bar) => &bar.sym,
+        }
+    }
+
+    pub fn venue(&self) -> &str {
+        match self {
+            MarketData::MarketBook(book) => &book.venue,
+            MarketData::Rate(rate) => &rate.venue,
+            MarketData::MarketTrade(trade) => &trade.venue,
+            MarketData::Ticker(ticker) => &ticker.venue,
+            MarketData::Bar(bar) => &bar.venue,
+        }
+    }
 }
 
+#[derive(Clone, Debug)]
 pub enum InternalData {
     Order(Order),
+    Execution(Execution),
     Position(Position),
     MarketBook(MarketBook),
     Rate(Rate),
     MarketTrade(MarketTrade),
+    Ticker(Ticker),
+    Bar(Bar),
 }
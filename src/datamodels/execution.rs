@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
 use crate::constants::constants;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::types::{Price, Qty, Side};
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Execution {
@@ -13,9 +15,9 @@ pub struct Execution {
     pub received_timestamp: OffsetDateTime,
     pub venue: String,
     pub sym: String,
-    pub side: i16,
-    pub price: f64,
-    pub amount: f64,
+    pub side: Side,
+    pub price: Price,
+    pub amount: Qty,
     pub execution_id: String,
     pub source_order_id: String,
     pub data_center: String,
@@ -26,31 +28,33 @@ pub struct Execution {
 }
 
 impl Execution {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         sym: &str,
-        side: i16,
-        price: f64,
-        amount: f64,
+        side: Side,
+        price: Price,
+        amount: Qty,
         venue: &str,
         source_order_id: String,
         execution_id: String,
+        ids: &IdSource,
     ) -> Execution {
-        return Execution {
+        Execution {
             timestamp: OffsetDateTime::now_utc(),
             market_created_timestamp: OffsetDateTime::now_utc(),
             received_timestamp: OffsetDateTime::now_utc(),
             sym: sym.to_string(),
-            side: side,
-            price: price,
-            amount: amount,
+            side,
+            price,
+            amount,
             venue: venue.to_string(),
-            source_order_id: source_order_id,
-            execution_id: execution_id,
+            source_order_id,
+            execution_id,
             data_center: constants::MACHINE_ID.to_string(),
-            process_id: constants::RUNTIME_ID.to_string(),
-            universal_id: uuid::Uuid::new_v4().to_string(),
+            process_id: ids.runtime_id().to_string(),
+            universal_id: ids.next_id(),
             latency_tracker: "".to_string(),
             misc: "".to_string(),
-        };
+        }
     }
 }
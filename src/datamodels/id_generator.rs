@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::constants::constants;
+
+/// Source of the `order_id`s and `universal_id`s created by ralpha.
+pub trait IdGenerator: Send {
+    fn next_uuid(&mut self) -> Uuid;
+
+    /// `process_id` of the records created with these ids.
+    fn runtime_id(&self) -> String {
+        constants::RUNTIME_ID.to_string()
+    }
+}
+
+/// Random v4 UUIDs.
+pub struct RandomIds;
+
+impl IdGenerator for RandomIds {
+    fn next_uuid(&mut self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+/// v4 UUIDs drawn from a splitmix64 sequence, the same for the same seed.
+pub struct SeededIds {
+    seed: u64,
+    state: u64,
+}
+
+impl SeededIds {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl IdGenerator for SeededIds {
+    fn next_uuid(&mut self) -> Uuid {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.next_u64().to_be_bytes());
+        bytes[8..].copy_from_slice(&self.next_u64().to_be_bytes());
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
+    }
+
+    /// The same for the same seed, so that records of two runs can be diffed.
+    fn runtime_id(&self) -> String {
+        let name = format!("ralpha seeded ids {}", self.seed);
+        Uuid::new_v3(&Uuid::NAMESPACE_OID, name.as_bytes()).to_string()
+    }
+}
+
+/// v1 UUIDs of the current time, with the first 6 bytes of `MACHINE_ID` as node id.
+pub struct TimeBasedIds {
+    node_id: [u8; 6],
+}
+
+impl Default for TimeBasedIds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeBasedIds {
+    pub fn new() -> Self {
+        let mut node_id = [0; 6];
+        if let Ok(machine_id) = hex::decode(constants::MACHINE_ID.as_str()) {
+            for (byte, id) in node_id.iter_mut().zip(machine_id) {
+                *byte = id;
+            }
+        }
+        Self { node_id }
+    }
+}
+
+impl IdGenerator for TimeBasedIds {
+    fn next_uuid(&mut self) -> Uuid {
+        Uuid::now_v1(&self.node_id)
+    }
+}
+
+/// `[ids.live]` and `[ids.backtest]` of the app config.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum IdGeneratorConfig {
+    Random,
+    Seeded { seed: u64 },
+    TimeBased,
+}
+
+impl IdGeneratorConfig {
+    pub fn build(&self) -> Box<dyn IdGenerator> {
+        match self {
+            IdGeneratorConfig::Random => Box::new(RandomIds),
+            IdGeneratorConfig::Seeded { seed } => Box::new(SeededIds::new(*seed)),
+            IdGeneratorConfig::TimeBased => Box::new(TimeBasedIds::new()),
+        }
+    }
+}
+
+/// Generator shared by the components of one run, passed to every constructor creating ids.
+///
+/// Seeded ids are only reproducible when they are drawn in the same order, i.e. from a
+/// single thread like the backtest.
+#[derive(Clone)]
+pub struct IdSource {
+    generator: Arc<Mutex<Box<dyn IdGenerator>>>,
+    runtime_id: Arc<str>,
+}
+
+impl Default for IdSource {
+    fn default() -> Self {
+        Self::new(Box::new(RandomIds))
+    }
+}
+
+impl fmt::Debug for IdSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "IdSource({})", self.runtime_id)
+    }
+}
+
+impl IdSource {
+    pub fn new(generator: Box<dyn IdGenerator>) -> Self {
+        let runtime_id = generator.runtime_id().into();
+        Self {
+            generator: Arc::new(Mutex::new(generator)),
+            runtime_id,
+        }
+    }
+
+    pub fn next_id(&self) -> String {
+        self.generator.lock().unwrap().next_uuid().to_string()
+    }
+
+    pub fn runtime_id(&self) -> &str {
+        &self.runtime_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_ids_repeat_per_seed() {
+        let ids = |seed| {
+            let source = IdSource::new(IdGeneratorConfig::Seeded { seed }.build());
+            let ids: Vec<String> = (0..3).map(|_| source.next_id()).collect();
+            (source.runtime_id().to_string(), ids)
+        };
+        assert_eq!(ids(7), ids(7));
+        assert_ne!(ids(7).0, ids(8).0);
+        assert_ne!(ids(7).1, ids(8).1);
+        assert_ne!(ids(7).0, constants::RUNTIME_ID.as_str());
+    }
+}
@@ -0,0 +1,249 @@
+use std::fmt;
+use std::str::FromStr;
+use time::OffsetDateTime;
+
+use crate::datamodels::enums::MarketData;
+
+/// Hops of a tick on its way to an order and its fill, in causal order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Hop {
+    /// `market_created_timestamp`.
+    Exchange,
+    /// `received_timestamp`, raw message read from the socket.
+    Receive,
+    /// `timestamp`, market data built from the message.
+    Parse,
+    /// Market data handed to the execution client and strategies.
+    Dispatch,
+    /// Strategy called `submit_order`.
+    Decision,
+    /// Order passed the risk checks and is sent to the venue.
+    Send,
+    /// Venue accepted or rejected the order.
+    Ack,
+    Fill,
+}
+
+impl Hop {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hop::Exchange => "exchange",
+            Hop::Receive => "receive",
+            Hop::Parse => "parse",
+            Hop::Dispatch => "dispatch",
+            Hop::Decision => "decision",
+            Hop::Send => "send",
+            Hop::Ack => "ack",
+            Hop::Fill => "fill",
+        }
+    }
+}
+
+impl fmt::Display for Hop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Hop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exchange" => Ok(Hop::Exchange),
+            "receive" => Ok(Hop::Receive),
+            "parse" => Ok(Hop::Parse),
+            "dispatch" => Ok(Hop::Dispatch),
+            "decision" => Ok(Hop::Decision),
+            "send" => Ok(Hop::Send),
+            "ack" => Ok(Hop::Ack),
+            "fill" => Ok(Hop::Fill),
+            _ => Err(format!("unknown hop {}", s)),
+        }
+    }
+}
+
+/// Timestamps of the hops an order went through, kept in the `latency_tracker` field of
+/// `Order`, `Execution` and `Position`.
+///
+/// Serialized as `<hop>:<unix nanos>` for the first stamp, then `<hop>:<nanos since the first>`,
+/// e.g. `exchange:1700000000000000000,receive:1520000,parse:1551000`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LatencyTracker {
+    stamps: Vec<(Hop, OffsetDateTime)>,
+}
+
+impl LatencyTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Exchange, receive and parse stamps of a tick. Receive times of old tick files are unknown.
+    pub fn of(data: &MarketData) -> Self {
+        let mut tracker = Self::new();
+        tracker.stamp(Hop::Exchange, data.market_created_timestamp());
+        if data.received_timestamp() != OffsetDateTime::UNIX_EPOCH {
+            tracker.stamp(Hop::Receive, data.received_timestamp());
+        }
+        tracker.stamp(Hop::Parse, data.timestamp());
+        tracker
+    }
+
+    pub fn stamp(&mut self, hop: Hop, timestamp: OffsetDateTime) {
+        self.stamps.push((hop, timestamp));
+    }
+
+    pub fn stamp_now(&mut self, hop: Hop) {
+        self.stamp(hop, OffsetDateTime::now_utc());
+    }
+
+    pub fn stamps(&self) -> &[(Hop, OffsetDateTime)] {
+        &self.stamps
+    }
+
+    pub fn get(&self, hop: Hop) -> Option<OffsetDateTime> {
+        self.stamps.iter().find(|(h, _)| *h == hop).map(|(_, t)| *t)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stamps.is_empty()
+    }
+
+    /// Append a stamp to a serialized tracker. An unreadable tracker is restarted.
+    pub fn stamp_field(field: &mut String, hop: Hop, timestamp: OffsetDateTime) {
+        let mut tracker = field.parse().unwrap_or_else(|_| Self::new());
+        tracker.stamp(hop, timestamp);
+        *field = tracker.to_string();
+    }
+}
+
+impl fmt::Display for LatencyTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let first = match self.stamps.first() {
+            Some((_, first)) => *first,
+            None => return Ok(()),
+        };
+        for (i, (hop, timestamp)) in self.stamps.iter().enumerate() {
+            let nanos = match i {
+                0 => timestamp.unix_timestamp_nanos(),
+                _ => (*timestamp - first).whole_nanoseconds(),
+            };
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}:{}", hop, nanos)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for LatencyTracker {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tracker = Self::new();
+        let mut first: Option<OffsetDateTime> = None;
+        for stamp in s.split(',').filter(|stamp| !stamp.is_empty()) {
+            let (hop, nanos) = stamp
+                .split_once(':')
+                .ok_or_else(|| format!("expected <hop>:<nanos>, got {}", stamp))?;
+            let nanos: i128 = nanos
+                .parse()
+                .map_err(|e| format!("invalid nanos in {} : {}", stamp, e))?;
+            let timestamp = match first {
+                None => OffsetDateTime::from_unix_timestamp_nanos(nanos)
+                    .map_err(|e| format!("invalid timestamp in {} : {}", stamp, e))?,
+                Some(first) => i64::try_from(nanos)
+                    .ok()
+                    .and_then(|nanos| first.checked_add(time::Duration::nanoseconds(nanos)))
+                    .ok_or_else(|| format!("timestamp out of range in {}", stamp))?,
+            };
+            first.get_or_insert(timestamp);
+            tracker.stamp(hop.parse()?, timestamp);
+        }
+        Ok(tracker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Duration;
+
+    fn at(nanos: i64) -> OffsetDateTime {
+        OffsetDateTime::UNIX_EPOCH + Duration::seconds(1_700_000_000) + Duration::nanoseconds(nanos)
+    }
+
+    #[test]
+    fn display_and_from_str_round_trip() {
+        let mut tracker = LatencyTracker::new();
+        tracker.stamp(Hop::Exchange, at(0));
+        tracker.stamp(Hop::Receive, at(1_520_000));
+        tracker.stamp(Hop::Parse, at(1_551_000));
+        // clocks of different hosts can run backwards
+        tracker.stamp(Hop::Fill, at(-3_000));
+        let text = tracker.to_string();
+        assert_eq!(
+            text,
+            "exchange:1700000000000000000,receive:1520000,parse:1551000,fill:-3000"
+        );
+        let parsed: LatencyTracker = text.parse().unwrap();
+        assert_eq!(parsed, tracker);
+        assert_eq!(parsed.get(Hop::Parse), Some(at(1_551_000)));
+        assert_eq!(parsed.get(Hop::Ack), None);
+
+        assert_eq!(LatencyTracker::new().to_string(), "");
+        assert!("".parse::<LatencyTracker>().unwrap().is_empty());
+        for hop in [
+            Hop::Exchange,
+            Hop::Receive,
+            Hop::Parse,
+            Hop::Dispatch,
+            Hop::Decision,
+            Hop::Send,
+            Hop::Ack,
+            Hop::Fill,
+        ] {
+            assert_eq!(hop.to_string().parse::<Hop>().unwrap(), hop);
+        }
+    }
+
+    #[test]
+    fn invalid_trackers_are_errors() {
+        for (text, expected) in [
+            ("exchange", "expected <hop>:<nanos>"),
+            ("exchange:abc", "invalid nanos in exchange:abc"),
+            ("wire:0", "unknown hop wire"),
+            ("exchange:0,wire:1", "unknown hop wire"),
+            ("exchange:1000000000000000000000000000", "invalid timestamp"),
+            (
+                "exchange:0,receive:9223372036854775808",
+                "timestamp out of range",
+            ),
+            (
+                "exchange:0,receive:-9223372036854775809",
+                "timestamp out of range",
+            ),
+            (
+                "exchange:253402300799000000000,receive:9223372036854775807",
+                "timestamp out of range",
+            ),
+        ] {
+            let error = text.parse::<LatencyTracker>().unwrap_err();
+            assert!(error.contains(expected), "{}: {}", text, error);
+        }
+    }
+
+    #[test]
+    fn stamp_field_appends_or_restarts() {
+        let mut field = String::new();
+        LatencyTracker::stamp_field(&mut field, Hop::Send, at(0));
+        assert_eq!(field, "send:1700000000000000000");
+        LatencyTracker::stamp_field(&mut field, Hop::Ack, at(250_000));
+        assert_eq!(field, "send:1700000000000000000,ack:250000");
+
+        let mut field = "garbage".to_string();
+        LatencyTracker::stamp_field(&mut field, Hop::Fill, at(0));
+        assert_eq!(field, "fill:1700000000000000000");
+    }
+}
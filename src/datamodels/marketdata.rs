@@ -1,21 +1,37 @@
-use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use time::OffsetDateTime;
 
 use crate::constants::constants;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::types::{Price, Qty, Side};
 
+/// Tick files recorded before `received_timestamp` existed are read back with the unix epoch.
+pub fn default_received_timestamp() -> OffsetDateTime {
+    OffsetDateTime::UNIX_EPOCH
+}
+
+/// Timestamp contract shared by `MarketBook`, `Rate` and `MarketTrade`.
+///
+/// * `timestamp` - when ralpha created the record.
+/// * `market_created_timestamp` - exchange-side event time. Taken from the venue message
+///   when the channel provides one, otherwise estimated by the feed handler.
+/// * `received_timestamp` - when the raw message was received from the venue.
+///
+/// Replays are ordered by `market_created_timestamp`.
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct MarketBook {
     #[serde(with = "time::serde::rfc3339")]
     pub timestamp: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
     pub market_created_timestamp: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339", default = "default_received_timestamp")]
+    pub received_timestamp: OffsetDateTime,
     pub sym: String,
     pub venue: String,
     pub category: String,
-    pub asks: BTreeMap<OrderedFloat<f64>, f64>,
-    pub bids: BTreeMap<OrderedFloat<f64>, f64>,
+    pub asks: BTreeMap<Price, Qty>,
+    pub bids: BTreeMap<Price, Qty>,
     pub misc: String,
     pub universal_id: String,
     pub data_center: String,
@@ -23,42 +39,42 @@ pub struct MarketBook {
 }
 
 impl MarketBook {
-    pub fn to_rate(&self) -> Rate {
+    pub fn to_rate(&self, ids: &IdSource) -> Rate {
         let best_bid = self
             .bids
             .keys()
             .last()
-            .map_or(f64::NEG_INFINITY, |&OrderedFloat(k)| k);
+            .map_or(f64::NEG_INFINITY, Price::to_f64);
 
-        let best_ask = self
-            .asks
-            .keys()
-            .next()
-            .map_or(f64::INFINITY, |&OrderedFloat(k)| k);
+        let best_ask = self.asks.keys().next().map_or(f64::INFINITY, Price::to_f64);
 
-        return Rate {
-            timestamp: self.timestamp.clone(),
-            market_created_timestamp: self.market_created_timestamp.clone(),
+        Rate {
+            timestamp: self.timestamp,
+            market_created_timestamp: self.market_created_timestamp,
+            received_timestamp: self.received_timestamp,
             sym: self.sym.clone(),
             venue: self.venue.clone(),
             category: self.category.clone(),
-            best_bid: best_bid,
-            best_ask: best_ask,
+            best_bid,
+            best_ask,
             mid_price: (best_bid + best_ask) / 2.0,
             misc: "".to_string(),
-            universal_id: uuid::Uuid::new_v4().to_string(),
+            universal_id: ids.next_id(),
             data_center: constants::MACHINE_ID.to_string(),
-            process_id: constants::RUNTIME_ID.to_string(),
-        };
+            process_id: ids.runtime_id().to_string(),
+        }
     }
 }
 
+/// See `MarketBook` for the timestamp contract.
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Rate {
     #[serde(with = "time::serde::rfc3339")]
     pub timestamp: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
     pub market_created_timestamp: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339", default = "default_received_timestamp")]
+    pub received_timestamp: OffsetDateTime,
     pub sym: String,
     pub venue: String,
     pub category: String,
@@ -71,18 +87,21 @@ pub struct Rate {
     pub process_id: String,
 }
 
+/// See `MarketBook` for the timestamp contract.
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct MarketTrade {
     #[serde(with = "time::serde::rfc3339")]
     pub timestamp: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
     pub market_created_timestamp: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339", default = "default_received_timestamp")]
+    pub received_timestamp: OffsetDateTime,
     pub sym: String,
     pub venue: String,
     pub category: String,
-    pub side: i16,
-    pub price: f64,
-    pub amount: f64,
+    pub side: Side,
+    pub price: Price,
+    pub amount: Qty,
     pub trade_id: String,
     pub order_ids: String,
     pub misc: String,
@@ -90,3 +109,152 @@ pub struct MarketTrade {
     pub process_id: String,
     pub data_center: String,
 }
+
+/// Top of book with sizes, depth, last traded price and volume.
+/// See `MarketBook` for the timestamp contract.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Ticker {
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub market_created_timestamp: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339", default = "default_received_timestamp")]
+    pub received_timestamp: OffsetDateTime,
+    pub sym: String,
+    pub venue: String,
+    pub category: String,
+    pub best_bid: f64,
+    pub best_ask: f64,
+    pub best_bid_size: f64,
+    pub best_ask_size: f64,
+    pub total_bid_depth: f64,
+    pub total_ask_depth: f64,
+    pub mid_price: f64,
+    pub ltp: f64,
+    pub volume: f64,
+    pub misc: String,
+    pub universal_id: String,
+    pub data_center: String,
+    pub process_id: String,
+}
+
+impl Ticker {
+    pub fn to_rate(&self, ids: &IdSource) -> Rate {
+        Rate {
+            timestamp: self.timestamp,
+            market_created_timestamp: self.market_created_timestamp,
+            received_timestamp: self.received_timestamp,
+            sym: self.sym.clone(),
+            venue: self.venue.clone(),
+            category: self.category.clone(),
+            best_bid: self.best_bid,
+            best_ask: self.best_ask,
+            mid_price: self.mid_price,
+            misc: "ticker".to_string(),
+            universal_id: ids.next_id(),
+            data_center: constants::MACHINE_ID.to_string(),
+            process_id: ids.runtime_id().to_string(),
+        }
+    }
+
+    /// Depth imbalance in [-1, 1]. Positive when bids outweigh asks.
+    pub fn depth_imbalance(&self) -> f64 {
+        let total = self.total_bid_depth + self.total_ask_depth;
+        if total > 0.0 {
+            (self.total_bid_depth - self.total_ask_depth) / total
+        } else {
+            0.0
+        }
+    }
+}
+
+/// OHLCV aggregate of `MarketTrade`s, see `analytics::bar_builder`.
+///
+/// `open_timestamp` is the market time of the first trade, or the bar start for time bars.
+/// `market_created_timestamp` is the market time the bar was closed at.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Bar {
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub open_timestamp: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub market_created_timestamp: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339", default = "default_received_timestamp")]
+    pub received_timestamp: OffsetDateTime,
+    pub sym: String,
+    pub venue: String,
+    pub category: String,
+    /// `BarSpec` the bar was built with, e.g. `time:1m`.
+    pub bar_type: String,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    pub volume: Qty,
+    pub buy_volume: Qty,
+    pub sell_volume: Qty,
+    /// Sum of `price * amount`, in quote currency.
+    pub notional: f64,
+    pub vwap: f64,
+    pub trade_count: u64,
+    pub misc: String,
+    pub universal_id: String,
+    pub data_center: String,
+    pub process_id: String,
+}
+
+impl Bar {
+    /// One-trade bar opened by `trade`.
+    pub fn open(
+        trade: &MarketTrade,
+        bar_type: &str,
+        open_timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> Bar {
+        let mut bar = Bar {
+            timestamp: OffsetDateTime::now_utc(),
+            open_timestamp,
+            market_created_timestamp: trade.market_created_timestamp,
+            received_timestamp: trade.received_timestamp,
+            sym: trade.sym.clone(),
+            venue: trade.venue.clone(),
+            category: trade.category.clone(),
+            bar_type: bar_type.to_string(),
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            close: trade.price,
+            volume: Qty::ZERO,
+            buy_volume: Qty::ZERO,
+            sell_volume: Qty::ZERO,
+            notional: 0.0,
+            vwap: trade.price.to_f64(),
+            trade_count: 0,
+            misc: "".to_string(),
+            universal_id: ids.next_id(),
+            data_center: constants::MACHINE_ID.to_string(),
+            process_id: ids.runtime_id().to_string(),
+        };
+        bar.add(trade);
+        bar
+    }
+
+    pub fn add(&mut self, trade: &MarketTrade) {
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        self.close = trade.price;
+        self.volume += trade.amount;
+        match trade.side {
+            Side::Buy => self.buy_volume += trade.amount,
+            Side::Sell => self.sell_volume += trade.amount,
+        }
+        self.notional += trade.amount.notional(trade.price);
+        if !self.volume.is_zero() {
+            self.vwap = self.notional / self.volume.to_f64();
+        }
+        self.trade_count += 1;
+        self.market_created_timestamp = trade.market_created_timestamp;
+        self.received_timestamp = trade.received_timestamp;
+    }
+}
@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
 use crate::constants::constants;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::types::{Price, Qty, Side};
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Order {
@@ -12,10 +14,10 @@ pub struct Order {
     #[serde(with = "time::serde::rfc3339")]
     pub received_timestamp: OffsetDateTime,
     pub sym: String,
-    pub side: i16,
-    pub price: f64,
-    pub amount: f64,
-    pub executed_amount: f64,
+    pub side: Side,
+    pub price: Price,
+    pub amount: Qty,
+    pub executed_amount: Qty,
     pub order_type: OrderType,
     pub order_status: OrderStatus,
     pub venue: String,
@@ -26,6 +28,8 @@ pub struct Order {
     pub universal_id: String,
     pub latency_tracker: String,
     pub misc: String,
+    #[serde(default)]
+    pub history: Vec<OrderTransition>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -35,46 +39,64 @@ pub enum OrderType {
     Limit,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum OrderStatus {
     Created,
     Submitted,
     Accepted,
     Amended,
+    PartiallyFilled,
     Filled,
+    PendingCancel,
     Canceled,
+    Rejected,
+    Expired,
+}
+
+/// One applied status transition, see `oms::order_lifecycle`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct OrderTransition {
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    pub from: OrderStatus,
+    pub to: OrderStatus,
+    pub executed_amount: Qty,
 }
 
 impl Order {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         timestamp: OffsetDateTime,
         sym: &str,
-        side: i16,
-        price: f64,
-        amount: f64,
+        side: Side,
+        price: Price,
+        amount: Qty,
         order_type: OrderType,
         venue: &str,
         model_id: &str,
+        ids: &IdSource,
     ) -> Order {
-        return Order {
-            timestamp: timestamp,
-            market_created_timestamp: OffsetDateTime::now_utc(),
-            received_timestamp: OffsetDateTime::now_utc(),
+        // not seen by the venue yet
+        Order {
+            timestamp,
+            market_created_timestamp: timestamp,
+            received_timestamp: timestamp,
             sym: sym.to_string(),
-            side: side,
-            price: price,
-            amount: amount,
-            executed_amount: 0.0,
-            order_type: order_type,
+            side,
+            price,
+            amount,
+            executed_amount: Qty::ZERO,
+            order_type,
             order_status: OrderStatus::Created,
             venue: venue.to_string(),
-            order_id: uuid::Uuid::new_v4().to_string(),
+            order_id: ids.next_id(),
             model_id: model_id.to_string(),
             data_center: constants::MACHINE_ID.to_string(),
-            process_id: constants::RUNTIME_ID.to_string(),
-            universal_id: uuid::Uuid::new_v4().to_string(),
+            process_id: ids.runtime_id().to_string(),
+            universal_id: ids.next_id(),
             latency_tracker: "".to_string(),
             misc: "".to_string(),
-        };
+            history: vec![],
+        }
     }
 }
@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
 use crate::constants::constants;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::types::{Qty, Side};
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Position {
@@ -9,7 +11,8 @@ pub struct Position {
     pub timestamp: OffsetDateTime,
     pub sym: String,
     pub venue: String,
-    pub amount: f64,
+    pub amount: Qty,
+    /// Signed notional paid for `amount`, in quote currency.
     pub cost: f64,
     pub model_id: String,
     pub data_center: String,
@@ -20,28 +23,23 @@ pub struct Position {
 }
 
 impl Position {
-    pub fn new(sym: &str, venue: &str, model_id: &str) -> Position {
-        return Position {
+    pub fn new(sym: &str, venue: &str, model_id: &str, ids: &IdSource) -> Position {
+        Position {
             timestamp: OffsetDateTime::now_utc(),
             sym: sym.to_string(),
             venue: venue.to_string(),
-            amount: 0.0,
+            amount: Qty::ZERO,
             cost: 0.0,
             model_id: model_id.to_string(),
             data_center: constants::MACHINE_ID.to_string(),
-            process_id: constants::RUNTIME_ID.to_string(),
-            universal_id: uuid::Uuid::new_v4().to_string(),
+            process_id: ids.runtime_id().to_string(),
+            universal_id: ids.next_id(),
             latency_tracker: "".to_string(),
             misc: "".to_string(),
-        };
-    }
-    fn side(&self) -> i16 {
-        if self.amount > 0.0 {
-            return 1;
-        } else if self.amount < 0.0 {
-            return -1;
-        } else {
-            return 0;
         }
     }
+    /// `None` when flat.
+    pub fn side(&self) -> Option<Side> {
+        Side::from_sign(self.amount.units() as f64)
+    }
 }
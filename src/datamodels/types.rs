@@ -0,0 +1,341 @@
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+/// Number of fixed-point units in 1.0 for `Price` and `Qty`.
+pub const FIXED_POINT_SCALE: i64 = 100_000_000;
+
+/// Order, trade or position side.
+///
+/// Serialized as `1` / `-1` like the tick files written before this type existed.
+/// `"BUY"` / `"SELL"` are accepted when reading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    pub fn sign(&self) -> i16 {
+        match self {
+            Side::Buy => 1,
+            Side::Sell => -1,
+        }
+    }
+
+    pub fn opposite(&self) -> Side {
+        match self {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        }
+    }
+
+    /// `None` for zero or NaN.
+    pub fn from_sign(sign: f64) -> Option<Side> {
+        if sign > 0.0 {
+            Some(Side::Buy)
+        } else if sign < 0.0 {
+            Some(Side::Sell)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Buy => write!(f, "BUY"),
+            Side::Sell => write!(f, "SELL"),
+        }
+    }
+}
+
+impl Serialize for Side {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i16(self.sign())
+    }
+}
+
+struct SideVisitor;
+
+impl<'de> Visitor<'de> for SideVisitor {
+    type Value = Side;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "1, -1, \"BUY\" or \"SELL\"")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Side, E> {
+        self.visit_f64(v as f64)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Side, E> {
+        self.visit_f64(v as f64)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Side, E> {
+        Side::from_sign(v).ok_or_else(|| E::custom(format!("invalid side {}", v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Side, E> {
+        match v.to_ascii_uppercase().as_str() {
+            "BUY" => Ok(Side::Buy),
+            "SELL" => Ok(Side::Sell),
+            _ => Err(E::custom(format!("invalid side {}", v))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Side {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Side, D::Error> {
+        deserializer.deserialize_any(SideVisitor)
+    }
+}
+
+/// Fixed-point decimal with `FIXED_POINT_SCALE` units, serialized as a float.
+macro_rules! fixed_point {
+    ($name:ident) => {
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(i64);
+
+        impl $name {
+            pub const ZERO: $name = $name(0);
+
+            pub fn from_f64(value: f64) -> $name {
+                $name((value * FIXED_POINT_SCALE as f64).round() as i64)
+            }
+
+            pub fn to_f64(&self) -> f64 {
+                self.0 as f64 / FIXED_POINT_SCALE as f64
+            }
+
+            pub fn from_units(units: i64) -> $name {
+                $name(units)
+            }
+
+            pub fn units(&self) -> i64 {
+                self.0
+            }
+
+            pub fn is_zero(&self) -> bool {
+                self.0 == 0
+            }
+
+            pub fn abs(&self) -> $name {
+                $name(self.0.abs())
+            }
+
+            /// Nearest multiple of `increment`, e.g. the tick or lot size.
+            pub fn round_to(&self, increment: $name) -> $name {
+                if increment.0 <= 0 {
+                    return *self;
+                }
+                let half = increment.0 / 2;
+                $name((self.0 + half).div_euclid(increment.0) * increment.0)
+            }
+
+            pub fn floor_to(&self, increment: $name) -> $name {
+                if increment.0 <= 0 {
+                    return *self;
+                }
+                $name(self.0.div_euclid(increment.0) * increment.0)
+            }
+
+            pub fn ceil_to(&self, increment: $name) -> $name {
+                if increment.0 <= 0 {
+                    return *self;
+                }
+                $name(-(-self.0).div_euclid(increment.0) * increment.0)
+            }
+
+            pub fn is_multiple_of(&self, increment: $name) -> bool {
+                increment.0 <= 0 || self.0 % increment.0 == 0
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+            fn add(self, rhs: $name) -> $name {
+                $name(self.0 + rhs.0)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+            fn sub(self, rhs: $name) -> $name {
+                $name(self.0 - rhs.0)
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+            fn neg(self) -> $name {
+                $name(-self.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, rhs: $name) {
+                self.0 += rhs.0;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, rhs: $name) {
+                self.0 -= rhs.0;
+            }
+        }
+
+        impl std::iter::Sum for $name {
+            fn sum<I: Iterator<Item = $name>>(iter: I) -> $name {
+                iter.fold($name::ZERO, |a, b| a + b)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.to_f64())
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_f64(self.to_f64())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<$name, D::Error> {
+                f64::deserialize(deserializer).map($name::from_f64)
+            }
+        }
+    };
+}
+
+fixed_point!(Price);
+fixed_point!(Qty);
+
+impl Qty {
+    /// Positive for `Buy`, negative for `Sell`.
+    pub fn signed(&self, side: Side) -> Qty {
+        match side {
+            Side::Buy => *self,
+            Side::Sell => -*self,
+        }
+    }
+
+    /// `price * self` in quote currency.
+    pub fn notional(&self, price: Price) -> f64 {
+        price.to_f64() * self.to_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn side(json: &str) -> Result<Side, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn side_is_written_as_the_recorded_sign() {
+        assert_eq!(serde_json::to_string(&Side::Buy).unwrap(), "1");
+        assert_eq!(serde_json::to_string(&Side::Sell).unwrap(), "-1");
+    }
+
+    #[test]
+    fn side_reads_recorded_signs_and_bitflyer_strings() {
+        assert_eq!(side("1").unwrap(), Side::Buy);
+        assert_eq!(side("-1").unwrap(), Side::Sell);
+        assert_eq!(side("1.0").unwrap(), Side::Buy);
+        assert_eq!(side("-1.0").unwrap(), Side::Sell);
+        assert_eq!(side("\"BUY\"").unwrap(), Side::Buy);
+        assert_eq!(side("\"SELL\"").unwrap(), Side::Sell);
+        assert_eq!(side("\"buy\"").unwrap(), Side::Buy);
+    }
+
+    #[test]
+    fn itayose_empty_side_is_not_a_side() {
+        // the bitFlyer feed handler maps it before building a Side
+        assert!(side("\"\"").is_err());
+        assert!(side("0").is_err());
+        assert!(side("null").is_err());
+        assert!(side("\"HOLD\"").is_err());
+    }
+
+    #[test]
+    fn fixed_point_keeps_eight_decimals() {
+        assert_eq!(Qty::from_f64(0.00000001).units(), 1);
+        assert_eq!(Qty::from_f64(-0.00000001).units(), -1);
+        assert_eq!(Qty::from_units(1).to_f64(), 0.00000001);
+        assert_eq!(
+            Price::from_f64(5_000_000.12345678).to_f64(),
+            5_000_000.12345678
+        );
+        // below the scale rounds to the nearest unit
+        assert_eq!(Qty::from_f64(0.123456789).units(), 12_345_679);
+        assert_eq!(Qty::from_f64(-0.123456789).units(), -12_345_679);
+        assert_eq!(Qty::from_f64(0.000000004).units(), 0);
+        assert_eq!(Qty::from_f64(0.000000006).units(), 1);
+        // float noise is absorbed
+        assert_eq!(Qty::from_f64(0.1 + 0.2), Qty::from_f64(0.3));
+        assert_eq!(Qty::from_f64(0.1 + 0.2).to_f64(), 0.3);
+        assert_eq!(
+            Qty::from_f64(0.7) + Qty::from_f64(0.7) - Qty::from_f64(1.4),
+            Qty::ZERO
+        );
+    }
+
+    #[test]
+    fn fixed_point_round_trips_the_recorded_float_json() {
+        for json in [
+            "0.01",
+            "-0.03",
+            "5000000.5",
+            "0.00000001",
+            "-123.45678901",
+            "0.0",
+        ] {
+            let qty: Qty = serde_json::from_str(json).unwrap();
+            let again: Qty = serde_json::from_str(&serde_json::to_string(&qty).unwrap()).unwrap();
+            assert_eq!(qty, again, "{}", json);
+            assert_eq!(qty.to_f64(), json.parse::<f64>().unwrap(), "{}", json);
+        }
+        assert_eq!(serde_json::to_string(&Qty::from_f64(0.01)).unwrap(), "0.01");
+        assert_eq!(
+            serde_json::to_string(&Qty::from_f64(-0.03)).unwrap(),
+            "-0.03"
+        );
+        // integers written by other tools are read too, and written back as floats
+        let price: Price = serde_json::from_str("5000000").unwrap();
+        assert_eq!(price, Price::from_f64(5_000_000.0));
+        assert_eq!(serde_json::to_string(&price).unwrap(), "5000000.0");
+        assert!(serde_json::from_str::<Price>("\"5000000\"").is_err());
+    }
+
+    #[test]
+    fn rounding_to_increments() {
+        let tick = Price::from_f64(0.5);
+        assert_eq!(
+            Price::from_f64(100.26).round_to(tick),
+            Price::from_f64(100.5)
+        );
+        assert_eq!(
+            Price::from_f64(100.24).round_to(tick),
+            Price::from_f64(100.0)
+        );
+        assert_eq!(
+            Price::from_f64(-100.24).floor_to(tick),
+            Price::from_f64(-100.5)
+        );
+        assert_eq!(
+            Price::from_f64(-100.24).ceil_to(tick),
+            Price::from_f64(-100.0)
+        );
+        assert!(Price::from_f64(100.5).is_multiple_of(tick));
+        assert!(!Price::from_f64(100.25).is_multiple_of(tick));
+    }
+}
@@ -1,3 +1,9 @@
+pub mod binance;
 pub mod bitflyer;
+pub mod orderlogger;
+pub mod replay;
 pub mod textwriter;
 pub mod ticklogger;
+pub mod tickreader;
+pub mod trade_dedup;
+pub mod websocket;
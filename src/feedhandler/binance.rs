@@ -0,0 +1,2 @@
+pub mod binance_websocket;
+pub mod datamodel;
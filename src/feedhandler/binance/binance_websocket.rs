@@ -0,0 +1,375 @@
+use log::{info, warn};
+use reqwest::blocking::Client;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+use crate::datamodels::enums::MarketData;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::marketdata::MarketBook;
+use crate::feedhandler::binance::datamodel::{
+    DepthSnapshot, DepthUpdate, StreamEvent, StreamMessage,
+};
+use crate::feedhandler::websocket::VenueAdapter;
+use crate::monitor::feed_health::{FeedHealth, CONNECTION};
+use crate::refdata::instrument::InstrumentRegistry;
+
+pub const ENDPOINT: &str = "wss://stream.binance.com:9443/ws";
+const REST_ENDPOINT: &str = "https://api.binance.com";
+const VENUE: &str = "binance";
+const SNAPSHOT_DEPTH: u32 = 1000;
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+/// Updates buffered while waiting for a usable snapshot.
+const MAX_PENDING: usize = 10000;
+
+/// Local book of one symbol, kept in sync with the depth stream by update ids.
+#[derive(Default)]
+struct DepthBook {
+    book: Option<MarketBook>,
+    last_update_id: u64,
+    pending: Vec<DepthUpdate>,
+    last_snapshot_request: Option<Instant>,
+    top: (f64, f64),
+}
+
+/// `VenueAdapter` of the Binance-style public streams `<symbol>@trade` and `<symbol>@depth`.
+///
+/// Depth updates are buffered until a REST snapshot covering them is fetched. An update whose
+/// first id skips past the last applied id is a gap: the book is dropped and rebuilt from a new
+/// snapshot, so no `MarketBook` is published from a book with missing updates.
+pub struct BinanceWebSocket {
+    rest_url: String,
+    client: Client,
+    instruments: Arc<InstrumentRegistry>,
+    books: HashMap<String, DepthBook>,
+    health: FeedHealth,
+    ids: IdSource,
+}
+
+impl Default for BinanceWebSocket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BinanceWebSocket {
+    pub fn new() -> Self {
+        Self::with_rest_url(REST_ENDPOINT)
+    }
+
+    /// Fetch snapshots from another REST endpoint, e.g. a local stand-in server.
+    pub fn with_rest_url(rest_url: &str) -> Self {
+        Self {
+            rest_url: rest_url.to_string(),
+            client: Client::new(),
+            instruments: Arc::new(InstrumentRegistry::new()),
+            books: HashMap::new(),
+            health: FeedHealth::default(),
+            ids: IdSource::default(),
+        }
+    }
+
+    /// Maps stream symbols to symbols. Unknown symbols are kept as they are.
+    pub fn set_instruments(&mut self, instruments: Arc<InstrumentRegistry>) {
+        self.instruments = instruments;
+    }
+
+    /// Source of the ids of the records built from the streams, random by default.
+    pub fn set_ids(&mut self, ids: IdSource) {
+        self.ids = ids;
+    }
+
+    fn fetch_snapshot(&self, symbol: &str) -> Result<DepthSnapshot, reqwest::Error> {
+        self.client
+            .get(format!("{}/api/v3/depth", self.rest_url))
+            .query(&[("symbol", symbol), ("limit", &SNAPSHOT_DEPTH.to_string())])
+            .send()?
+            .error_for_status()?
+            .json()
+    }
+
+    /// `<symbol>@trade` or `<symbol>@depth`, whatever the update speed subscribed.
+    fn channel(symbol: &str, stream: &str) -> String {
+        format!("{}@{}", symbol.to_lowercase(), stream)
+    }
+
+    fn on_depth_update(
+        &mut self,
+        update: DepthUpdate,
+        received_timestamp: OffsetDateTime,
+    ) -> Vec<MarketData> {
+        let symbol = update.symbol.clone();
+        let depth = self.books.entry(symbol.clone()).or_default();
+
+        if depth.book.is_some() {
+            if update.final_update_id <= depth.last_update_id {
+                return vec![];
+            }
+            if update.first_update_id > depth.last_update_id + 1 {
+                self.health
+                    .on_book_error(VENUE, &Self::channel(&symbol, "depth"));
+                warn!(
+                    "Gap on {} depth: expected update {}, received {}..{}. Resync from snapshot.",
+                    symbol,
+                    depth.last_update_id + 1,
+                    update.first_update_id,
+                    update.final_update_id
+                );
+                depth.book = None;
+                depth.last_snapshot_request = None;
+            } else {
+                let book = depth.book.as_mut().unwrap();
+                update.apply(book, received_timestamp, &self.ids);
+                depth.last_update_id = update.final_update_id;
+                self.health
+                    .check_book(VENUE, &Self::channel(&symbol, "depth"), book);
+                return Self::publish(depth, &self.ids);
+            }
+        }
+
+        if depth.pending.len() >= MAX_PENDING {
+            self.health
+                .on_book_error(VENUE, &Self::channel(&symbol, "depth"));
+            warn!("Drop {} pending depth updates of {}", MAX_PENDING, symbol);
+            depth.pending.clear();
+        }
+        depth.pending.push(update);
+        self.resync(&symbol, received_timestamp)
+    }
+
+    /// Rebuild the book from a snapshot and the pending updates it does not cover.
+    fn resync(&mut self, symbol: &str, received_timestamp: OffsetDateTime) -> Vec<MarketData> {
+        let throttled = self.books[symbol]
+            .last_snapshot_request
+            .is_some_and(|t| t.elapsed() < SNAPSHOT_INTERVAL);
+        if throttled {
+            return vec![];
+        }
+        self.books.get_mut(symbol).unwrap().last_snapshot_request = Some(Instant::now());
+
+        let snapshot = match self.fetch_snapshot(symbol) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                warn!("Failed to fetch {} depth snapshot : {}", symbol, e);
+                return vec![];
+            }
+        };
+        let sym = self.instruments.sym(VENUE, symbol);
+        let depth = self.books.get_mut(symbol).unwrap();
+        let last_update_id = snapshot.last_update_id;
+        depth.pending.retain(|u| u.final_update_id > last_update_id);
+        if let Some(first) = depth.pending.first() {
+            if first.first_update_id > last_update_id + 1 {
+                warn!(
+                    "{} depth snapshot {} is older than pending update {}, retry",
+                    symbol, last_update_id, first.first_update_id
+                );
+                return vec![];
+            }
+        }
+
+        let mut book = snapshot.to_market_book(sym, received_timestamp, &self.ids);
+        let mut last_update_id = last_update_id;
+        for update in depth.pending.drain(..) {
+            if update.first_update_id > last_update_id + 1 {
+                warn!("Gap on {} pending depth updates, resync later", symbol);
+                return vec![];
+            }
+            update.apply(&mut book, received_timestamp, &self.ids);
+            last_update_id = update.final_update_id;
+        }
+        info!("Synced {} depth at update {}", symbol, last_update_id);
+        depth.book = Some(book);
+        depth.last_update_id = last_update_id;
+        Self::publish(depth, &self.ids)
+    }
+
+    /// `MarketBook` of every applied update, preceded by a `Rate` when the top of book moved.
+    fn publish(depth: &mut DepthBook, ids: &IdSource) -> Vec<MarketData> {
+        let book = depth.book.as_ref().unwrap();
+        let mut rate = book.to_rate(ids);
+        rate.misc = book.misc.clone();
+        let top = (rate.best_bid, rate.best_ask);
+        let mut data = vec![];
+        if top != depth.top {
+            depth.top = top;
+            data.push(MarketData::Rate(rate));
+        }
+        data.push(MarketData::MarketBook(book.clone()));
+        data
+    }
+}
+
+impl VenueAdapter for BinanceWebSocket {
+    fn venue(&self) -> &str {
+        VENUE
+    }
+
+    fn subscribe_messages(&self, channels: &[String]) -> Vec<String> {
+        let message = serde_json::json!({
+            "method": "SUBSCRIBE",
+            "params": channels,
+            "id": 1,
+        });
+        vec![message.to_string()]
+    }
+
+    fn on_message(&mut self, message: &str, received_timestamp: OffsetDateTime) -> Vec<MarketData> {
+        let event = match serde_json::from_str(message) {
+            Ok(StreamMessage::Combined { data, .. }) | Ok(StreamMessage::Event(data)) => data,
+            Ok(StreamMessage::Response { result, id }) => {
+                info!("Response to request {} : {}", id, result);
+                return vec![];
+            }
+            Err(e) => {
+                self.health.on_parse_error(VENUE, CONNECTION);
+                warn!("Unknown message: {} : {}", message, e);
+                return vec![];
+            }
+        };
+        match event {
+            StreamEvent::Trade(trade) => {
+                let channel = Self::channel(&trade.symbol, "trade");
+                self.health.on_message(VENUE, &channel, received_timestamp);
+                let sym = self.instruments.sym(VENUE, &trade.symbol);
+                vec![MarketData::MarketTrade(trade.to_market_trade(
+                    sym,
+                    received_timestamp,
+                    &self.ids,
+                ))]
+            }
+            StreamEvent::DepthUpdate(update) => {
+                let channel = Self::channel(&update.symbol, "depth");
+                self.health.on_message(VENUE, &channel, received_timestamp);
+                self.on_depth_update(update, received_timestamp)
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.books.clear();
+    }
+
+    /// `btcusdt@depth@100ms` -> `btcusdt@depth`.
+    fn health_channel(&self, channel: &str) -> String {
+        channel.splitn(3, '@').take(2).collect::<Vec<_>>().join("@")
+    }
+
+    fn set_health(&mut self, health: FeedHealth) {
+        self.health = health;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::mock_server::MockServer;
+    use crate::datamodels::types::{Price, Qty};
+    use crate::feedhandler::websocket::WebSocketFeed;
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use tungstenite::Message;
+
+    fn depth(first: u64, last: u64, bids: &str, asks: &str) -> String {
+        format!(
+            r#"{{"e": "depthUpdate", "E": 1760832000000, "s": "BTCUSDT", "U": {}, "u": {}, "b": {}, "a": {}}}"#,
+            first, last, bids, asks
+        )
+    }
+
+    /// Local WebSocket server sending `frames` after the subscribe message, then closing.
+    fn replay_server(frames: Vec<String>) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            let subscribe = socket.read_message().unwrap().into_text().unwrap();
+            for frame in frames {
+                socket.write_message(Message::Text(frame)).unwrap();
+            }
+            socket.close(None).unwrap();
+            while socket.read_message().is_ok() {}
+            subscribe
+        });
+        (url, server)
+    }
+
+    fn ladder(levels: &[(f64, f64)]) -> Vec<(Price, Qty)> {
+        levels
+            .iter()
+            .map(|(price, size)| (Price::from_f64(*price), Qty::from_f64(*size)))
+            .collect()
+    }
+
+    #[test]
+    fn gap_in_depth_updates_resyncs_from_a_new_snapshot() {
+        let rest = MockServer::start(vec![
+            (
+                200,
+                vec![],
+                r#"{"lastUpdateId": 100, "bids": [["100.0", "1.0"]], "asks": [["101.0", "1.0"]]}"#,
+            ),
+            (
+                200,
+                vec![],
+                r#"{"lastUpdateId": 200, "bids": [["98.0", "1.0"]], "asks": [["103.0", "1.0"]]}"#,
+            ),
+        ]);
+        let (url, server) = replay_server(vec![
+            r#"{"e": "trade", "E": 1760832000000, "s": "BTCUSDT", "t": 1, "p": "100.5", "q": "0.1", "T": 1760832000000, "m": true}"#.to_string(),
+            // buffered, then applied over the first snapshot
+            depth(95, 102, r#"[["100.0", "2.0"]]"#, "[]"),
+            depth(103, 103, "[]", r#"[["101.0", "0"], ["102.0", "3.0"]]"#),
+            // 104..109 are missing
+            depth(110, 110, r#"[["99.0", "5.0"]]"#, "[]"),
+            depth(201, 201, r#"[["98.5", "1.0"]]"#, "[]"),
+        ]);
+
+        let received = Arc::new(Mutex::new(vec![]));
+        let sink = received.clone();
+        let mut feed = WebSocketFeed::new(&url, BinanceWebSocket::with_rest_url(&rest.url));
+        feed.set_callback(Box::new(move |data| sink.lock().unwrap().push(data)));
+        feed.connect(vec![
+            "btcusdt@trade".to_string(),
+            "btcusdt@depth@100ms".to_string(),
+        ]);
+
+        let subscribe: serde_json::Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+        assert_eq!(subscribe["method"], "SUBSCRIBE");
+        assert_eq!(subscribe["params"][1], "btcusdt@depth@100ms");
+
+        let received = received.lock().unwrap();
+        let books: Vec<&MarketBook> = received
+            .iter()
+            .filter_map(|data| match data {
+                MarketData::MarketBook(book) => Some(book),
+                _ => None,
+            })
+            .collect();
+        let rates = received
+            .iter()
+            .filter(|data| matches!(data, MarketData::Rate(_)))
+            .count();
+        assert!(matches!(received[0], MarketData::MarketTrade(_)));
+        assert_eq!(books.len(), 4);
+        assert_eq!(rates, 4);
+
+        let levels = |ladder: &std::collections::BTreeMap<Price, Qty>| -> Vec<(Price, Qty)> {
+            ladder.iter().map(|(price, size)| (*price, *size)).collect()
+        };
+        assert_eq!(levels(&books[0].bids), ladder(&[(100.0, 2.0)]));
+        assert_eq!(levels(&books[0].asks), ladder(&[(101.0, 1.0)]));
+        assert_eq!(levels(&books[1].asks), ladder(&[(102.0, 3.0)]));
+        // the update after the gap is not applied, the book is the second snapshot
+        assert_eq!(books[2].misc, "snapshot");
+        assert_eq!(levels(&books[2].bids), ladder(&[(98.0, 1.0)]));
+        assert_eq!(levels(&books[2].asks), ladder(&[(103.0, 1.0)]));
+        assert_eq!(levels(&books[3].bids), ladder(&[(98.0, 1.0), (98.5, 1.0)]));
+
+        let requests = rest.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].path, "/api/v3/depth?symbol=BTCUSDT&limit=1000");
+    }
+}
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+
+use crate::constants::constants;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::marketdata::{MarketBook, MarketTrade};
+use crate::datamodels::types::{Price, Qty, Side};
+
+const VENUE: &str = "binance";
+const CATEGORY: &str = "spot";
+
+/// Any message of a raw (`/ws`) or combined (`/stream`) connection.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum StreamMessage {
+    Combined { stream: String, data: StreamEvent },
+    Event(StreamEvent),
+    Response { result: serde_json::Value, id: u64 },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "e")]
+pub enum StreamEvent {
+    #[serde(rename = "trade")]
+    Trade(Trade),
+    #[serde(rename = "depthUpdate")]
+    DepthUpdate(DepthUpdate),
+}
+
+/// Prices and sizes are sent as decimal strings.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PriceLevel(
+    #[serde(deserialize_with = "deserialize_decimal")] pub f64,
+    #[serde(deserialize_with = "deserialize_decimal")] pub f64,
+);
+
+fn deserialize_decimal<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(serde::de::Error::custom)
+}
+
+fn deserialize_millis<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<OffsetDateTime, D::Error> {
+    let millis = i64::deserialize(deserializer)?;
+    OffsetDateTime::from_unix_timestamp_nanos(millis as i128 * 1_000_000)
+        .map_err(serde::de::Error::custom)
+}
+
+/// `<symbol>@trade` stream.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Trade {
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "t")]
+    pub trade_id: u64,
+    #[serde(rename = "p", deserialize_with = "deserialize_decimal")]
+    pub price: f64,
+    #[serde(rename = "q", deserialize_with = "deserialize_decimal")]
+    pub quantity: f64,
+    #[serde(rename = "T", deserialize_with = "deserialize_millis")]
+    pub trade_time: OffsetDateTime,
+    /// The buyer is the maker, so the aggressor sold.
+    #[serde(rename = "m")]
+    pub is_buyer_maker: bool,
+}
+
+impl Trade {
+    pub fn to_market_trade(
+        &self,
+        sym: String,
+        received_timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> MarketTrade {
+        MarketTrade {
+            timestamp: OffsetDateTime::now_utc(),
+            market_created_timestamp: self.trade_time,
+            received_timestamp,
+            sym,
+            venue: VENUE.to_string(),
+            category: CATEGORY.to_string(),
+            side: if self.is_buyer_maker {
+                Side::Sell
+            } else {
+                Side::Buy
+            },
+            price: Price::from_f64(self.price),
+            amount: Qty::from_f64(self.quantity),
+            trade_id: self.trade_id.to_string(),
+            order_ids: "".to_string(),
+            misc: "".to_string(),
+            universal_id: ids.next_id(),
+            data_center: constants::MACHINE_ID.to_string(),
+            process_id: ids.runtime_id().to_string(),
+        }
+    }
+}
+
+/// `<symbol>@depth` stream. Covers update ids `first_update_id..=final_update_id`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct DepthUpdate {
+    #[serde(rename = "E", deserialize_with = "deserialize_millis")]
+    pub event_time: OffsetDateTime,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    #[serde(rename = "b")]
+    pub bids: Vec<PriceLevel>,
+    #[serde(rename = "a")]
+    pub asks: Vec<PriceLevel>,
+}
+
+impl DepthUpdate {
+    /// Apply the absolute sizes of this update, zero removing the level.
+    pub fn apply(&self, book: &mut MarketBook, received_timestamp: OffsetDateTime, ids: &IdSource) {
+        for (levels, ladder) in [(&self.bids, &mut book.bids), (&self.asks, &mut book.asks)] {
+            for PriceLevel(price, size) in levels {
+                let size = Qty::from_f64(*size);
+                if size.is_zero() {
+                    ladder.remove(&Price::from_f64(*price));
+                } else {
+                    ladder.insert(Price::from_f64(*price), size);
+                }
+            }
+        }
+        book.timestamp = OffsetDateTime::now_utc();
+        book.market_created_timestamp = self.event_time;
+        book.received_timestamp = received_timestamp;
+        book.universal_id = ids.next_id();
+        book.misc = "diff".to_string();
+    }
+}
+
+/// `GET /api/v3/depth` response.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DepthSnapshot {
+    pub last_update_id: u64,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+impl DepthSnapshot {
+    /// Snapshots carry no exchange time, the receive time is used instead.
+    pub fn to_market_book(
+        self,
+        sym: String,
+        received_timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> MarketBook {
+        let ladder = |levels: Vec<PriceLevel>| -> BTreeMap<Price, Qty> {
+            levels
+                .into_iter()
+                .map(|PriceLevel(price, size)| (Price::from_f64(price), Qty::from_f64(size)))
+                .filter(|(_, size)| !size.is_zero())
+                .collect()
+        };
+        MarketBook {
+            timestamp: OffsetDateTime::now_utc(),
+            market_created_timestamp: received_timestamp,
+            received_timestamp,
+            sym,
+            venue: VENUE.to_string(),
+            category: CATEGORY.to_string(),
+            asks: ladder(self.asks),
+            bids: ladder(self.bids),
+            misc: "snapshot".to_string(),
+            universal_id: ids.next_id(),
+            data_center: constants::MACHINE_ID.to_string(),
+            process_id: ids.runtime_id().to_string(),
+        }
+    }
+}
@@ -1,2 +1,4 @@
+pub mod auth;
+pub mod backfill;
 pub mod bitflyer_socketio;
 pub mod datamodel;
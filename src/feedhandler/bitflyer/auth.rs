@@ -0,0 +1,40 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// HMAC-SHA256 signature in lower hex, as required by bitFlyer Lightning.
+pub fn sign(api_secret: &str, message: &str) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(api_secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(message.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    pub api_key: String,
+    pub api_secret: String,
+}
+
+impl Credentials {
+    pub fn new(api_key: &str, api_secret: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            api_secret: api_secret.to_string(),
+        }
+    }
+
+    /// Payload of the realtime API `auth` event.
+    pub fn auth_params(&self) -> serde_json::Value {
+        let timestamp = (time::OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000) as i64;
+        let nonce = uuid::Uuid::new_v4().simple().to_string();
+        let signature = sign(&self.api_secret, &format!("{}{}", timestamp, nonce));
+        serde_json::json!({
+            "api_key": self.api_key,
+            "timestamp": timestamp,
+            "nonce": nonce,
+            "signature": signature,
+        })
+    }
+}
@@ -0,0 +1,146 @@
+use log::warn;
+use reqwest::blocking::Client;
+
+use crate::feedhandler::bitflyer::datamodel::Execution;
+
+const ENDPOINT: &str = "https://api.bitflyer.com";
+const PAGE_SIZE: usize = 500;
+/// Pages fetched per range at most, the public API allows about 500 requests per 5 minutes.
+const MAX_PAGES: usize = 10;
+
+/// Fetches executions missed by the socket from the public `GET /v1/executions`.
+pub struct ExecutionBackfill {
+    base_url: String,
+    http: Client,
+}
+
+impl Default for ExecutionBackfill {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecutionBackfill {
+    pub fn new() -> Self {
+        Self::with_url(ENDPOINT)
+    }
+
+    /// Send requests to another endpoint, e.g. a local mock server.
+    pub fn with_url(url: &str) -> Self {
+        Self {
+            base_url: url.trim_end_matches('/').to_string(),
+            http: Client::new(),
+        }
+    }
+
+    /// Executions of `product_code` with `after < id < before`, oldest first.
+    /// Pages back from `before`, the oldest executions of very long ranges are dropped.
+    pub fn executions(
+        &self,
+        product_code: &str,
+        after: u64,
+        before: u64,
+    ) -> Result<Vec<Execution>, reqwest::Error> {
+        let mut executions = vec![];
+        let mut page_before = before;
+        for page in 1..=MAX_PAGES {
+            // newest first
+            let executions_page: Vec<Execution> = self
+                .http
+                .get(format!("{}/v1/executions", self.base_url))
+                .query(&[
+                    ("product_code", product_code),
+                    ("count", &PAGE_SIZE.to_string()),
+                    ("before", &page_before.to_string()),
+                    ("after", &after.to_string()),
+                ])
+                .send()?
+                .error_for_status()?
+                .json()?;
+            let oldest = executions_page.iter().map(|e| e.id).min();
+            let full = executions_page.len() >= PAGE_SIZE;
+            executions.extend(
+                executions_page
+                    .into_iter()
+                    .filter(|e| after < e.id && e.id < before),
+            );
+            match oldest {
+                Some(oldest) if full && oldest > after + 1 => page_before = oldest,
+                _ => break,
+            }
+            if page == MAX_PAGES {
+                warn!(
+                    "Backfill of {} stopped after {} pages, executions after {} and before {} are missing",
+                    product_code, MAX_PAGES, after, page_before
+                );
+            }
+        }
+        executions.sort_by_key(|e| e.id);
+        Ok(executions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::mock_server::MockServer;
+
+    /// Executions with `ids`, newest first like the API.
+    fn page(ids: impl DoubleEndedIterator<Item = u64>) -> &'static str {
+        let executions: Vec<String> = ids
+            .rev()
+            .map(|id| {
+                format!(
+                    r#"{{"id": {}, "side": "BUY", "price": 5000000.0, "size": 0.01,
+                    "exec_date": "2026-10-19T00:00:00.000",
+                    "buy_child_order_acceptance_id": "JRF{}",
+                    "sell_child_order_acceptance_id": "JRF0"}}"#,
+                    id, id
+                )
+            })
+            .collect();
+        Box::leak(format!("[{}]", executions.join(",")).into_boxed_str())
+    }
+
+    #[test]
+    fn executions_are_paged_back_from_before() {
+        let server = MockServer::start(vec![
+            (200, vec![], page(1501..=2000)),
+            // the oldest id is outside of the range
+            (200, vec![], page(1100..=1500)),
+        ]);
+        let backfill = ExecutionBackfill::with_url(&format!("{}/", server.url));
+        let executions = backfill.executions("FX_BTC_JPY", 1100, 2001).unwrap();
+        assert_eq!(executions.len(), 900);
+        assert_eq!(executions.first().unwrap().id, 1101);
+        assert_eq!(executions.last().unwrap().id, 2000);
+        assert!(executions.windows(2).all(|w| w[0].id < w[1].id));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[0].path,
+            "/v1/executions?product_code=FX_BTC_JPY&count=500&before=2001&after=1100"
+        );
+        assert_eq!(
+            requests[1].path,
+            "/v1/executions?product_code=FX_BTC_JPY&count=500&before=1501&after=1100"
+        );
+    }
+
+    #[test]
+    fn a_full_page_reaching_after_is_the_last() {
+        let server = MockServer::start(vec![(200, vec![], page(11..=510))]);
+        let backfill = ExecutionBackfill::with_url(&server.url);
+        let executions = backfill.executions("FX_BTC_JPY", 10, 600).unwrap();
+        assert_eq!(executions.len(), 500);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn http_errors_are_returned() {
+        let server = MockServer::start(vec![(500, vec![], r#"{"status": -500}"#)]);
+        let backfill = ExecutionBackfill::with_url(&server.url);
+        assert!(backfill.executions("FX_BTC_JPY", 10, 20).is_err());
+    }
+}
@@ -1,11 +1,24 @@
-use crate::datamodels::enums::MarketData;
-use crate::datamodels::marketdata::MarketBook;
-use crate::feedhandler::bitflyer::datamodel::{Board, Execution};
-use log::{error, info, warn};
-use ordered_float::OrderedFloat;
+use crate::datamodels::enums::{InternalData, MarketData};
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::marketdata::{MarketBook, MarketTrade};
+use crate::datamodels::order::Order;
+use crate::datamodels::types::{Price, Qty};
+use crate::feedhandler::bitflyer::auth::Credentials;
+use crate::feedhandler::bitflyer::backfill::ExecutionBackfill;
+use crate::feedhandler::bitflyer::datamodel::{
+    Board, ChildOrderEvent, Execution, ParentOrderEvent, Ticker,
+};
+use crate::feedhandler::trade_dedup::{MissingRange, TradeCheck, TradeDedup};
+use crate::monitor::feed_health::FeedHealth;
+use crate::monitor::metrics;
+use crate::refdata::calendar::MaintenanceCalendar;
+use crate::refdata::instrument::InstrumentRegistry;
+use log::{debug, error, info, warn};
 use rust_socketio::{client::Client, ClientBuilder, Event, Payload, RawClient, TransportType};
+use serde::de::DeserializeOwned;
 use serde_json::from_str;
 use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::sleep;
 use std::vec;
 use std::{
@@ -15,94 +28,346 @@ use std::{
 use time::OffsetDateTime;
 
 type Shared<T> = Arc<Mutex<T>>;
+type Callback = Box<dyn FnMut(MarketData) + Send>;
+type PrivateCallback = Box<dyn FnMut(InternalData) + Send>;
+
+const ENDPOINT: &str = "https://io.lightstream.bitflyer.com";
+pub const PRIVATE_CHANNELS: [&str; 2] = ["child_order_events", "parent_order_events"];
+const VENUE: &str = "bitflyer";
+/// Execution ids remembered per sym to drop resent executions.
+pub const DEDUPE_WINDOW: usize = 10000;
 
 pub struct BitFlyerSocketIo {
-    pub callbacks: Shared<Vec<Box<dyn FnMut(MarketData) + Send>>>,
+    pub callbacks: Shared<Vec<Callback>>,
+    pub private_callbacks: Shared<Vec<PrivateCallback>>,
+    url: String,
+    credentials: Option<Credentials>,
+    instruments: Arc<InstrumentRegistry>,
+    calendar: Arc<MaintenanceCalendar>,
     client: Option<Client>,
+    orders: Shared<BTreeMap<String, Order>>,
     latest_book: Shared<BTreeMap<String, MarketBook>>,
     latest_bid: Shared<BTreeMap<String, f64>>,
     latest_ask: Shared<BTreeMap<String, f64>>,
+    feed_latency: Shared<BTreeMap<String, time::Duration>>,
+    health: FeedHealth,
+    trades: Shared<TradeDedup>,
+    backfill: Option<ExecutionBackfill>,
+    ids: IdSource,
+}
+
+impl Default for BitFlyerSocketIo {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BitFlyerSocketIo {
     pub fn new() -> Self {
+        Self::with_url(ENDPOINT)
+    }
+
+    /// Connect to another socket.io endpoint, e.g. a local stand-in server.
+    pub fn with_url(url: &str) -> Self {
         Self {
+            url: url.to_string(),
+            credentials: None,
+            instruments: Arc::new(InstrumentRegistry::new()),
+            calendar: Arc::new(MaintenanceCalendar::new()),
             client: None,
             callbacks: Arc::new(Mutex::new(vec![])),
+            private_callbacks: Arc::new(Mutex::new(vec![])),
+            orders: Arc::new(Mutex::new(BTreeMap::new())),
             latest_book: Arc::new(Mutex::new(BTreeMap::new())),
             latest_bid: Arc::new(Mutex::new(BTreeMap::new())),
             latest_ask: Arc::new(Mutex::new(BTreeMap::new())),
+            feed_latency: Arc::new(Mutex::new(BTreeMap::new())),
+            health: FeedHealth::default(),
+            trades: Arc::new(Mutex::new(TradeDedup::new(DEDUPE_WINDOW))),
+            backfill: None,
+            ids: IdSource::default(),
         }
     }
 
-    pub fn set_callback(&mut self, callback: Box<dyn FnMut(MarketData) + Send>) {
+    pub fn set_callback(&mut self, callback: Callback) {
         self.callbacks.lock().unwrap().push(callback);
     }
 
+    /// Receives `Order` updates and `Execution`s of the private channels.
+    pub fn set_private_callback(&mut self, callback: PrivateCallback) {
+        self.private_callbacks.lock().unwrap().push(callback);
+    }
+
+    /// Credentials used to authenticate before subscribing private channels.
+    pub fn set_credentials(&mut self, api_key: &str, api_secret: &str) {
+        self.credentials = Some(Credentials::new(api_key, api_secret));
+    }
+
+    /// Maps channel product codes to symbols. Unknown product codes drop their underscores.
+    pub fn set_instruments(&mut self, instruments: Arc<InstrumentRegistry>) {
+        self.instruments = instruments;
+    }
+
+    /// Disconnections within a maintenance window are expected and only logged as info.
+    pub fn set_calendar(&mut self, calendar: Arc<MaintenanceCalendar>) {
+        self.calendar = calendar;
+    }
+
+    /// Public channels are tracked for staleness, private ones may be quiet for hours.
+    pub fn set_health(&mut self, health: FeedHealth) {
+        self.health = health;
+    }
+
+    /// Source of the ids of the records built from the channels, random by default.
+    pub fn set_ids(&mut self, ids: IdSource) {
+        self.ids = ids;
+    }
+
+    /// Execution ids remembered per sym, 0 keeps resent executions.
+    pub fn set_dedupe_window(&mut self, window: usize) {
+        self.trades = Arc::new(Mutex::new(TradeDedup::new(window)));
+    }
+
+    /// Fetch the executions possibly missed across a reconnect before passing on the new ones.
+    pub fn set_backfill(&mut self, backfill: ExecutionBackfill) {
+        self.backfill = Some(backfill);
+    }
+
     pub fn connect(mut self, channels: Vec<String>) {
-        let channel_memo = Arc::new(Mutex::new(channels.clone()));
-        let client = ClientBuilder::new("https://io.lightstream.bitflyer.com")
+        let (private_channels, public_channels): (Vec<String>, Vec<String>) = channels
+            .into_iter()
+            .partition(|channel| PRIVATE_CHANNELS.contains(&channel.as_str()));
+        self.health.subscribe(VENUE, &public_channels);
+        let health = self.health.clone();
+        let trades = self.trades.clone();
+        let connected = AtomicBool::new(false);
+        let channel_memo = Arc::new(Mutex::new(public_channels));
+        let private_channel_memo = Arc::new(Mutex::new(private_channels));
+        let credentials = self.credentials.clone();
+        let calendar = self.calendar.clone();
+        let client = ClientBuilder::new(self.url.as_str())
             .transport_type(TransportType::Websocket)
             .reconnect_on_disconnect(true)
             .reconnect_delay(3000, 30000)
             .on("open", move |_payload: Payload, _raw_client| {
                 info!("Open socket to BitFlyer");
+                if connected.swap(true, Ordering::Relaxed) {
+                    health.on_reconnect(VENUE);
+                    trades.lock().unwrap().on_reconnect();
+                }
                 for channel in channel_memo.lock().unwrap().iter() {
                     info!("Send subscribe message for channel: {}", channel.as_str());
                     _raw_client.emit("subscribe", channel.as_str()).unwrap();
                 }
+
+                // private channels are subscribed once auth is acknowledged
+                let private_channels = private_channel_memo.lock().unwrap().clone();
+                if private_channels.is_empty() {
+                    return;
+                }
+                match &credentials {
+                    Some(credentials) => {
+                        info!("Send auth message");
+                        _raw_client
+                            .emit_with_ack(
+                                "auth",
+                                credentials.auth_params(),
+                                Duration::from_secs(10),
+                                move |payload: Payload, raw_client: RawClient| {
+                                    if let Some(err) = Self::auth_error(&payload) {
+                                        error!("Auth failed on BitFlyer : {}", err);
+                                        return;
+                                    }
+                                    for channel in private_channels.iter() {
+                                        info!("Send subscribe message for channel: {}", channel);
+                                        raw_client.emit("subscribe", channel.as_str()).unwrap();
+                                    }
+                                },
+                            )
+                            .unwrap();
+                    }
+                    None => error!("Credentials are required to subscribe private channels"),
+                }
             })
             .on("close", move |_payload: Payload, _raw_client: RawClient| {
                 warn!("Close socket to BitFlyer");
             })
             .on("error", move |_payload: Payload, _raw_client: RawClient| {
-                error!("Error on socket to BitFlyer : {:#?}", _payload);
-                sleep(Duration::from_secs(3));
-
-                // sleep within maintenance.
-                let now = OffsetDateTime::now_utc();
-                if now.hour() == 19 && now.minute() < 10 {
-                    info!("Sleep within maintenance. ...");
-                    let maintenance_end =
-                        now.replace_minute(10).unwrap().replace_second(0).unwrap();
-                    sleep(Duration::from_secs_f64(
-                        (maintenance_end - now).as_seconds_f64(),
-                    ));
+                // reconnection is left to the client's backoff, never block this thread.
+                match calendar.maintenance_end(VENUE, OffsetDateTime::now_utc()) {
+                    Some(until) => info!(
+                        "Error on socket to BitFlyer within maintenance until {} : {:?}",
+                        until, _payload
+                    ),
+                    None => error!("Error on socket to BitFlyer : {:#?}", _payload),
                 }
             })
             .on_any(move |event: Event, payload: Payload, _: RawClient| {
+                let received_timestamp = OffsetDateTime::now_utc();
                 if let Payload::String(message) = payload {
+                    self.health
+                        .on_message(VENUE, event.as_str(), received_timestamp);
                     match event.as_str() {
+                        "child_order_events" => {
+                            let events: Vec<ChildOrderEvent> =
+                                match Self::parse(&self.health, event.as_str(), &message) {
+                                    Some(events) => events,
+                                    None => return,
+                                };
+                            for evt in events {
+                                let sym = self.instruments.sym(VENUE, &evt.product_code);
+                                let mut orders = self.orders.lock().unwrap();
+                                let order = orders
+                                    .entry(evt.child_order_acceptance_id.clone())
+                                    .or_insert_with(|| {
+                                        evt.to_order(sym.clone(), received_timestamp, &self.ids)
+                                    });
+                                if let Err(anomaly) = evt.update_order(order, received_timestamp) {
+                                    warn!(
+                                        "Ignored {} child order event : {}",
+                                        evt.event_type, anomaly
+                                    );
+                                }
+                                let order = order.clone();
+                                if order.order_status.is_terminal() {
+                                    orders.remove(&evt.child_order_acceptance_id);
+                                }
+                                drop(orders);
+
+                                // pass to callback - Execution, then Order
+                                let execution =
+                                    evt.to_execution(sym, received_timestamp, &self.ids);
+                                for callback in self.private_callbacks.lock().unwrap().iter_mut() {
+                                    if let Some(execution) = &execution {
+                                        callback(InternalData::Execution(execution.clone()));
+                                    }
+                                    callback(InternalData::Order(order.clone()));
+                                }
+                            }
+                        }
+
+                        "parent_order_events" => {
+                            let events: Vec<ParentOrderEvent> =
+                                match Self::parse(&self.health, event.as_str(), &message) {
+                                    Some(events) => events,
+                                    None => return,
+                                };
+                            for evt in events {
+                                let sym = self.instruments.sym(VENUE, &evt.product_code);
+                                let mut orders = self.orders.lock().unwrap();
+                                let order = orders
+                                    .entry(evt.parent_order_acceptance_id.clone())
+                                    .or_insert_with(|| {
+                                        evt.to_order(sym, received_timestamp, &self.ids)
+                                    });
+                                if let Err(anomaly) = evt.update_order(order, received_timestamp) {
+                                    warn!(
+                                        "Ignored {} parent order event : {}",
+                                        evt.event_type, anomaly
+                                    );
+                                }
+                                let order = order.clone();
+                                if order.order_status.is_terminal() {
+                                    orders.remove(&evt.parent_order_acceptance_id);
+                                }
+                                drop(orders);
+
+                                // pass to callback - Order
+                                for callback in self.private_callbacks.lock().unwrap().iter_mut() {
+                                    callback(InternalData::Order(order.clone()));
+                                }
+                            }
+                        }
+
                         evt if evt.contains("executions") => {
                             // parse message
-                            let sym = evt.split("executions_").last().unwrap().replace("_", "");
-                            let executions_msg: Vec<Execution> = from_str(&message).unwrap();
+                            let product_code = evt.split("executions_").last().unwrap();
+                            let sym = Self::channel_sym(&self.instruments, evt, "executions_");
+                            let category =
+                                Self::channel_category(&self.instruments, evt, "executions_");
+                            let executions_msg: Vec<Execution> =
+                                match Self::parse(&self.health, evt, &message) {
+                                    Some(executions) => executions,
+                                    None => return,
+                                };
 
-                            // pass to callback
+                            // track exchange -> receive latency to estimate board timestamps
+                            if let Some(exec) = executions_msg.last() {
+                                self.feed_latency
+                                    .lock()
+                                    .unwrap()
+                                    .insert(sym.clone(), received_timestamp - exec.exec_date);
+                            }
+
+                            // pass to callback, without resent executions
                             for exec in executions_msg {
-                                let market_trade = exec.to_market_trade(sym.clone());
-                                for callback in self.callbacks.lock().unwrap().iter_mut() {
-                                    callback(MarketData::MarketTrade(market_trade.clone()));
+                                let market_trade = exec.to_market_trade(
+                                    sym.clone(),
+                                    category.clone(),
+                                    received_timestamp,
+                                    &self.ids,
+                                );
+                                let market_trades = Self::dedupe(
+                                    &self.trades,
+                                    self.backfill.as_ref(),
+                                    &self.ids,
+                                    product_code,
+                                    market_trade,
+                                );
+                                for market_trade in market_trades {
+                                    for callback in self.callbacks.lock().unwrap().iter_mut() {
+                                        callback(MarketData::MarketTrade(market_trade.clone()));
+                                    }
                                 }
                             }
                         }
 
                         evt if evt.contains("ticker") => {
-                            // let ticker_msg: Ticker = from_str(&message).unwrap();
-                            // let mut t = self.ticker.lock().unwrap();
-                            // *t = ticker_msg;
+                            // parse message
+                            let sym = Self::channel_sym(&self.instruments, evt, "ticker_");
+                            let category =
+                                Self::channel_category(&self.instruments, evt, "ticker_");
+                            let ticker_msg: Ticker = match Self::parse(&self.health, evt, &message)
+                            {
+                                Some(ticker) => ticker,
+                                None => return,
+                            };
+                            self.feed_latency
+                                .lock()
+                                .unwrap()
+                                .insert(sym.clone(), received_timestamp - ticker_msg.timestamp);
+                            let ticker =
+                                ticker_msg.to_ticker(sym, category, received_timestamp, &self.ids);
+
+                            // pass to callback
+                            for callback in self.callbacks.lock().unwrap().iter_mut() {
+                                callback(MarketData::Ticker(ticker.clone()));
+                            }
                         }
 
                         evt if evt.contains("board_snapshot") => {
                             // parse message
-                            let sym = evt
-                                .split("board_snapshot_")
-                                .last()
-                                .unwrap()
-                                .replace("_", "");
-                            let board_msg: Board = from_str(&message).unwrap();
-                            let market_book = board_msg.to_market_book(sym.clone());
-                            let rate = market_book.to_rate();
+                            let sym = Self::channel_sym(&self.instruments, evt, "board_snapshot_");
+                            let category =
+                                Self::channel_category(&self.instruments, evt, "board_snapshot_");
+                            let board_msg: Board = match Self::parse(&self.health, evt, &message) {
+                                Some(board) => board,
+                                None => return,
+                            };
+                            let market_created_timestamp = Self::estimate_market_timestamp(
+                                &self.feed_latency,
+                                &sym,
+                                received_timestamp,
+                            );
+                            let market_book = board_msg.to_market_book(
+                                sym.clone(),
+                                category,
+                                market_created_timestamp,
+                                received_timestamp,
+                                &self.ids,
+                            );
+                            let rate = market_book.to_rate(&self.ids);
+                            self.health.check_book(VENUE, evt, &market_book);
 
                             // update latest book
                             let mut books = self.latest_book.lock().unwrap();
@@ -131,18 +396,32 @@ impl BitFlyerSocketIo {
 
                         evt if evt.contains("board") => {
                             // parse message
-                            let sym = evt.split("board_").last().unwrap().replace("_", "");
-                            let board_msg: Board = from_str(&message).unwrap();
+                            let sym = Self::channel_sym(&self.instruments, evt, "board_");
+                            let board_msg: Board = match Self::parse(&self.health, evt, &message) {
+                                Some(board) => board,
+                                None => return,
+                            };
                             let mut books = self.latest_book.lock().unwrap();
 
                             if books.contains_key(sym.as_str()) {
                                 // update latest book if snapshot already exists
                                 let latest_book = books.get(sym.as_str()).unwrap();
-                                let merged_book =
-                                    Self::merge_board_message(latest_book.clone(), board_msg);
-                                let mut rate = merged_book.to_rate();
+                                let market_created_timestamp = Self::estimate_market_timestamp(
+                                    &self.feed_latency,
+                                    &sym,
+                                    received_timestamp,
+                                );
+                                let merged_book = Self::merge_board_message(
+                                    latest_book.clone(),
+                                    board_msg,
+                                    market_created_timestamp,
+                                    received_timestamp,
+                                    &self.ids,
+                                );
+                                let mut rate = merged_book.to_rate(&self.ids);
                                 rate.mid_price = (rate.best_ask + rate.best_bid) / 2.0;
                                 rate.misc = "diff".to_string();
+                                self.health.check_book(VENUE, evt, &merged_book);
 
                                 // update latest book
                                 *books.get_mut(&sym).unwrap() = merged_book.clone();
@@ -208,36 +487,391 @@ impl BitFlyerSocketIo {
         }
     }
 
-    fn merge_board_message(latest_book: MarketBook, board: Board) -> MarketBook {
+    /// Unparsable messages are counted on the channel and skipped.
+    fn parse<T: DeserializeOwned>(health: &FeedHealth, channel: &str, message: &str) -> Option<T> {
+        match from_str(message) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                health.on_parse_error(VENUE, channel);
+                warn!("Failed to parse {} message: {} : {}", channel, message, e);
+                None
+            }
+        }
+    }
+
+    /// The `auth` ack carries its arguments as an array, `[null]` on success.
+    fn auth_error(payload: &Payload) -> Option<String> {
+        let message = match payload {
+            Payload::String(message) => message,
+            Payload::Binary(_) => return None,
+        };
+        match from_str::<serde_json::Value>(message) {
+            Ok(serde_json::Value::Null) => None,
+            Ok(serde_json::Value::Array(args)) => match args.first() {
+                None | Some(serde_json::Value::Null) => None,
+                Some(err) => Some(err.to_string()),
+            },
+            _ if message.is_empty() => None,
+            _ => Some(message.clone()),
+        }
+    }
+
+    /// Drop a resent trade. The first new trade after a reconnect comes after the trades
+    /// backfilled in between, if any.
+    fn dedupe(
+        trades: &Shared<TradeDedup>,
+        backfill: Option<&ExecutionBackfill>,
+        ids: &IdSource,
+        product_code: &str,
+        market_trade: MarketTrade,
+    ) -> Vec<MarketTrade> {
+        let check = trades.lock().unwrap().check(&market_trade);
+        match check {
+            TradeCheck::New => vec![market_trade],
+            TradeCheck::Duplicate => {
+                debug!("Drop resent execution {}", market_trade.trade_id);
+                metrics::TRADE_DUPLICATES
+                    .with_label_values(&[VENUE, &market_trade.sym])
+                    .inc();
+                vec![]
+            }
+            TradeCheck::AfterGap(range) => {
+                let mut market_trades = match backfill {
+                    Some(backfill) => Self::backfill(
+                        trades,
+                        backfill,
+                        ids,
+                        product_code,
+                        &range,
+                        &market_trade.category,
+                        market_trade.received_timestamp,
+                    ),
+                    None => {
+                        warn!(
+                            "Executions of {} after {} and before {} may be missing after reconnect",
+                            range.sym, range.after, range.before
+                        );
+                        vec![]
+                    }
+                };
+                market_trades.push(market_trade);
+                market_trades
+            }
+        }
+    }
+
+    /// New executions of `range` from the REST API, marked `backfill` in `misc`.
+    fn backfill(
+        trades: &Shared<TradeDedup>,
+        backfill: &ExecutionBackfill,
+        ids: &IdSource,
+        product_code: &str,
+        range: &MissingRange,
+        category: &str,
+        received_timestamp: OffsetDateTime,
+    ) -> Vec<MarketTrade> {
+        let executions = match backfill.executions(product_code, range.after, range.before) {
+            Ok(executions) => executions,
+            Err(e) => {
+                error!(
+                    "Failed to backfill executions of {} after {} and before {} : {}",
+                    range.sym, range.after, range.before, e
+                );
+                return vec![];
+            }
+        };
+        let mut trades = trades.lock().unwrap();
+        let market_trades: Vec<MarketTrade> = executions
+            .iter()
+            .map(|exec| {
+                let mut market_trade = exec.to_market_trade(
+                    range.sym.clone(),
+                    category.to_string(),
+                    received_timestamp,
+                    ids,
+                );
+                market_trade.misc = "backfill".to_string();
+                market_trade
+            })
+            .filter(|market_trade| trades.check(market_trade) == TradeCheck::New)
+            .collect();
+        info!(
+            "Backfilled {} executions of {} after {} and before {}",
+            market_trades.len(),
+            range.sym,
+            range.after,
+            range.before
+        );
+        metrics::TRADES_BACKFILLED
+            .with_label_values(&[VENUE, &range.sym])
+            .inc_by(market_trades.len() as u64);
+        market_trades
+    }
+
+    /// Board channels carry no exchange time.
+    /// Estimate it from the latest exchange -> receive latency observed on the executions or ticker channel,
+    /// and fall back to the receive time until an execution has been seen.
+    fn estimate_market_timestamp(
+        feed_latency: &Shared<BTreeMap<String, time::Duration>>,
+        sym: &str,
+        received_timestamp: OffsetDateTime,
+    ) -> OffsetDateTime {
+        match feed_latency.lock().unwrap().get(sym) {
+            Some(latency) if latency.is_positive() => received_timestamp - *latency,
+            _ => received_timestamp,
+        }
+    }
+
+    /// `lightning_board_FX_BTC_JPY` with `board_` -> `FXBTCJPY`.
+    fn channel_sym(instruments: &InstrumentRegistry, channel: &str, prefix: &str) -> String {
+        let product_code = channel.split(prefix).last().unwrap();
+        instruments.sym(VENUE, product_code)
+    }
+
+    /// `lightning_board_FX_BTC_JPY` with `board_` -> `lightning`, the category of the instrument
+    /// or else the channel's own prefix.
+    fn channel_category(instruments: &InstrumentRegistry, channel: &str, prefix: &str) -> String {
+        let product_code = channel.split(prefix).last().unwrap();
+        instruments
+            .category(VENUE, product_code)
+            .unwrap_or_else(|| channel.split('_').next().unwrap().to_string())
+    }
+
+    fn merge_board_message(
+        latest_book: MarketBook,
+        board: Board,
+        market_created_timestamp: OffsetDateTime,
+        received_timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> MarketBook {
         /*merge board_message to the latest_book data.
          */
         let mut merged_book = latest_book.clone();
-        let now = OffsetDateTime::now_utc();
-        merged_book.timestamp = now;
-        merged_book.market_created_timestamp = now;
-        merged_book.universal_id = uuid::Uuid::new_v4().to_string();
+        merged_book.timestamp = OffsetDateTime::now_utc();
+        merged_book.market_created_timestamp = market_created_timestamp;
+        merged_book.received_timestamp = received_timestamp;
+        merged_book.universal_id = ids.next_id();
         merged_book.misc = "diff".to_string();
 
         // insert new data
         for ask in board.asks {
-            merged_book.asks.insert(OrderedFloat(ask.price), ask.size);
-            if merged_book.asks.get(&OrderedFloat(ask.price)) == Some(&0f64) {
-                merged_book.asks.remove(&OrderedFloat(ask.price));
+            let price = Price::from_f64(ask.price);
+            merged_book.asks.insert(price, Qty::from_f64(ask.size));
+            if merged_book.asks.get(&price).is_some_and(Qty::is_zero) {
+                merged_book.asks.remove(&price);
             }
         }
         for bid in board.bids {
-            merged_book
-                .bids
-                .insert(OrderedFloat(bid.price), bid.size as f64);
-            if merged_book.bids.get(&OrderedFloat(bid.price)) == Some(&0f64) {
-                merged_book.bids.remove(&OrderedFloat(bid.price));
+            let price = Price::from_f64(bid.price);
+            merged_book.bids.insert(price, Qty::from_f64(bid.size));
+            if merged_book.bids.get(&price).is_some_and(Qty::is_zero) {
+                merged_book.bids.remove(&price);
             }
         }
 
         // drop 0 size ladder
-        merged_book.asks.retain(|_, &mut value| value != 0.0);
-        merged_book.bids.retain(|_, &mut value| value != 0.0);
+        merged_book.asks.retain(|_, value| !value.is_zero());
+        merged_book.bids.retain(|_, value| !value.is_zero());
 
         merged_book
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodels::order::OrderStatus;
+    use crate::feedhandler::bitflyer::auth::sign;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use tungstenite::Message;
+
+    const CHILD_ORDER_EVENTS: &str = r#"42["child_order_events",[
+        {"product_code":"FX_BTC_JPY","child_order_id":"JOR1","child_order_acceptance_id":"JRF1","event_date":"2026-10-19T00:00:00.000Z","event_type":"ORDER","child_order_type":"LIMIT","side":"BUY","price":5000000,"size":1.0},
+        {"product_code":"FX_BTC_JPY","child_order_id":"JOR1","child_order_acceptance_id":"JRF1","event_date":"2026-10-19T00:00:01.000Z","event_type":"EXECUTION","side":"BUY","price":5000000,"size":0.4,"exec_id":1},
+        {"product_code":"FX_BTC_JPY","child_order_id":"JOR1","child_order_acceptance_id":"JRF1","event_date":"2026-10-19T00:00:02.000Z","event_type":"EXECUTION","side":"BUY","price":5000000,"size":0.6,"exec_id":2,"outstanding_size":0}
+    ]]"#;
+
+    /// `42<ack id>["event", arg]` sent by the client -> (ack id, event, arg).
+    fn parse_emit(message: &str) -> (Option<u32>, String, serde_json::Value) {
+        let message = message.strip_prefix("42").unwrap();
+        let split = message.find('[').unwrap();
+        let ack_id = message[..split].parse().ok();
+        let args: Vec<serde_json::Value> = from_str(&message[split..]).unwrap();
+        (
+            ack_id,
+            args[0].as_str().unwrap().to_string(),
+            args[1].clone(),
+        )
+    }
+
+    /// Local socket.io server acking `auth` with `api_secret`, then sending the child order events
+    /// once they are subscribed. Returns the (event, arg) emitted by the client.
+    fn socketio_server(
+        api_secret: &'static str,
+    ) -> (String, mpsc::Receiver<(String, serde_json::Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (emitted, received) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            let open = r#"0{"sid":"engine","upgrades":[],"pingInterval":25000,"pingTimeout":20000,"maxPayload":1000000}"#;
+            socket
+                .write_message(Message::Text(open.to_string()))
+                .unwrap();
+            while let Ok(message) = socket.read_message() {
+                let message = match message {
+                    Message::Text(message) => message,
+                    _ => continue,
+                };
+                if message.starts_with("40") {
+                    let connect = r#"40{"sid":"socket"}"#.to_string();
+                    socket.write_message(Message::Text(connect)).unwrap();
+                    continue;
+                }
+                if !message.starts_with("42") {
+                    continue;
+                }
+                let (ack_id, event, arg) = parse_emit(&message);
+                emitted.send((event.clone(), arg.clone())).unwrap();
+                match (event.as_str(), arg.as_str()) {
+                    ("auth", _) => {
+                        let payload =
+                            format!("{}{}", arg["timestamp"], arg["nonce"].as_str().unwrap());
+                        let ack = if arg["signature"] == sign(api_secret, &payload) {
+                            "[null]"
+                        } else {
+                            r#"["invalid signature"]"#
+                        };
+                        let ack = format!("43{}{}", ack_id.unwrap(), ack);
+                        socket.write_message(Message::Text(ack)).unwrap();
+                    }
+                    ("subscribe", Some("child_order_events")) => {
+                        let events = CHILD_ORDER_EVENTS.to_string();
+                        socket.write_message(Message::Text(events)).unwrap();
+                    }
+                    _ => {}
+                }
+            }
+        });
+        (url, received)
+    }
+
+    #[test]
+    fn private_channels_are_subscribed_after_auth() {
+        let (url, emitted) = socketio_server("secret");
+        let (sender, received) = mpsc::channel();
+        let mut bfsocket = BitFlyerSocketIo::with_url(&url);
+        bfsocket.set_credentials("key", "secret");
+        bfsocket.set_private_callback(Box::new(move |data| sender.send(data).unwrap()));
+        bfsocket.connect(vec![
+            "lightning_ticker_FX_BTC_JPY".to_string(),
+            "child_order_events".to_string(),
+        ]);
+
+        let timeout = Duration::from_secs(10);
+        let mut emits = vec![];
+        for _ in 0..3 {
+            emits.push(emitted.recv_timeout(timeout).unwrap());
+        }
+        assert_eq!(emits[0].0, "subscribe");
+        assert_eq!(emits[0].1, "lightning_ticker_FX_BTC_JPY");
+        assert_eq!(emits[1].0, "auth");
+        assert_eq!(emits[1].1["api_key"], "key");
+        assert_eq!(emits[2].0, "subscribe");
+        assert_eq!(emits[2].1, "child_order_events");
+
+        let mut statuses = vec![];
+        let mut executions = vec![];
+        for _ in 0..5 {
+            match received.recv_timeout(timeout).unwrap() {
+                InternalData::Order(order) => {
+                    assert_eq!(order.order_id, "JRF1");
+                    assert_eq!(order.sym, "FXBTCJPY");
+                    statuses.push(order.order_status);
+                }
+                InternalData::Execution(execution) => executions.push(execution.amount),
+                data => panic!("unexpected {:?}", data),
+            }
+        }
+        assert_eq!(
+            statuses,
+            vec![
+                OrderStatus::Accepted,
+                OrderStatus::PartiallyFilled,
+                OrderStatus::Filled
+            ]
+        );
+        assert_eq!(executions, vec![Qty::from_f64(0.4), Qty::from_f64(0.6)]);
+    }
+
+    #[test]
+    fn auth_ack_is_an_error_unless_null() {
+        let ack = |message: &str| BitFlyerSocketIo::auth_error(&Payload::String(message.into()));
+        assert_eq!(ack("[null]"), None);
+        assert_eq!(ack("null"), None);
+        assert_eq!(ack("[]"), None);
+        assert_eq!(ack(""), None);
+        assert_eq!(
+            ack(r#"["invalid signature"]"#),
+            Some(r#""invalid signature""#.to_string())
+        );
+    }
+
+    #[test]
+    fn category_defaults_to_the_channel_prefix() {
+        let empty = InstrumentRegistry::new();
+        for (channel, prefix) in [
+            ("lightning_board_FX_BTC_JPY", "board_"),
+            ("lightning_board_snapshot_FX_BTC_JPY", "board_snapshot_"),
+            ("lightning_ticker_FX_BTC_JPY", "ticker_"),
+            ("lightning_executions_FX_BTC_JPY", "executions_"),
+        ] {
+            assert_eq!(
+                BitFlyerSocketIo::channel_category(&empty, channel, prefix),
+                "lightning"
+            );
+            assert_eq!(
+                BitFlyerSocketIo::channel_sym(&empty, channel, prefix),
+                "FXBTCJPY"
+            );
+        }
+
+        let mut instruments = InstrumentRegistry::from_toml(
+            r#"
+            [[instrument]]
+            venue = "bitflyer"
+            category = "futures"
+            native_symbol = "BTCJPY27DEC2026"
+            sym = "BTCJPYDEC26"
+            base = "BTC"
+            quote = "JPY"
+            tick_size = 1.0
+            lot_size = 0.00000001
+            min_size = 0.01
+            contract_type = "Futures"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            BitFlyerSocketIo::channel_category(
+                &instruments,
+                "lightning_executions_BTCJPY27DEC2026",
+                "executions_"
+            ),
+            "futures"
+        );
+        let mut instrument = instruments.instruments()[0].clone();
+        instrument.category = "".to_string();
+        instruments.insert(instrument);
+        assert_eq!(
+            BitFlyerSocketIo::channel_category(
+                &instruments,
+                "lightning_executions_BTCJPY27DEC2026",
+                "executions_"
+            ),
+            "lightning"
+        );
+    }
+}
@@ -1,10 +1,15 @@
 use std::collections::BTreeMap;
 
 use crate::constants::constants;
-use crate::datamodels::marketdata::{MarketBook, MarketTrade, Rate};
+use crate::datamodels::execution;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::marketdata::{self, MarketBook, MarketTrade, Rate};
+use crate::datamodels::order::{Order, OrderStatus, OrderType};
+use crate::datamodels::types::{Price, Qty, Side};
+use crate::oms::order_lifecycle::OrderAnomaly;
 
-use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
@@ -21,6 +26,12 @@ pub struct Board {
     pub bids: Vec<PriceSize>,
 }
 
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Board {
     pub fn new() -> Self {
         Self {
@@ -36,38 +47,54 @@ impl Board {
         }
     }
 
-    pub fn to_market_book(self, sym: String) -> MarketBook {
+    /// Board messages carry no exchange time, so `market_created_timestamp` is estimated by the caller.
+    pub fn to_market_book(
+        self,
+        sym: String,
+        category: String,
+        market_created_timestamp: OffsetDateTime,
+        received_timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> MarketBook {
         let mut asks = BTreeMap::new();
         let mut bids = BTreeMap::new();
         for ask in self.asks {
-            asks.insert(OrderedFloat(ask.price), ask.size);
+            asks.insert(Price::from_f64(ask.price), Qty::from_f64(ask.size));
         }
         for bid in self.bids {
-            bids.insert(OrderedFloat(bid.price), bid.size);
+            bids.insert(Price::from_f64(bid.price), Qty::from_f64(bid.size));
         }
-        let t = OffsetDateTime::now_utc();
         MarketBook {
-            timestamp: t,
-            market_created_timestamp: t,
-            sym: sym,
+            timestamp: OffsetDateTime::now_utc(),
+            market_created_timestamp,
+            received_timestamp,
+            sym,
             venue: "bitflyer".to_string(),
-            category: "lightning".to_string(),
-            asks: asks,
-            bids: bids,
-            universal_id: Uuid::new_v4().to_string(),
+            category,
+            asks,
+            bids,
+            universal_id: ids.next_id(),
             misc: "".to_string(),
             data_center: constants::MACHINE_ID.to_string(),
-            process_id: constants::RUNTIME_ID.to_string(),
+            process_id: ids.runtime_id().to_string(),
         }
     }
 
-    pub fn to_rate(self, sym: String) -> Rate {
+    pub fn to_rate(
+        self,
+        sym: String,
+        category: String,
+        market_created_timestamp: OffsetDateTime,
+        received_timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> Rate {
         Rate {
             timestamp: OffsetDateTime::now_utc(),
-            market_created_timestamp: OffsetDateTime::now_utc(),
-            sym: sym,
+            market_created_timestamp,
+            received_timestamp,
+            sym,
             venue: "bitflyer".to_string(),
-            category: "lightning".to_string(),
+            category,
             best_bid: self
                 .bids
                 .iter()
@@ -89,37 +116,67 @@ impl Board {
                 .map(|p| p.price)
                 .unwrap_or(f64::INFINITY),
             mid_price: self.mid_price,
-            universal_id: Uuid::new_v4().to_string(),
+            universal_id: ids.next_id(),
             misc: "".to_string(),
             data_center: constants::MACHINE_ID.to_string(),
-            process_id: constants::RUNTIME_ID.to_string(),
+            process_id: ids.runtime_id().to_string(),
         }
     }
 }
 
+/// Execution of the `lightning_executions_<product>` channel and of `GET /v1/executions`.
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Execution {
-    pub id: u32,
+    pub id: u64,
     pub side: String,
     pub price: f64,
     pub size: f64,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(
+        deserialize_with = "deserialize_exec_date",
+        serialize_with = "time::serde::rfc3339::serialize"
+    )]
     pub exec_date: OffsetDateTime,
     pub buy_child_order_acceptance_id: String,
     pub sell_child_order_acceptance_id: String,
 }
 
+/// The REST API omits the offset of UTC timestamps, e.g. `2015-07-08T02:43:34.823`.
+fn deserialize_exec_date<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<OffsetDateTime, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    let has_offset = value.ends_with('Z')
+        || value
+            .rsplit('T')
+            .next()
+            .is_some_and(|t| t.contains(['+', '-']));
+    let value = if has_offset {
+        value
+    } else {
+        format!("{}Z", value)
+    };
+    OffsetDateTime::parse(&value, &Rfc3339).map_err(serde::de::Error::custom)
+}
+
 impl Execution {
-    pub fn to_market_trade(&self, sym: String) -> MarketTrade {
+    /// `universal_id` is derived from the execution id, so resent executions keep their id.
+    pub fn to_market_trade(
+        &self,
+        sym: String,
+        category: String,
+        received_timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> MarketTrade {
         MarketTrade {
             timestamp: OffsetDateTime::now_utc(),
             market_created_timestamp: self.exec_date,
-            sym: sym,
+            received_timestamp,
+            sym,
             venue: "bitflyer".to_string(),
-            category: "lightning".to_string(),
-            side: if self.side == "BUY" { 1 } else { -1 },
-            price: self.price,
-            amount: self.size,
+            category,
+            side: to_side(&self.side),
+            price: Price::from_f64(self.price),
+            amount: Qty::from_f64(self.size),
             trade_id: self.id.to_string(),
             order_ids: [
                 self.buy_child_order_acceptance_id.clone(),
@@ -127,9 +184,13 @@ impl Execution {
             ]
             .join(";"),
             misc: "".to_string(),
-            universal_id: Uuid::new_v4().to_string(),
+            universal_id: Uuid::new_v3(
+                &Uuid::NAMESPACE_OID,
+                format!("bitflyer/execution/{}", self.id).as_bytes(),
+            )
+            .to_string(),
             data_center: constants::MACHINE_ID.to_string(),
-            process_id: constants::RUNTIME_ID.to_string(),
+            process_id: ids.runtime_id().to_string(),
         }
     }
 }
@@ -154,6 +215,12 @@ pub struct Ticker {
     pub volume_by_product: f64,
 }
 
+impl Default for Ticker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Ticker {
     pub fn new() -> Self {
         Self {
@@ -174,4 +241,345 @@ impl Ticker {
             volume_by_product: 0.0,
         }
     }
+
+    pub fn to_ticker(
+        &self,
+        sym: String,
+        category: String,
+        received_timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> marketdata::Ticker {
+        marketdata::Ticker {
+            timestamp: OffsetDateTime::now_utc(),
+            market_created_timestamp: self.timestamp,
+            received_timestamp,
+            sym,
+            venue: "bitflyer".to_string(),
+            category,
+            best_bid: self.best_bid,
+            best_ask: self.best_ask,
+            best_bid_size: self.best_bid_size,
+            best_ask_size: self.best_ask_size,
+            total_bid_depth: self.total_bid_depth,
+            total_ask_depth: self.total_ask_depth,
+            mid_price: (self.best_bid + self.best_ask) / 2.0,
+            ltp: self.ltp,
+            volume: self.volume,
+            misc: self.state.clone(),
+            universal_id: ids.next_id(),
+            data_center: constants::MACHINE_ID.to_string(),
+            process_id: ids.runtime_id().to_string(),
+        }
+    }
+}
+
+/// Itayose executions carry an empty side and are reported as `Sell` like before.
+fn to_side(side: &str) -> Side {
+    if side == "BUY" {
+        Side::Buy
+    } else {
+        Side::Sell
+    }
+}
+
+fn to_order_type(child_order_type: &str) -> OrderType {
+    match child_order_type {
+        "MARKET" => OrderType::Market,
+        "STOP_LIMIT" => OrderType::StopLimit,
+        _ => OrderType::Limit,
+    }
+}
+
+/// Message of the private `child_order_events` channel.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ChildOrderEvent {
+    pub product_code: String,
+    pub child_order_id: String,
+    pub child_order_acceptance_id: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub event_date: OffsetDateTime,
+    pub event_type: String,
+    pub child_order_type: Option<String>,
+    pub side: Option<String>,
+    pub price: Option<f64>,
+    pub size: Option<f64>,
+    pub reason: Option<String>,
+    pub exec_id: Option<u64>,
+    pub commission: Option<f64>,
+    pub sfd: Option<f64>,
+    pub outstanding_size: Option<f64>,
+}
+
+impl ChildOrderEvent {
+    /// `size` of an EXECUTION event is the executed size, the order size adds the outstanding size.
+    pub fn to_order(
+        &self,
+        sym: String,
+        received_timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> Order {
+        let mut size = self.size.unwrap_or(0.0);
+        if self.event_type == "EXECUTION" {
+            size += self.outstanding_size.unwrap_or(0.0);
+        }
+        let mut order = Order::new(
+            self.event_date,
+            sym.as_str(),
+            to_side(self.side.as_deref().unwrap_or("")),
+            Price::from_f64(self.price.unwrap_or(0.0)),
+            Qty::from_f64(size),
+            to_order_type(self.child_order_type.as_deref().unwrap_or("")),
+            "bitflyer",
+            "",
+            ids,
+        );
+        order.order_id = self.child_order_acceptance_id.clone();
+        order.market_created_timestamp = self.event_date;
+        order.received_timestamp = received_timestamp;
+        // an order reported by the venue has been submitted, by us or by another session.
+        let _ = order.transition(OrderStatus::Submitted, self.event_date);
+        order
+    }
+
+    /// Apply this event to the order it refers to.
+    pub fn update_order(
+        &self,
+        order: &mut Order,
+        received_timestamp: OffsetDateTime,
+    ) -> Result<(), OrderAnomaly> {
+        order.market_created_timestamp = self.event_date;
+        order.received_timestamp = received_timestamp;
+        match self.event_type.as_str() {
+            "ORDER" => order.transition(OrderStatus::Accepted, self.event_date),
+            "ORDER_FAILED" => {
+                order.misc = self.reason.clone().unwrap_or_default();
+                order.transition(OrderStatus::Rejected, self.event_date)
+            }
+            "CANCEL" => order.transition(OrderStatus::Canceled, self.event_date),
+            "EXPIRE" => order.transition(OrderStatus::Expired, self.event_date),
+            "EXECUTION" => {
+                order.fill(Qty::from_f64(self.size.unwrap_or(0.0)), self.event_date)?;
+                // the venue's outstanding size wins over our own accounting, without it the fill decides.
+                match self.outstanding_size {
+                    Some(outstanding_size) if outstanding_size <= 0.0 => {
+                        order.transition(OrderStatus::Filled, self.event_date)
+                    }
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fill record of an EXECUTION event.
+    pub fn to_execution(
+        &self,
+        sym: String,
+        received_timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> Option<execution::Execution> {
+        if self.event_type != "EXECUTION" {
+            return None;
+        }
+        let mut exec = execution::Execution::new(
+            sym.as_str(),
+            to_side(self.side.as_deref().unwrap_or("")),
+            Price::from_f64(self.price.unwrap_or(0.0)),
+            Qty::from_f64(self.size.unwrap_or(0.0)),
+            "bitflyer",
+            self.child_order_acceptance_id.clone(),
+            self.exec_id.map(|id| id.to_string()).unwrap_or_default(),
+            ids,
+        );
+        exec.market_created_timestamp = self.event_date;
+        exec.received_timestamp = received_timestamp;
+        exec.misc = format!(
+            "commission={};sfd={}",
+            self.commission.unwrap_or(0.0),
+            self.sfd.unwrap_or(0.0)
+        );
+        Some(exec)
+    }
+}
+
+/// Message of the private `parent_order_events` channel.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ParentOrderEvent {
+    pub product_code: String,
+    pub parent_order_id: String,
+    pub parent_order_acceptance_id: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub event_date: OffsetDateTime,
+    pub event_type: String,
+    pub parent_order_type: Option<String>,
+    pub child_order_type: Option<String>,
+    pub child_order_acceptance_id: Option<String>,
+    pub parameter_index: Option<u32>,
+    pub side: Option<String>,
+    pub price: Option<f64>,
+    pub size: Option<f64>,
+    pub reason: Option<String>,
+}
+
+impl ParentOrderEvent {
+    pub fn to_order(
+        &self,
+        sym: String,
+        received_timestamp: OffsetDateTime,
+        ids: &IdSource,
+    ) -> Order {
+        let mut order = Order::new(
+            self.event_date,
+            sym.as_str(),
+            to_side(self.side.as_deref().unwrap_or("")),
+            Price::from_f64(self.price.unwrap_or(0.0)),
+            Qty::from_f64(self.size.unwrap_or(0.0)),
+            to_order_type(self.child_order_type.as_deref().unwrap_or("")),
+            "bitflyer",
+            "",
+            ids,
+        );
+        order.order_id = self.parent_order_acceptance_id.clone();
+        order.market_created_timestamp = self.event_date;
+        order.received_timestamp = received_timestamp;
+        order.misc = self.parent_order_type.clone().unwrap_or_default();
+        let _ = order.transition(OrderStatus::Submitted, self.event_date);
+        order
+    }
+
+    /// Apply this event to the parent order it refers to.
+    /// TRIGGER only moves the parent order forward, the triggered child order arrives on `child_order_events`.
+    pub fn update_order(
+        &self,
+        order: &mut Order,
+        received_timestamp: OffsetDateTime,
+    ) -> Result<(), OrderAnomaly> {
+        order.market_created_timestamp = self.event_date;
+        order.received_timestamp = received_timestamp;
+        match self.event_type.as_str() {
+            "ORDER" => order.transition(OrderStatus::Accepted, self.event_date),
+            "ORDER_FAILED" => {
+                order.misc = self.reason.clone().unwrap_or_default();
+                order.transition(OrderStatus::Rejected, self.event_date)
+            }
+            "CANCEL" => order.transition(OrderStatus::Canceled, self.event_date),
+            "EXPIRE" => order.transition(OrderStatus::Expired, self.event_date),
+            "COMPLETE" => order.transition(OrderStatus::Filled, self.event_date),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn child_event(event_type: &str, size: f64, outstanding_size: Option<f64>) -> ChildOrderEvent {
+        ChildOrderEvent {
+            product_code: "FX_BTC_JPY".to_string(),
+            child_order_id: "JOR20261019-000000-000001".to_string(),
+            child_order_acceptance_id: "JRF20261019-000000-000001".to_string(),
+            event_date: OffsetDateTime::UNIX_EPOCH,
+            event_type: event_type.to_string(),
+            child_order_type: Some("LIMIT".to_string()),
+            side: Some("BUY".to_string()),
+            price: Some(5_000_000.0),
+            size: Some(size),
+            reason: None,
+            exec_id: Some(1),
+            commission: None,
+            sfd: None,
+            outstanding_size,
+        }
+    }
+
+    #[test]
+    fn execution_without_outstanding_size_is_a_partial_fill() {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        let mut order = child_event("ORDER", 1.0, None).to_order(
+            "FXBTCJPY".to_string(),
+            now,
+            &IdSource::default(),
+        );
+        child_event("ORDER", 1.0, None)
+            .update_order(&mut order, now)
+            .unwrap();
+
+        child_event("EXECUTION", 0.4, None)
+            .update_order(&mut order, now)
+            .unwrap();
+        assert_eq!(order.order_status, OrderStatus::PartiallyFilled);
+        assert_eq!(order.executed_amount, Qty::from_f64(0.4));
+
+        child_event("EXECUTION", 0.6, Some(0.0))
+            .update_order(&mut order, now)
+            .unwrap();
+        assert_eq!(order.order_status, OrderStatus::Filled);
+        assert_eq!(order.executed_amount, Qty::from_f64(1.0));
+    }
+
+    #[test]
+    fn zero_outstanding_size_fills_the_order() {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        let mut order = child_event("ORDER", 1.0, None).to_order(
+            "FXBTCJPY".to_string(),
+            now,
+            &IdSource::default(),
+        );
+        child_event("ORDER", 1.0, None)
+            .update_order(&mut order, now)
+            .unwrap();
+
+        // the venue reports the order done even though our fills add up to less
+        child_event("EXECUTION", 0.4, Some(0.0))
+            .update_order(&mut order, now)
+            .unwrap();
+        assert_eq!(order.order_status, OrderStatus::Filled);
+    }
+
+    #[test]
+    fn execution_first_seen_sizes_the_order_with_its_outstanding_size() {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        let order = child_event("EXECUTION", 0.4, Some(0.6)).to_order(
+            "FXBTCJPY".to_string(),
+            now,
+            &IdSource::default(),
+        );
+        assert_eq!(order.amount, Qty::from_f64(1.0));
+        assert_eq!(order.order_status, OrderStatus::Submitted);
+    }
+
+    #[test]
+    fn terminal_order_reports_the_anomaly() {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        let mut order = child_event("ORDER", 1.0, None).to_order(
+            "FXBTCJPY".to_string(),
+            now,
+            &IdSource::default(),
+        );
+        child_event("CANCEL", 1.0, None)
+            .update_order(&mut order, now)
+            .unwrap();
+        let anomaly = child_event("EXECUTION", 0.4, None)
+            .update_order(&mut order, now)
+            .unwrap_err();
+        assert_eq!(anomaly.from, OrderStatus::Canceled);
+        assert_eq!(order.order_status, OrderStatus::Canceled);
+    }
+
+    #[test]
+    fn itayose_execution_without_side_is_a_sell() {
+        let execution: Execution = serde_json::from_str(
+            r#"{"id":1,"side":"","price":5000000.0,"size":0.01,"exec_date":"2026-10-19T03:00:00.1234567Z","buy_child_order_acceptance_id":"JRF1","sell_child_order_acceptance_id":"JRF2"}"#,
+        )
+        .unwrap();
+        let trade = execution.to_market_trade(
+            "FXBTCJPY".to_string(),
+            "lightning".to_string(),
+            OffsetDateTime::UNIX_EPOCH,
+            &IdSource::default(),
+        );
+        assert_eq!(trade.side, Side::Sell);
+        assert_eq!(trade.amount, Qty::from_f64(0.01));
+    }
 }
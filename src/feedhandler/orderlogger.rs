@@ -0,0 +1,38 @@
+use std::path::Path;
+
+use crate::datamodels::enums::InternalData;
+use crate::datamodels::execution::Execution;
+use crate::datamodels::order::Order;
+use crate::feedhandler::textwriter::TextWriter;
+
+pub struct OrderLogger {
+    order_logger: TextWriter,
+    exec_logger: TextWriter,
+}
+
+impl OrderLogger {
+    pub fn new(name: &str) -> OrderLogger {
+        OrderLogger {
+            order_logger: TextWriter::new(Path::new("Order").join(name).to_str().unwrap()),
+            exec_logger: TextWriter::new(Path::new("Execution").join(name).to_str().unwrap()),
+        }
+    }
+
+    pub fn log_order(&mut self, order: &Order) {
+        self.order_logger
+            .write(serde_json::to_string(order).unwrap().as_str());
+    }
+
+    pub fn log_execution(&mut self, execution: &Execution) {
+        self.exec_logger
+            .write(serde_json::to_string(execution).unwrap().as_str());
+    }
+
+    pub fn callback(&mut self, data: InternalData) {
+        match data {
+            InternalData::Order(order) => self.log_order(&order),
+            InternalData::Execution(execution) => self.log_execution(&execution),
+            _ => {}
+        }
+    }
+}
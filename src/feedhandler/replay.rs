@@ -0,0 +1,96 @@
+use std::iter::Peekable;
+use time::OffsetDateTime;
+
+use crate::analytics::data_quality::UsableIntervals;
+use crate::datamodels::enums::MarketData;
+use crate::datamodels::marketdata::{MarketBook, MarketTrade, Rate, Ticker};
+use crate::feedhandler::tickreader::TickReader;
+
+type Stream = Peekable<Box<dyn Iterator<Item = MarketData>>>;
+
+/// Recorded `MarketBook`, `Rate`, `MarketTrade` and `Ticker` streams of some venues,
+/// merged in `market_created_timestamp` order.
+///
+/// Each stream is assumed ordered on its own, as written by `TickLogger`.
+pub struct MarketDataReplay {
+    streams: Vec<Stream>,
+    start: Option<OffsetDateTime>,
+    end: Option<OffsetDateTime>,
+    usable: Option<UsableIntervals>,
+}
+
+impl MarketDataReplay {
+    pub fn new(venues: &[&str]) -> Self {
+        let mut streams = vec![];
+        for venue in venues {
+            streams.push(Self::stream::<MarketBook>(
+                "MarketBook",
+                venue,
+                MarketData::MarketBook,
+            ));
+            streams.push(Self::stream::<Rate>("Rate", venue, MarketData::Rate));
+            streams.push(Self::stream::<MarketTrade>(
+                "MarketTrade",
+                venue,
+                MarketData::MarketTrade,
+            ));
+            streams.push(Self::stream::<Ticker>("Ticker", venue, MarketData::Ticker));
+        }
+        Self {
+            streams,
+            start: None,
+            end: None,
+            usable: None,
+        }
+    }
+
+    /// Only replay data within `[start, end)`.
+    pub fn between(mut self, start: Option<OffsetDateTime>, end: Option<OffsetDateTime>) -> Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    /// Only replay data within the usable intervals of its venue.
+    pub fn usable(mut self, usable: Option<UsableIntervals>) -> Self {
+        self.usable = usable;
+        self
+    }
+
+    fn stream<T: serde::de::DeserializeOwned + 'static>(
+        data_type: &str,
+        venue: &str,
+        wrap: fn(T) -> MarketData,
+    ) -> Stream {
+        let reader = TickReader::new(&format!("{}/{}", data_type, venue));
+        let records: Box<dyn Iterator<Item = MarketData>> = Box::new(reader.records().map(wrap));
+        records.peekable()
+    }
+}
+
+impl Iterator for MarketDataReplay {
+    type Item = MarketData;
+
+    fn next(&mut self) -> Option<MarketData> {
+        loop {
+            let earliest = self
+                .streams
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(i, stream)| Some((i, stream.peek()?.market_created_timestamp())))
+                .min_by_key(|(_, timestamp)| *timestamp)?;
+            let (i, timestamp) = earliest;
+            let data = self.streams[i].next();
+            if self.end.is_some_and(|end| timestamp >= end) {
+                return None;
+            }
+            let usable = match (&self.usable, &data) {
+                (Some(usable), Some(data)) => usable.contains(data.venue(), timestamp),
+                _ => true,
+            };
+            if usable && self.start.is_none_or(|start| timestamp >= start) {
+                return data;
+            }
+        }
+    }
+}
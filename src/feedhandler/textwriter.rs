@@ -2,18 +2,34 @@ use file_rotate::{
     compression::Compression, suffix::AppendTimestamp, suffix::FileLimit, ContentLimit, FileRotate,
     TimeFrequency,
 };
+use once_cell::sync::OnceCell;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use crate::monitor::metrics;
+
+static ROOT: OnceCell<PathBuf> = OnceCell::new();
+
+/// Set the root directory of every stream once at startup. Defaults to `tickerplant`.
+pub fn set_root<P: AsRef<Path>>(root: P) -> Result<(), PathBuf> {
+    ROOT.set(root.as_ref().to_path_buf())
+}
+
+pub fn root() -> &'static Path {
+    ROOT.get_or_init(|| PathBuf::from("tickerplant"))
+}
 
 pub struct TextWriter {
+    name: String,
     log: FileRotate<AppendTimestamp>,
 }
 
 impl TextWriter {
     pub fn new(path: &str) -> TextWriter {
         TextWriter {
+            name: path.to_string(),
             log: FileRotate::new(
-                Path::new("tickerplant").join(path),
+                root().join(path),
                 AppendTimestamp::default(FileLimit::MaxFiles(14)),
                 ContentLimit::Time(TimeFrequency::Daily),
                 Compression::OnRotate(10),
@@ -24,5 +40,9 @@ impl TextWriter {
 
     pub fn write(&mut self, message: &str) {
         writeln!(self.log, "{}", message).unwrap();
+        metrics::TICK_RECORDS.with_label_values(&[&self.name]).inc();
+        metrics::TICK_BYTES
+            .with_label_values(&[&self.name])
+            .inc_by(message.len() as u64 + 1);
     }
 }
@@ -9,6 +9,8 @@ pub struct TickLogger {
     book_logger: TextWriter,
     trad_logger: TextWriter,
     rate_logger: TextWriter,
+    tick_logger: TextWriter,
+    bar_logger: TextWriter,
     throttling_sec: BTreeMap<String, f64>,
 }
 
@@ -18,6 +20,8 @@ impl TickLogger {
             book_logger: TextWriter::new(Path::new("MarketBook").join(venue).to_str().unwrap()),
             trad_logger: TextWriter::new(Path::new("MarketTrade").join(venue).to_str().unwrap()),
             rate_logger: TextWriter::new(Path::new("Rate").join(venue).to_str().unwrap()),
+            tick_logger: TextWriter::new(Path::new("Ticker").join(venue).to_str().unwrap()),
+            bar_logger: TextWriter::new(Path::new("Bar").join(venue).to_str().unwrap()),
             throttling_sec: BTreeMap::new(),
         }
     }
@@ -47,6 +51,14 @@ impl TickLogger {
                 self.rate_logger
                     .write(serde_json::to_string(&data).unwrap().as_str());
             }
+            MarketData::Ticker(data) => {
+                self.tick_logger
+                    .write(serde_json::to_string(&data).unwrap().as_str());
+            }
+            MarketData::Bar(data) => {
+                self.bar_logger
+                    .write(serde_json::to_string(&data).unwrap().as_str());
+            }
         }
     }
 }
@@ -0,0 +1,96 @@
+use flate2::read::GzDecoder;
+use log::warn;
+use serde::de::DeserializeOwned;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::feedhandler::textwriter;
+
+/// Reads back the JSON lines written by `TextWriter`, including rotated and gzipped files.
+pub struct TickReader {
+    files: Vec<PathBuf>,
+}
+
+impl TickReader {
+    /// Every file of the `TextWriter` stream `name`, e.g. `MarketTrade/bitflyer`, under `textwriter::root`.
+    pub fn new(name: &str) -> TickReader {
+        let path = textwriter::root().join(name);
+        let mut files = vec![];
+        if let (Some(dir), Some(base)) = (path.parent(), path.file_name()) {
+            let prefix = format!("{}.", base.to_string_lossy());
+            if let Ok(entries) = std::fs::read_dir(dir) {
+                // rotated files carry a timestamp suffix, so they sort chronologically.
+                let mut rotated: Vec<PathBuf> = entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| {
+                        p.file_name()
+                            .is_some_and(|f| f.to_string_lossy().starts_with(&prefix))
+                    })
+                    .collect();
+                rotated.sort();
+                files.extend(rotated);
+            }
+        }
+        if path.is_file() {
+            files.push(path);
+        }
+        TickReader { files }
+    }
+
+    /// `input` as a file when it exists, otherwise as a stream name like `new`.
+    pub fn from_path_or_name(input: &str) -> TickReader {
+        if Path::new(input).is_file() {
+            TickReader::from_files(vec![PathBuf::from(input)])
+        } else {
+            TickReader::new(input)
+        }
+    }
+
+    /// Read the given files in order.
+    pub fn from_files(files: Vec<PathBuf>) -> TickReader {
+        TickReader { files }
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Records of every file. Unreadable files and malformed lines are logged and skipped.
+    pub fn records<T: DeserializeOwned>(&self) -> impl Iterator<Item = T> {
+        self.files
+            .clone()
+            .into_iter()
+            .filter_map(|path| match Self::open(&path) {
+                Ok(reader) => Some((path, reader)),
+                Err(e) => {
+                    warn!("Failed to open {} : {}", path.display(), e);
+                    None
+                }
+            })
+            .flat_map(|(path, reader)| {
+                reader
+                    .lines()
+                    .map_while(Result::ok)
+                    .filter(|line| !line.trim().is_empty())
+                    .filter_map(move |line| match serde_json::from_str(&line) {
+                        Ok(record) => Some(record),
+                        Err(e) => {
+                            warn!("Skip malformed line in {} : {}", path.display(), e);
+                            None
+                        }
+                    })
+            })
+    }
+
+    fn open(path: &Path) -> std::io::Result<Box<dyn BufRead>> {
+        let file = File::open(path)?;
+        let reader: Box<dyn Read> = if path.extension().is_some_and(|e| e == "gz") {
+            Box::new(GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        Ok(Box::new(BufReader::new(reader)))
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+use crate::datamodels::marketdata::MarketTrade;
+
+/// Venue trade ids of a sym possibly missed between two trades, both exclusive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissingRange {
+    pub sym: String,
+    pub after: u64,
+    pub before: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TradeCheck {
+    New,
+    /// `trade_id` already seen within the window.
+    Duplicate,
+    /// First new trade of the sym after a reconnect, trades of the range may have been missed.
+    AfterGap(MissingRange),
+}
+
+#[derive(Default)]
+struct SymTrades {
+    seen: HashSet<String>,
+    order: VecDeque<String>,
+    last_id: Option<u64>,
+    reconnected: bool,
+}
+
+/// Drops `MarketTrade`s resent by a venue, e.g. after a reconnect, by `trade_id` within a
+/// sliding window of the latest `window` ids per sym.
+///
+/// Numeric ids are assumed increasing, so the ids between the last trade before a reconnect
+/// and the first one after it are reported as a `MissingRange`. They need not be contiguous
+/// per sym, so the range only bounds what may have been missed.
+pub struct TradeDedup {
+    window: usize,
+    syms: BTreeMap<String, SymTrades>,
+}
+
+impl TradeDedup {
+    /// `window` 0 disables the deduplication.
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            syms: BTreeMap::new(),
+        }
+    }
+
+    /// The next new trade of every sym is checked for a gap.
+    pub fn on_reconnect(&mut self) {
+        for trades in self.syms.values_mut() {
+            trades.reconnected = true;
+        }
+    }
+
+    /// Check a trade and remember it when new.
+    pub fn check(&mut self, trade: &MarketTrade) -> TradeCheck {
+        if self.window == 0 || trade.trade_id.is_empty() {
+            return TradeCheck::New;
+        }
+        let trades = self.syms.entry(trade.sym.clone()).or_default();
+        if trades.seen.contains(&trade.trade_id) {
+            return TradeCheck::Duplicate;
+        }
+        trades.seen.insert(trade.trade_id.clone());
+        trades.order.push_back(trade.trade_id.clone());
+        if trades.order.len() > self.window {
+            let oldest = trades.order.pop_front().unwrap();
+            trades.seen.remove(&oldest);
+        }
+
+        let id = match trade.trade_id.parse::<u64>() {
+            Ok(id) => id,
+            Err(_) => return TradeCheck::New,
+        };
+        let last_id = trades.last_id;
+        trades.last_id = Some(last_id.map_or(id, |last_id| last_id.max(id)));
+        if !std::mem::take(&mut trades.reconnected) {
+            return TradeCheck::New;
+        }
+        match last_id {
+            Some(last_id) if id > last_id + 1 => TradeCheck::AfterGap(MissingRange {
+                sym: trade.sym.clone(),
+                after: last_id,
+                before: id,
+            }),
+            _ => TradeCheck::New,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodels::types::{Price, Qty, Side};
+    use time::OffsetDateTime;
+
+    fn trade(sym: &str, trade_id: &str) -> MarketTrade {
+        MarketTrade {
+            timestamp: OffsetDateTime::UNIX_EPOCH,
+            market_created_timestamp: OffsetDateTime::UNIX_EPOCH,
+            received_timestamp: OffsetDateTime::UNIX_EPOCH,
+            sym: sym.to_string(),
+            venue: "bitflyer".to_string(),
+            category: "lightning".to_string(),
+            side: Side::Buy,
+            price: Price::from_f64(100.0),
+            amount: Qty::from_f64(0.01),
+            trade_id: trade_id.to_string(),
+            order_ids: "".to_string(),
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        }
+    }
+
+    fn check(dedup: &mut TradeDedup, sym: &str, trade_id: &str) -> TradeCheck {
+        dedup.check(&trade(sym, trade_id))
+    }
+
+    fn gap(sym: &str, after: u64, before: u64) -> TradeCheck {
+        TradeCheck::AfterGap(MissingRange {
+            sym: sym.to_string(),
+            after,
+            before,
+        })
+    }
+
+    #[test]
+    fn duplicates_are_dropped_within_the_window_per_sym() {
+        let mut dedup = TradeDedup::new(3);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "1"), TradeCheck::New);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "2"), TradeCheck::New);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "1"), TradeCheck::Duplicate);
+        assert_eq!(check(&mut dedup, "BTCJPY", "1"), TradeCheck::New);
+        // empty ids cannot be deduplicated
+        assert_eq!(check(&mut dedup, "FXBTCJPY", ""), TradeCheck::New);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", ""), TradeCheck::New);
+    }
+
+    #[test]
+    fn ids_are_evicted_past_the_window() {
+        let mut dedup = TradeDedup::new(2);
+        for id in ["1", "2", "3"] {
+            assert_eq!(check(&mut dedup, "FXBTCJPY", id), TradeCheck::New);
+        }
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "2"), TradeCheck::Duplicate);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "3"), TradeCheck::Duplicate);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "1"), TradeCheck::New);
+    }
+
+    #[test]
+    fn window_zero_disables_deduplication() {
+        let mut dedup = TradeDedup::new(0);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "1"), TradeCheck::New);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "1"), TradeCheck::New);
+        dedup.on_reconnect();
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "5"), TradeCheck::New);
+    }
+
+    #[test]
+    fn non_numeric_ids_are_deduplicated_without_gaps() {
+        let mut dedup = TradeDedup::new(10);
+        assert_eq!(check(&mut dedup, "BTCUSDT", "a-1"), TradeCheck::New);
+        dedup.on_reconnect();
+        assert_eq!(check(&mut dedup, "BTCUSDT", "a-1"), TradeCheck::Duplicate);
+        assert_eq!(check(&mut dedup, "BTCUSDT", "z-9"), TradeCheck::New);
+    }
+
+    #[test]
+    fn gap_is_reported_on_the_first_new_trade_after_reconnect_only() {
+        let mut dedup = TradeDedup::new(10);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "10"), TradeCheck::New);
+        // increasing ids without reconnect are never gaps
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "20"), TradeCheck::New);
+        assert_eq!(check(&mut dedup, "BTCJPY", "5"), TradeCheck::New);
+
+        dedup.on_reconnect();
+        // resent trades keep the check pending
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "20"), TradeCheck::Duplicate);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "25"), gap("FXBTCJPY", 20, 25));
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "30"), TradeCheck::New);
+        // the next id is no gap, and clears the check of the sym
+        assert_eq!(check(&mut dedup, "BTCJPY", "6"), TradeCheck::New);
+        assert_eq!(check(&mut dedup, "BTCJPY", "9"), TradeCheck::New);
+
+        // a sym first seen after the reconnect has nothing to compare with
+        dedup.on_reconnect();
+        assert_eq!(check(&mut dedup, "ETHJPY", "100"), TradeCheck::New);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "28"), TradeCheck::New);
+        assert_eq!(check(&mut dedup, "FXBTCJPY", "40"), TradeCheck::New);
+    }
+}
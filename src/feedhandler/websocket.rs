@@ -0,0 +1,227 @@
+use log::{error, info, warn};
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::Message;
+
+use crate::datamodels::enums::MarketData;
+use crate::monitor::feed_health::FeedHealth;
+use crate::refdata::calendar::MaintenanceCalendar;
+
+type Shared<T> = Arc<Mutex<T>>;
+type Callback = Box<dyn FnMut(MarketData) + Send>;
+
+const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(3);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(30);
+/// Silence after which the connection is probed with a ping.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Venue specific part of a `WebSocketFeed`: subscription and message mapping.
+pub trait VenueAdapter: Send + 'static {
+    fn venue(&self) -> &str;
+
+    /// Messages sent after every (re)connection to subscribe `channels`.
+    fn subscribe_messages(&self, channels: &[String]) -> Vec<String>;
+
+    /// Map one text message to market data.
+    fn on_message(&mut self, message: &str, received_timestamp: OffsetDateTime) -> Vec<MarketData>;
+
+    /// Drop any per-connection state, e.g. books waiting for a snapshot.
+    fn reset(&mut self);
+
+    /// Channel reported to `FeedHealth` for a subscribed channel.
+    fn health_channel(&self, channel: &str) -> String {
+        channel.to_string()
+    }
+
+    /// Messages, parse errors and book errors are reported per channel by the adapter.
+    fn set_health(&mut self, _health: FeedHealth) {}
+}
+
+/// Plain JSON WebSocket feed handler. Reconnects with backoff and hands every text message
+/// to its `VenueAdapter`.
+///
+/// A connection silent for `read_timeout` is pinged, and dropped when the next `read_timeout`
+/// passes without any message, so a half-open connection is reconnected.
+pub struct WebSocketFeed<A: VenueAdapter> {
+    pub callbacks: Shared<Vec<Callback>>,
+    url: String,
+    adapter: A,
+    calendar: Arc<MaintenanceCalendar>,
+    health: FeedHealth,
+    read_timeout: Duration,
+}
+
+impl<A: VenueAdapter> WebSocketFeed<A> {
+    pub fn new(url: &str, adapter: A) -> Self {
+        Self {
+            callbacks: Arc::new(Mutex::new(vec![])),
+            url: url.to_string(),
+            adapter,
+            calendar: Arc::new(MaintenanceCalendar::new()),
+            health: FeedHealth::default(),
+            read_timeout: READ_TIMEOUT,
+        }
+    }
+
+    pub fn set_read_timeout(&mut self, read_timeout: Duration) {
+        self.read_timeout = read_timeout;
+    }
+
+    pub fn set_callback(&mut self, callback: Callback) {
+        self.callbacks.lock().unwrap().push(callback);
+    }
+
+    /// Disconnections within a maintenance window are expected and only logged as info.
+    pub fn set_calendar(&mut self, calendar: Arc<MaintenanceCalendar>) {
+        self.calendar = calendar;
+    }
+
+    pub fn set_health(&mut self, health: FeedHealth) {
+        self.adapter.set_health(health.clone());
+        self.health = health;
+    }
+
+    /// Run the feed on its own thread until the process exits.
+    pub fn connect(mut self, channels: Vec<String>) -> JoinHandle<()> {
+        let health_channels: Vec<String> = channels
+            .iter()
+            .map(|channel| self.adapter.health_channel(channel))
+            .collect();
+        self.health
+            .subscribe(self.adapter.venue(), &health_channels);
+        std::thread::spawn(move || {
+            let mut delay = RECONNECT_DELAY_MIN;
+            let mut connected = false;
+            loop {
+                match self.run(&channels, &mut connected) {
+                    Ok(()) => {
+                        warn!("Close socket to {}", self.adapter.venue());
+                        delay = RECONNECT_DELAY_MIN;
+                    }
+                    Err(e) => {
+                        match self
+                            .calendar
+                            .maintenance_end(self.adapter.venue(), OffsetDateTime::now_utc())
+                        {
+                            Some(until) => info!(
+                                "Error on socket to {} within maintenance until {} : {}",
+                                self.adapter.venue(),
+                                until,
+                                e
+                            ),
+                            None => error!("Error on socket to {} : {}", self.adapter.venue(), e),
+                        }
+                        delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+                    }
+                }
+                self.adapter.reset();
+                std::thread::sleep(delay);
+            }
+        })
+    }
+
+    /// One connection, until the venue closes it or an error occurs.
+    fn run(
+        &mut self,
+        channels: &[String],
+        connected: &mut bool,
+    ) -> Result<(), Box<tungstenite::Error>> {
+        let (mut socket, _) = tungstenite::connect(self.url.as_str())?;
+        let stream: Option<&TcpStream> = match socket.get_ref() {
+            MaybeTlsStream::Plain(stream) => Some(stream),
+            MaybeTlsStream::NativeTls(stream) => Some(stream.get_ref()),
+            _ => None,
+        };
+        match stream {
+            Some(stream) => stream
+                .set_read_timeout(Some(self.read_timeout))
+                .map_err(tungstenite::Error::Io)?,
+            None => warn!("No read timeout on socket to {}", self.adapter.venue()),
+        }
+        info!("Open socket to {}", self.adapter.venue());
+        if *connected {
+            self.health.on_reconnect(self.adapter.venue());
+        }
+        *connected = true;
+        for message in self.adapter.subscribe_messages(channels) {
+            info!("Send subscribe message: {}", message);
+            socket.write_message(Message::Text(message))?;
+        }
+
+        let mut pinged = false;
+        loop {
+            let message = match socket.read_message() {
+                Ok(message) => message,
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    if pinged {
+                        return Err(Box::new(tungstenite::Error::Io(std::io::Error::new(
+                            ErrorKind::TimedOut,
+                            format!("no message within {:?} of a ping", self.read_timeout),
+                        ))));
+                    }
+                    socket.write_message(Message::Ping(vec![]))?;
+                    pinged = true;
+                    continue;
+                }
+                Err(e) => return Err(Box::new(e)),
+            };
+            pinged = false;
+            let message = match message {
+                Message::Text(message) => message,
+                Message::Binary(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                Message::Close(_) => return Ok(()),
+                // pongs are queued by tungstenite and flushed on the next read.
+                _ => continue,
+            };
+            let received_timestamp = OffsetDateTime::now_utc();
+            for data in self.adapter.on_message(&message, received_timestamp) {
+                for callback in self.callbacks.lock().unwrap().iter_mut() {
+                    callback(data.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feedhandler::binance::binance_websocket::BinanceWebSocket;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    #[test]
+    fn silent_connection_is_dropped_after_an_unanswered_ping() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            socket.read_message().unwrap();
+            // half-open: never read again, so the ping is not answered
+            std::thread::sleep(Duration::from_secs(1));
+        });
+
+        let mut feed = WebSocketFeed::new(&url, BinanceWebSocket::new());
+        feed.set_read_timeout(Duration::from_millis(200));
+        let start = Instant::now();
+        let mut connected = false;
+        let error = feed
+            .run(&["btcusdt@trade".to_string()], &mut connected)
+            .unwrap_err();
+        assert!(connected);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        match *error {
+            tungstenite::Error::Io(e) => assert_eq!(e.kind(), ErrorKind::TimedOut),
+            e => panic!("expected a timeout, got {}", e),
+        }
+        server.join().unwrap();
+    }
+}
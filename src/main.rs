@@ -1,51 +1,81 @@
+pub mod analytics;
 pub mod api_client;
+pub mod cli;
+pub mod config;
 pub mod constants;
 pub mod datamodels;
 pub mod feedhandler;
+pub mod monitor;
+pub mod oms;
+pub mod refdata;
+pub mod risk;
+pub mod strategy;
 
-// use chrono::Utc;
-use flexi_logger::Duplicate;
-use flexi_logger::FileSpec;
+use clap::Parser;
 use flexi_logger::Logger;
 use log::info;
-// use uuid::Uuid;
-
-// use crate::api_client::execution_client::ExecutionClient;
-use crate::feedhandler::bitflyer::bitflyer_socketio::BitFlyerSocketIo;
-use crate::feedhandler::ticklogger;
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    info!("Start");
-
-    // set logger
-    Logger::try_with_str("info, my::critical::module=trace")?
-        .format(flexi_logger::detailed_format)
-        .log_to_file(FileSpec::default().directory("testlogs"))
-        .duplicate_to_stdout(Duplicate::All)
-        .append()
-        .rotate(
-            flexi_logger::Criterion::Age(flexi_logger::Age::Day),
-            flexi_logger::Naming::Timestamps,
-            flexi_logger::Cleanup::KeepLogFiles(3),
-        )
-        .start()?;
-
-    // set logic
-    let mut t_logger = ticklogger::TickLogger::new("bitflyer");
-
-    // set feedhandlers
-    let mut bfsocket = BitFlyerSocketIo::new();
-    bfsocket.set_callback(Box::new(move |data| t_logger.callback(data)));
-    bfsocket.connect(vec![
-        "lightning_executions_BTC_JPY".to_string(),
-        "lightning_board_snapshot_BTC_JPY".to_string(),
-        "lightning_board_BTC_JPY".to_string(),
-        // "lightning_ticker_FX_BTC_JPY".to_string(),
-    ]);
-
-    // start loop
-    loop {
-        std::thread::sleep(std::time::Duration::from_secs(10));
-        info!("Hearbeat");
+
+use crate::cli::args::{Cli, Command};
+use crate::cli::run::ClientMode;
+use crate::cli::{convert, inspect, latency, quality, replay, run};
+use crate::config::app_config::AppConfig;
+use crate::feedhandler::textwriter;
+
+fn main() {
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Command::Convert(_) | Command::Inspect(_) | Command::BookAt(_) | Command::Latency(_) => {
+            offline(&cli)
+        }
+        _ => configured(&cli),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Commands reading recorded ticks only, they need the tickerplant root and no feeds.
+fn offline(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let tickerplant = AppConfig::tickerplant_from_file_with_overrides(&cli.config, &cli.overrides)?;
+    textwriter::set_root(&tickerplant.root).unwrap();
+    // offline commands only print warnings to stderr
+    Logger::try_with_str("warn")?.start()?;
+
+    match &cli.command {
+        Command::Convert(args) => convert::convert(args).map(|count| {
+            println!("{} records written to {}", count, args.output.display());
+        }),
+        Command::Inspect(args) => inspect::inspect(args),
+        Command::BookAt(args) => inspect::book_at(args),
+        Command::Latency(args) => latency::latency(args),
+        _ => unreachable!("{:?} needs the config", cli.command),
+    }
+}
+
+/// Commands loading and validating the whole config.
+fn configured(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let config = AppConfig::from_file_with_overrides(&cli.config, &cli.overrides)?;
+    textwriter::set_root(&config.tickerplant.root).unwrap();
+
+    // set logger, replay and quality only print warnings to stderr
+    match cli.command {
+        Command::Record | Command::Paper | Command::Live | Command::Backtest(_) => {
+            run::start_logger(&config)?;
+            info!("Start {:?} with {}", cli.command, cli.config.display());
+        }
+        _ => {
+            Logger::try_with_str("warn")?.start()?;
+        }
+    }
+
+    match &cli.command {
+        Command::Record => run::run(&config, None).map_err(Into::into),
+        Command::Paper => run::run(&config, Some(ClientMode::Paper)).map_err(Into::into),
+        Command::Live => run::run(&config, Some(ClientMode::Live)).map_err(Into::into),
+        Command::Replay(args) => replay::replay(&config, args),
+        Command::Backtest(args) => replay::backtest(&config, args),
+        Command::Quality(args) => quality::quality(&config, args),
+        _ => unreachable!("{:?} does not load the config", cli.command),
     }
 }
@@ -0,0 +1,3 @@
+pub mod alert;
+pub mod feed_health;
+pub mod metrics;
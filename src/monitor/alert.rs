@@ -0,0 +1,143 @@
+use log::{error, info, warn};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertLevel {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Alert {
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    pub level: AlertLevel,
+    pub venue: String,
+    pub channel: String,
+    pub message: String,
+}
+
+/// Destination of the alerts raised by `FeedHealth::check`.
+pub trait AlertSink: Send {
+    fn send(&mut self, alert: &Alert);
+}
+
+/// One `[[health.sink]]` of the app config.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AlertSinkConfig {
+    Log,
+    /// Alerts appended as JSON lines.
+    File {
+        path: PathBuf,
+    },
+    /// Alerts POSTed as JSON, e.g. to a local relay.
+    Webhook {
+        url: String,
+        #[serde(default = "default_webhook_timeout_ms")]
+        timeout_ms: u64,
+    },
+}
+
+fn default_webhook_timeout_ms() -> u64 {
+    2000
+}
+
+impl AlertSinkConfig {
+    pub fn build(&self) -> std::io::Result<Box<dyn AlertSink>> {
+        Ok(match self {
+            AlertSinkConfig::Log => Box::new(LogSink),
+            AlertSinkConfig::File { path } => Box::new(FileSink::new(path.clone())?),
+            AlertSinkConfig::Webhook { url, timeout_ms } => {
+                Box::new(WebhookSink::new(url, Duration::from_millis(*timeout_ms)))
+            }
+        })
+    }
+}
+
+/// Alerts as log records of the matching level.
+pub struct LogSink;
+
+impl AlertSink for LogSink {
+    fn send(&mut self, alert: &Alert) {
+        match alert.level {
+            AlertLevel::Info => info!(
+                "Alert {}/{} : {}",
+                alert.venue, alert.channel, alert.message
+            ),
+            AlertLevel::Warning => {
+                warn!(
+                    "Alert {}/{} : {}",
+                    alert.venue, alert.channel, alert.message
+                )
+            }
+            AlertLevel::Critical => {
+                error!(
+                    "Alert {}/{} : {}",
+                    alert.venue, alert.channel, alert.message
+                )
+            }
+        }
+    }
+}
+
+pub struct FileSink {
+    path: PathBuf,
+    file: File,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf) -> std::io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, file })
+    }
+}
+
+impl AlertSink for FileSink {
+    fn send(&mut self, alert: &Alert) {
+        let line = serde_json::to_string(alert).unwrap();
+        if let Err(e) = writeln!(self.file, "{}", line) {
+            error!("Failed to write alert to {} : {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Failed deliveries are logged and dropped, a down endpoint never blocks the monitor for long.
+pub struct WebhookSink {
+    url: String,
+    client: Client,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str, timeout: Duration) -> Self {
+        Self {
+            url: url.to_string(),
+            client: Client::builder().timeout(timeout).build().unwrap(),
+        }
+    }
+}
+
+impl AlertSink for WebhookSink {
+    fn send(&mut self, alert: &Alert) {
+        let result = self
+            .client
+            .post(&self.url)
+            .json(alert)
+            .send()
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            error!("Failed to post alert to {} : {}", self.url, e);
+        }
+    }
+}
@@ -0,0 +1,494 @@
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+use crate::datamodels::marketdata::MarketBook;
+use crate::monitor::alert::{Alert, AlertLevel, AlertSink, AlertSinkConfig};
+use crate::monitor::metrics;
+use crate::refdata::calendar::MaintenanceCalendar;
+
+/// Channel of events that cannot be attributed to a subscription, e.g. unparsable messages.
+pub const CONNECTION: &str = "*";
+
+/// Staleness thresholds and alert sinks, `[health]` of the app config.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Seconds between checks, also the window of the reported message rates.
+    #[serde(default = "default_check_interval_secs")]
+    pub check_interval_secs: u64,
+    /// A subscribed channel without message for this long is stale.
+    #[serde(default = "default_stale_secs")]
+    pub stale_secs: f64,
+    /// Overrides of `stale_secs` keyed by `<venue>/<channel>` or `<venue>`,
+    /// e.g. for trade channels of quiet markets.
+    #[serde(default)]
+    pub stale_secs_by_channel: BTreeMap<String, f64>,
+    #[serde(rename = "sink", default = "default_sinks")]
+    pub sinks: Vec<AlertSinkConfig>,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            check_interval_secs: default_check_interval_secs(),
+            stale_secs: default_stale_secs(),
+            stale_secs_by_channel: BTreeMap::new(),
+            sinks: default_sinks(),
+        }
+    }
+}
+
+fn default_check_interval_secs() -> u64 {
+    10
+}
+
+fn default_stale_secs() -> f64 {
+    30.0
+}
+
+fn default_sinks() -> Vec<AlertSinkConfig> {
+    vec![AlertSinkConfig::Log]
+}
+
+impl HealthConfig {
+    pub fn stale_secs(&self, venue: &str, channel: &str) -> f64 {
+        let by_channel = &self.stale_secs_by_channel;
+        by_channel
+            .get(&format!("{}/{}", venue, channel))
+            .or_else(|| by_channel.get(venue))
+            .copied()
+            .unwrap_or(self.stale_secs)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Counters {
+    messages: u64,
+    reconnects: u64,
+    parse_errors: u64,
+    book_errors: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ChannelHealth {
+    pub venue: String,
+    pub channel: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_message: Option<OffsetDateTime>,
+    pub messages: u64,
+    /// Messages per second over the last check interval.
+    pub message_rate: f64,
+    pub reconnects: u64,
+    pub parse_errors: u64,
+    pub book_errors: u64,
+    pub stale: bool,
+    /// Only subscribed channels can go stale.
+    pub subscribed: bool,
+    /// Subscription time, staleness is measured from it until the first message.
+    #[serde(with = "time::serde::rfc3339")]
+    pub since: OffsetDateTime,
+    #[serde(skip)]
+    checked: Counters,
+    #[serde(skip)]
+    checked_at: Option<OffsetDateTime>,
+}
+
+impl ChannelHealth {
+    fn new(venue: &str, channel: &str, since: OffsetDateTime) -> Self {
+        Self {
+            venue: venue.to_string(),
+            channel: channel.to_string(),
+            last_message: None,
+            messages: 0,
+            message_rate: 0.0,
+            reconnects: 0,
+            parse_errors: 0,
+            book_errors: 0,
+            stale: false,
+            subscribed: false,
+            since,
+            checked: Counters::default(),
+            checked_at: None,
+        }
+    }
+
+    fn counters(&self) -> Counters {
+        Counters {
+            messages: self.messages,
+            reconnects: self.reconnects,
+            parse_errors: self.parse_errors,
+            book_errors: self.book_errors,
+        }
+    }
+
+    /// Seconds since the last message, or since the subscription.
+    pub fn silence(&self, now: OffsetDateTime) -> f64 {
+        (now - self.last_message.unwrap_or(self.since)).as_seconds_f64()
+    }
+}
+
+/// Per (venue, channel) message counters fed by the feed handlers and checked against
+/// `HealthConfig` thresholds. Clones share the same state, so one handle is given to every feed.
+#[derive(Clone)]
+pub struct FeedHealth {
+    config: Arc<HealthConfig>,
+    calendar: Arc<MaintenanceCalendar>,
+    channels: Arc<Mutex<BTreeMap<(String, String), ChannelHealth>>>,
+}
+
+impl Default for FeedHealth {
+    fn default() -> Self {
+        Self::new(
+            HealthConfig::default(),
+            Arc::new(MaintenanceCalendar::new()),
+        )
+    }
+}
+
+impl FeedHealth {
+    /// Channels of a venue within a maintenance window never go stale.
+    pub fn new(config: HealthConfig, calendar: Arc<MaintenanceCalendar>) -> Self {
+        Self {
+            config: Arc::new(config),
+            calendar,
+            channels: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn config(&self) -> &HealthConfig {
+        &self.config
+    }
+
+    fn update<F: FnOnce(&mut ChannelHealth)>(&self, venue: &str, channel: &str, f: F) {
+        let mut channels = self.channels.lock().unwrap();
+        let health = channels
+            .entry((venue.to_string(), channel.to_string()))
+            .or_insert_with(|| ChannelHealth::new(venue, channel, OffsetDateTime::now_utc()));
+        f(health);
+    }
+
+    /// Track `channels` of `venue` for staleness from now on.
+    pub fn subscribe(&self, venue: &str, channels: &[String]) {
+        let now = OffsetDateTime::now_utc();
+        for channel in channels {
+            self.update(venue, channel, |health| {
+                health.subscribed = true;
+                health.since = now;
+            });
+        }
+    }
+
+    pub fn on_message(&self, venue: &str, channel: &str, received_timestamp: OffsetDateTime) {
+        metrics::MESSAGES.with_label_values(&[venue, channel]).inc();
+        self.update(venue, channel, |health| {
+            health.messages += 1;
+            health.last_message = Some(received_timestamp);
+        });
+    }
+
+    /// A new connection after the first one, counted on every subscribed channel of the venue.
+    pub fn on_reconnect(&self, venue: &str) {
+        let mut channels = self.channels.lock().unwrap();
+        for health in channels.values_mut() {
+            if health.venue == venue && health.subscribed {
+                health.reconnects += 1;
+            }
+        }
+    }
+
+    /// `channel` is `CONNECTION` when the message cannot be attributed.
+    pub fn on_parse_error(&self, venue: &str, channel: &str) {
+        self.update(venue, channel, |health| health.parse_errors += 1);
+    }
+
+    /// A book that cannot be trusted anymore, e.g. after a sequence gap.
+    pub fn on_book_error(&self, venue: &str, channel: &str) {
+        self.update(venue, channel, |health| health.book_errors += 1);
+    }
+
+    /// Record a book error if `book` is crossed, returns whether it is sound.
+    pub fn check_book(&self, venue: &str, channel: &str, book: &MarketBook) -> bool {
+        let crossed = matches!(
+            (book.best_bid(), book.best_ask()),
+            (Some((bid, _)), Some((ask, _))) if bid >= ask
+        );
+        if crossed {
+            self.on_book_error(venue, channel);
+        }
+        !crossed
+    }
+
+    pub fn snapshot(&self) -> Vec<ChannelHealth> {
+        self.channels.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Update message rates and staleness, returns the alerts raised since the last check.
+    pub fn check(&self, now: OffsetDateTime) -> Vec<Alert> {
+        let mut alerts = vec![];
+        let mut channels = self.channels.lock().unwrap();
+        for health in channels.values_mut() {
+            let alert = |level, message: String| Alert {
+                timestamp: now,
+                level,
+                venue: health.venue.clone(),
+                channel: health.channel.clone(),
+                message,
+            };
+            let counters = health.counters();
+            let checked = health.checked;
+
+            if health.subscribed {
+                let threshold = self.config.stale_secs(&health.venue, &health.channel);
+                let silence = health.silence(now);
+                let maintenance = self.calendar.is_maintenance(&health.venue, now);
+                if silence > threshold && !health.stale && !maintenance {
+                    alerts.push(alert(
+                        AlertLevel::Warning,
+                        format!("stale, no message for {:.0}s", silence),
+                    ));
+                    health.stale = true;
+                } else if silence <= threshold && health.stale {
+                    alerts.push(alert(AlertLevel::Info, "recovered".to_string()));
+                    health.stale = false;
+                }
+            }
+            if counters.reconnects > checked.reconnects {
+                alerts.push(alert(
+                    AlertLevel::Warning,
+                    format!("{} reconnects", counters.reconnects - checked.reconnects),
+                ));
+            }
+            if counters.parse_errors > checked.parse_errors {
+                alerts.push(alert(
+                    AlertLevel::Warning,
+                    format!(
+                        "{} parse errors",
+                        counters.parse_errors - checked.parse_errors
+                    ),
+                ));
+            }
+            if counters.book_errors > checked.book_errors {
+                alerts.push(alert(
+                    AlertLevel::Critical,
+                    format!(
+                        "{} book integrity failures",
+                        counters.book_errors - checked.book_errors
+                    ),
+                ));
+            }
+
+            if let Some(checked_at) = health.checked_at {
+                let elapsed = (now - checked_at).as_seconds_f64();
+                if elapsed > 0.0 {
+                    health.message_rate = (counters.messages - checked.messages) as f64 / elapsed;
+                }
+            }
+            health.checked = counters;
+            health.checked_at = Some(now);
+        }
+        alerts
+    }
+
+    /// Check every `check_interval_secs` forever, sending alerts to `sinks` and logging
+    /// the message rates.
+    pub fn monitor(&self, mut sinks: Vec<Box<dyn AlertSink>>) -> ! {
+        let interval = std::time::Duration::from_secs(self.config.check_interval_secs);
+        loop {
+            std::thread::sleep(interval);
+            let now = OffsetDateTime::now_utc();
+            for alert in self.check(now) {
+                for sink in sinks.iter_mut() {
+                    sink.send(&alert);
+                }
+            }
+
+            let snapshot = self.snapshot();
+            let stale = snapshot.iter().filter(|health| health.stale).count();
+            let rates = snapshot
+                .iter()
+                .filter(|health| health.subscribed)
+                .map(|health| {
+                    format!(
+                        "{}/{} {:.1}/s",
+                        health.venue, health.channel, health.message_rate
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            if stale > 0 {
+                error!("Heartbeat, {} stale channels : {}", stale, rates);
+            } else {
+                info!("Heartbeat : {}", rates);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::alert::FileSink;
+    use time::Duration;
+
+    const CHANNEL: &str = "lightning_executions_FX_BTC_JPY";
+
+    fn at(secs: i64) -> OffsetDateTime {
+        OffsetDateTime::UNIX_EPOCH + Duration::seconds(secs)
+    }
+
+    /// One subscribed bitflyer channel with a message at `at(0)`.
+    fn health(config: HealthConfig, calendar: &str) -> FeedHealth {
+        let calendar = MaintenanceCalendar::from_toml(calendar).unwrap();
+        let health = FeedHealth::new(config, Arc::new(calendar));
+        health.subscribe("bitflyer", &[CHANNEL.to_string()]);
+        health.on_message("bitflyer", CHANNEL, at(0));
+        health.check(at(0));
+        health
+    }
+
+    fn messages(alerts: &[Alert]) -> Vec<(AlertLevel, &str)> {
+        alerts
+            .iter()
+            .map(|alert| (alert.level, alert.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn stale_and_recovered_are_raised_once() {
+        let health = health(HealthConfig::default(), "");
+        assert!(health.check(at(30)).is_empty());
+        let alerts = health.check(at(31));
+        assert_eq!(
+            messages(&alerts),
+            vec![(AlertLevel::Warning, "stale, no message for 31s")]
+        );
+        assert_eq!(alerts[0].venue, "bitflyer");
+        assert_eq!(alerts[0].channel, CHANNEL);
+        assert!(health.snapshot()[0].stale);
+        assert!(health.check(at(60)).is_empty());
+
+        health.on_message("bitflyer", CHANNEL, at(61));
+        assert_eq!(
+            messages(&health.check(at(62))),
+            vec![(AlertLevel::Info, "recovered")]
+        );
+        assert!(!health.snapshot()[0].stale);
+        assert!(health.check(at(63)).is_empty());
+    }
+
+    #[test]
+    fn unsubscribed_channels_never_go_stale() {
+        let health = health(HealthConfig::default(), "");
+        health.on_message("bitflyer", "lightning_board_FX_BTC_JPY", at(0));
+        let alerts = health.check(at(100));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].channel, CHANNEL);
+    }
+
+    #[test]
+    fn maintenance_suppresses_staleness() {
+        let calendar = r#"
+            [[venue]]
+            venue = "bitflyer"
+            daily = [{ start = "00:00", end = "00:10" }]
+        "#;
+        let health = health(HealthConfig::default(), calendar);
+        assert!(health.check(at(31)).is_empty());
+        assert!(health.check(at(599)).is_empty());
+        assert_eq!(
+            messages(&health.check(at(600))),
+            vec![(AlertLevel::Warning, "stale, no message for 600s")]
+        );
+    }
+
+    #[test]
+    fn thresholds_are_overridden_by_channel_then_venue() {
+        let mut config = HealthConfig::default();
+        config
+            .stale_secs_by_channel
+            .insert(format!("bitflyer/{}", CHANNEL), 120.0);
+        config
+            .stale_secs_by_channel
+            .insert("bitflyer".to_string(), 5.0);
+        assert_eq!(config.stale_secs("bitflyer", CHANNEL), 120.0);
+        assert_eq!(
+            config.stale_secs("bitflyer", "lightning_board_FX_BTC_JPY"),
+            5.0
+        );
+        assert_eq!(config.stale_secs("binance", "btcusdt@trade"), 30.0);
+
+        let health = health(config, "");
+        assert!(health.check(at(120)).is_empty());
+        assert_eq!(health.check(at(121)).len(), 1);
+    }
+
+    #[test]
+    fn error_counters_are_alerted_as_deltas() {
+        let health = health(HealthConfig::default(), "");
+        health.on_reconnect("bitflyer");
+        health.on_reconnect("bitflyer");
+        health.on_reconnect("binance");
+        health.on_parse_error("bitflyer", CHANNEL);
+        health.on_book_error("bitflyer", CHANNEL);
+        assert_eq!(
+            messages(&health.check(at(1))),
+            vec![
+                (AlertLevel::Warning, "2 reconnects"),
+                (AlertLevel::Warning, "1 parse errors"),
+                (AlertLevel::Critical, "1 book integrity failures"),
+            ]
+        );
+        assert!(health.check(at(2)).is_empty());
+
+        health.on_parse_error("bitflyer", CHANNEL);
+        health.on_parse_error("bitflyer", CONNECTION);
+        let alerts = health.check(at(3));
+        let channels: Vec<&str> = alerts.iter().map(|a| a.channel.as_str()).collect();
+        assert_eq!(channels, vec![CONNECTION, CHANNEL]);
+        assert!(alerts.iter().all(|a| a.message == "1 parse errors"));
+        let snapshot = health.snapshot();
+        assert_eq!(snapshot[1].reconnects, 2);
+        assert_eq!(snapshot[1].parse_errors, 2);
+    }
+
+    #[test]
+    fn message_rate_covers_the_last_interval() {
+        let health = health(HealthConfig::default(), "");
+        for i in 0..20 {
+            health.on_message("bitflyer", CHANNEL, at(i / 2));
+        }
+        health.check(at(10));
+        assert_eq!(health.snapshot()[0].message_rate, 2.0);
+    }
+
+    #[test]
+    fn file_sink_appends_json_lines() {
+        let path = std::env::temp_dir()
+            .join(format!("ralpha-health-{}", std::process::id()))
+            .join("alerts.jsonl");
+        let _ = std::fs::remove_file(&path);
+        let health = health(HealthConfig::default(), "");
+        health.on_book_error("bitflyer", CHANNEL);
+        let mut sink = FileSink::new(path.clone()).unwrap();
+        for alert in health.check(at(31)) {
+            sink.send(&alert);
+        }
+        drop(sink);
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["timestamp"], "1970-01-01T00:00:31Z");
+        assert_eq!(lines[0]["level"], "warning");
+        assert_eq!(lines[0]["venue"], "bitflyer");
+        assert_eq!(lines[0]["channel"], CHANNEL);
+        assert_eq!(lines[0]["message"], "stale, no message for 31s");
+        assert_eq!(lines[1]["level"], "critical");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_gauge_vec, register_histogram_vec, register_int_counter_vec, Encoder, GaugeVec,
+    HistogramVec, IntCounterVec, TextEncoder,
+};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Latency buckets in seconds, from 100us to 10s.
+const LATENCY_BUCKETS: [f64; 14] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 10.0,
+];
+
+pub static MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "ralpha_messages_total",
+        "Messages received per feed channel",
+        &["venue", "channel"]
+    )
+    .unwrap()
+});
+
+pub static CALLBACK_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "ralpha_callback_seconds",
+        "Processing time of the feed callbacks per market data",
+        &["venue", "type"],
+        LATENCY_BUCKETS.to_vec()
+    )
+    .unwrap()
+});
+
+pub static FEED_LATENCY_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "ralpha_feed_latency_seconds",
+        "timestamp - market_created_timestamp of the market data reaching the callbacks",
+        &["venue", "type"],
+        LATENCY_BUCKETS.to_vec()
+    )
+    .unwrap()
+});
+
+pub static TRADE_DUPLICATES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "ralpha_trade_duplicates_total",
+        "Trades dropped as resent by the venue",
+        &["venue", "sym"]
+    )
+    .unwrap()
+});
+
+pub static TRADES_BACKFILLED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "ralpha_trades_backfilled_total",
+        "Trades missed by the feed and fetched from the REST API",
+        &["venue", "sym"]
+    )
+    .unwrap()
+});
+
+pub static BAR_LATE_TRADES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "ralpha_bar_late_trades_total",
+        "Trades older than the latest trade of their sym, dropped by the bar builder",
+        &["venue", "sym"]
+    )
+    .unwrap()
+});
+
+pub static TICK_RECORDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "ralpha_tick_records_written_total",
+        "Records written per tickerplant stream",
+        &["stream"]
+    )
+    .unwrap()
+});
+
+pub static TICK_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "ralpha_tick_bytes_written_total",
+        "Bytes written per tickerplant stream, before compression",
+        &["stream"]
+    )
+    .unwrap()
+});
+
+pub static ORDERS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "ralpha_orders_total",
+        "Orders per model and event: sent, canceled, rejected",
+        &["model_id", "event"]
+    )
+    .unwrap()
+});
+
+pub static FILLS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "ralpha_fills_total",
+        "Fills per model and sym",
+        &["model_id", "sym"]
+    )
+    .unwrap()
+});
+
+pub static POSITION: Lazy<GaugeVec> = Lazy::new(|| {
+    register_gauge_vec!(
+        "ralpha_position",
+        "Signed position per model and sym",
+        &["model_id", "sym"]
+    )
+    .unwrap()
+});
+
+pub static PNL: Lazy<GaugeVec> = Lazy::new(|| {
+    register_gauge_vec!(
+        "ralpha_pnl",
+        "Mark-to-mid PnL per model, in quote currency",
+        &["model_id"]
+    )
+    .unwrap()
+});
+
+/// Whether `serve` was called. Metrics needing extra work, e.g. position queries, are only
+/// updated when enabled.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Serve `GET /metrics` in the Prometheus text format on its own thread.
+pub fn serve(addr: SocketAddr) -> std::io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr)?;
+    ENABLED.store(true, Ordering::Relaxed);
+    info!("Serve metrics on http://{}/metrics", addr);
+    Ok(std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.and_then(respond);
+            if let Err(e) = result {
+                warn!("Failed to serve metrics : {}", e);
+            }
+        }
+    }))
+}
+
+fn respond(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+
+    let (status, content_type, body) = if path == "/metrics" {
+        let encoder = TextEncoder::new();
+        let mut body = vec![];
+        encoder.encode(&prometheus::gather(), &mut body).unwrap();
+        ("200 OK", encoder.format_type().to_string(), body)
+    } else {
+        (
+            "404 Not Found",
+            "text/plain".to_string(),
+            b"not found\n".to_vec(),
+        )
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
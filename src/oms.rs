@@ -0,0 +1,2 @@
+pub mod order_lifecycle;
+pub mod order_manager;
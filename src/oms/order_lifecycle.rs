@@ -0,0 +1,212 @@
+use log::warn;
+use std::fmt;
+use time::OffsetDateTime;
+
+use crate::datamodels::order::{Order, OrderStatus, OrderTransition};
+use crate::datamodels::types::Qty;
+
+/// Illegal transition requested on an order. The order is left untouched.
+#[derive(Clone, Debug)]
+pub struct OrderAnomaly {
+    pub timestamp: OffsetDateTime,
+    pub order_id: String,
+    pub from: OrderStatus,
+    pub to: OrderStatus,
+}
+
+impl fmt::Display for OrderAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "illegal transition {:?} -> {:?} on order {} at {}",
+            self.from, self.to, self.order_id, self.timestamp
+        )
+    }
+}
+
+impl std::error::Error for OrderAnomaly {}
+
+impl OrderStatus {
+    /// No transition is allowed out of a terminal status.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Filled
+                | OrderStatus::Canceled
+                | OrderStatus::Rejected
+                | OrderStatus::Expired
+        )
+    }
+
+    /// Working at the venue, or about to be.
+    pub fn is_open(&self) -> bool {
+        !self.is_terminal()
+    }
+
+    pub fn can_transition_to(&self, to: OrderStatus) -> bool {
+        use OrderStatus::*;
+        match self {
+            Created => matches!(to, Submitted | Rejected | Canceled),
+            Submitted => matches!(
+                to,
+                Accepted | PartiallyFilled | Filled | PendingCancel | Canceled | Rejected | Expired
+            ),
+            Accepted | Amended | PartiallyFilled => matches!(
+                to,
+                Amended | PartiallyFilled | Filled | PendingCancel | Canceled | Expired
+            ),
+            // fills may race with the cancel request, and a failed cancel reverts to the working state.
+            PendingCancel => matches!(to, Accepted | PartiallyFilled | Filled | Canceled | Expired),
+            Filled | Canceled | Rejected | Expired => false,
+        }
+    }
+}
+
+impl Order {
+    /// Move the order to `to`, recording the transition in `history`.
+    ///
+    /// Re-applying the current status is a no-op, except for `Amended` and `PartiallyFilled`
+    /// which can legitimately repeat. Illegal transitions are logged and returned as `OrderAnomaly`.
+    pub fn transition(
+        &mut self,
+        to: OrderStatus,
+        timestamp: OffsetDateTime,
+    ) -> Result<(), OrderAnomaly> {
+        let from = self.order_status;
+        let repeatable = matches!(to, OrderStatus::Amended | OrderStatus::PartiallyFilled);
+        if from == to && !repeatable {
+            return Ok(());
+        }
+        if !from.can_transition_to(to) {
+            return Err(self.anomaly(to, timestamp));
+        }
+        self.order_status = to;
+        self.history.push(OrderTransition {
+            timestamp,
+            from,
+            to,
+            executed_amount: self.executed_amount,
+        });
+        Ok(())
+    }
+
+    fn anomaly(&self, to: OrderStatus, timestamp: OffsetDateTime) -> OrderAnomaly {
+        let anomaly = OrderAnomaly {
+            timestamp,
+            order_id: self.order_id.clone(),
+            from: self.order_status,
+            to,
+        };
+        warn!("Order anomaly: {}", anomaly);
+        anomaly
+    }
+
+    /// Add `amount` to `executed_amount` and move to `PartiallyFilled` or `Filled`.
+    ///
+    /// An overfill is logged and clamped to the order amount. A fill on a terminal order,
+    /// e.g. a late or duplicate venue fill, is an anomaly even if the order is already `Filled`.
+    pub fn fill(&mut self, amount: Qty, timestamp: OffsetDateTime) -> Result<(), OrderAnomaly> {
+        let executed_amount = self.executed_amount + amount;
+        let to = if executed_amount >= self.amount {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+        if self.order_status.is_terminal() || !self.order_status.can_transition_to(to) {
+            return Err(self.anomaly(to, timestamp));
+        }
+        if executed_amount > self.amount {
+            warn!(
+                "Order overfill: {} executed of {} on order {} at {}, clamped",
+                executed_amount, self.amount, self.order_id, timestamp
+            );
+        }
+        self.executed_amount = executed_amount.min(self.amount);
+        self.transition(to, timestamp)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.order_status.is_open()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodels::id_generator::IdSource;
+    use crate::datamodels::order::OrderType;
+    use crate::datamodels::types::{Price, Side};
+
+    fn submitted(amount: f64) -> Order {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        let mut order = Order::new(
+            now,
+            "FXBTCJPY",
+            Side::Buy,
+            Price::from_f64(100.0),
+            Qty::from_f64(amount),
+            OrderType::Limit,
+            "bitflyer",
+            "",
+            &IdSource::default(),
+        );
+        order.transition(OrderStatus::Submitted, now).unwrap();
+        order
+    }
+
+    #[test]
+    fn partial_then_full_fill() {
+        let mut order = submitted(1.0);
+        order
+            .fill(Qty::from_f64(0.4), OffsetDateTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(order.order_status, OrderStatus::PartiallyFilled);
+        order
+            .fill(Qty::from_f64(0.6), OffsetDateTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(order.order_status, OrderStatus::Filled);
+        assert_eq!(order.executed_amount, Qty::from_f64(1.0));
+    }
+
+    #[test]
+    fn overfill_is_clamped_to_the_order_amount() {
+        let mut order = submitted(1.0);
+        order
+            .fill(Qty::from_f64(0.7), OffsetDateTime::UNIX_EPOCH)
+            .unwrap();
+        order
+            .fill(Qty::from_f64(0.7), OffsetDateTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(order.order_status, OrderStatus::Filled);
+        assert_eq!(order.executed_amount, Qty::from_f64(1.0));
+    }
+
+    #[test]
+    fn fill_after_terminal_status_is_an_anomaly() {
+        let mut order = submitted(1.0);
+        order
+            .transition(OrderStatus::Canceled, OffsetDateTime::UNIX_EPOCH)
+            .unwrap();
+        let anomaly = order
+            .fill(Qty::from_f64(0.5), OffsetDateTime::UNIX_EPOCH)
+            .unwrap_err();
+        assert_eq!(anomaly.from, OrderStatus::Canceled);
+        assert_eq!(order.executed_amount, Qty::ZERO);
+    }
+
+    #[test]
+    fn fill_after_filled_is_an_anomaly() {
+        let mut order = submitted(1.0);
+        order
+            .fill(Qty::from_f64(1.0), OffsetDateTime::UNIX_EPOCH)
+            .unwrap();
+        let history = order.history.len();
+        let anomaly = order
+            .fill(Qty::from_f64(0.5), OffsetDateTime::UNIX_EPOCH)
+            .unwrap_err();
+        assert_eq!(anomaly.from, OrderStatus::Filled);
+        assert_eq!(anomaly.to, OrderStatus::Filled);
+        assert_eq!(order.executed_amount, Qty::from_f64(1.0));
+        assert_eq!(order.history.len(), history);
+    }
+}
@@ -0,0 +1,376 @@
+use log::warn;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use time::OffsetDateTime;
+
+use crate::datamodels::order::Order;
+use crate::datamodels::types::{Price, Qty, Side};
+use crate::oms::order_lifecycle::OrderAnomaly;
+
+/// Differences found by `OrderManager::reconcile`.
+#[derive(Clone, Debug, Default)]
+pub struct ReconcileReport {
+    /// Open at the venue but unknown to us. Adopted into the book.
+    pub unknown_orders: Vec<String>,
+    /// Open in our book but absent from the venue snapshot.
+    pub missing_orders: Vec<String>,
+    /// Status or executed amount taken over from the venue.
+    pub updated_orders: Vec<String>,
+    /// Venue state we could not apply.
+    pub anomalies: Vec<OrderAnomaly>,
+}
+
+impl ReconcileReport {
+    pub fn is_clean(&self) -> bool {
+        self.unknown_orders.is_empty()
+            && self.missing_orders.is_empty()
+            && self.updated_orders.is_empty()
+            && self.anomalies.is_empty()
+    }
+}
+
+/// Every `Order` we know of, indexed by `order_id` and `model_id`.
+#[derive(Clone, Debug, Default)]
+pub struct OrderManager {
+    orders: HashMap<String, Order>,
+    model_orders: HashMap<String, BTreeSet<String>>,
+    open_order_ids: BTreeSet<String>,
+}
+
+impl OrderManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// Insert or replace an order.
+    pub fn insert(&mut self, order: Order) {
+        if let Some(previous) = self.orders.get(&order.order_id) {
+            if previous.model_id != order.model_id {
+                self.unindex_model(&previous.model_id.clone(), &order.order_id);
+            }
+        }
+        self.model_orders
+            .entry(order.model_id.clone())
+            .or_default()
+            .insert(order.order_id.clone());
+        self.refresh_open(&order);
+        self.orders.insert(order.order_id.clone(), order);
+    }
+
+    pub fn remove(&mut self, order_id: &str) -> Option<Order> {
+        let order = self.orders.remove(order_id)?;
+        self.unindex_model(&order.model_id, order_id);
+        self.open_order_ids.remove(order_id);
+        Some(order)
+    }
+
+    pub fn get(&self, order_id: &str) -> Option<&Order> {
+        self.orders.get(order_id)
+    }
+
+    /// Mutate an order in place. `order_id` and `model_id` must not be changed here.
+    pub fn update<F: FnOnce(&mut Order)>(&mut self, order_id: &str, f: F) -> Option<&Order> {
+        let order = self.orders.get_mut(order_id)?;
+        f(order);
+        if order.is_open() {
+            self.open_order_ids.insert(order_id.to_string());
+        } else {
+            self.open_order_ids.remove(order_id);
+        }
+        self.orders.get(order_id)
+    }
+
+    pub fn orders(&self) -> impl Iterator<Item = &Order> {
+        self.orders.values()
+    }
+
+    pub fn orders_of_model(&self, model_id: &str) -> Vec<&Order> {
+        self.model_orders
+            .get(model_id)
+            .map(|ids| ids.iter().filter_map(|id| self.orders.get(id)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Open orders, optionally filtered by sym, side and model.
+    pub fn open_orders(
+        &self,
+        sym: Option<&str>,
+        side: Option<Side>,
+        model_id: Option<&str>,
+    ) -> Vec<&Order> {
+        self.open_order_ids
+            .iter()
+            .filter_map(|id| self.orders.get(id))
+            .filter(|o| sym.is_none_or(|sym| o.sym == sym))
+            .filter(|o| side.is_none_or(|side| o.side == side))
+            .filter(|o| model_id.is_none_or(|model_id| o.model_id == model_id))
+            .collect()
+    }
+
+    pub fn open_order_ids(&self) -> Vec<String> {
+        self.open_order_ids.iter().cloned().collect()
+    }
+
+    /// Signed remaining amount of open orders per price level. Bids are positive, asks negative.
+    pub fn exposure_by_price(&self, sym: &str, model_id: Option<&str>) -> BTreeMap<Price, Qty> {
+        let mut exposure = BTreeMap::new();
+        for order in self.open_orders(Some(sym), None, model_id) {
+            *exposure.entry(order.price).or_insert(Qty::ZERO) +=
+                (order.amount - order.executed_amount).signed(order.side);
+        }
+        exposure
+    }
+
+    /// Remaining amount of open orders on one side.
+    pub fn open_amount(&self, sym: &str, side: Side, model_id: Option<&str>) -> Qty {
+        self.open_orders(Some(sym), Some(side), model_id)
+            .iter()
+            .map(|o| o.amount - o.executed_amount)
+            .sum()
+    }
+
+    /// Align our book with the open orders reported by `venue` for `sym`.
+    ///
+    /// Venue state wins: unknown orders are adopted, statuses and executed amounts are taken over
+    /// through the order lifecycle. Our open orders absent from the snapshot are only reported,
+    /// since the venue may have filled or canceled them while we missed the events.
+    pub fn reconcile(
+        &mut self,
+        venue: &str,
+        sym: &str,
+        snapshot: Vec<Order>,
+        timestamp: OffsetDateTime,
+    ) -> ReconcileReport {
+        let mut report = ReconcileReport::default();
+        let snapshot_ids: BTreeSet<String> = snapshot.iter().map(|o| o.order_id.clone()).collect();
+
+        for venue_order in snapshot {
+            self.apply(venue_order, timestamp, &mut report);
+        }
+
+        for order in self.open_orders(Some(sym), None, None) {
+            if order.venue == venue && !snapshot_ids.contains(&order.order_id) {
+                report.missing_orders.push(order.order_id.clone());
+            }
+        }
+
+        if !report.is_clean() {
+            warn!(
+                "Reconcile {} {} : unknown={:?} missing={:?} updated={:?} anomalies={}",
+                venue,
+                sym,
+                report.unknown_orders,
+                report.missing_orders,
+                report.updated_orders,
+                report.anomalies.len()
+            );
+        }
+        report
+    }
+
+    /// Take over the state of one order reported by the venue, e.g. by an order event.
+    /// Unknown orders are adopted.
+    pub fn apply(
+        &mut self,
+        venue_order: Order,
+        timestamp: OffsetDateTime,
+        report: &mut ReconcileReport,
+    ) {
+        let order_id = venue_order.order_id.clone();
+        let order = match self.orders.get(&order_id) {
+            Some(order) => order,
+            None => {
+                report.unknown_orders.push(order_id);
+                self.insert(venue_order);
+                return;
+            }
+        };
+        let unseen_fill = venue_order.executed_amount - order.executed_amount;
+        let status_differs = venue_order.order_status != order.order_status;
+        if unseen_fill <= Qty::ZERO && !status_differs {
+            return;
+        }
+        let mut anomalies = vec![];
+        self.update(&order_id, |order| {
+            if unseen_fill > Qty::ZERO {
+                if let Err(anomaly) = order.fill(unseen_fill, timestamp) {
+                    anomalies.push(anomaly);
+                }
+            }
+            if venue_order.order_status != order.order_status {
+                if let Err(anomaly) = order.transition(venue_order.order_status, timestamp) {
+                    anomalies.push(anomaly);
+                }
+            }
+        });
+        report.updated_orders.push(order_id);
+        report.anomalies.extend(anomalies);
+    }
+
+    fn refresh_open(&mut self, order: &Order) {
+        if order.is_open() {
+            self.open_order_ids.insert(order.order_id.clone());
+        } else {
+            self.open_order_ids.remove(&order.order_id);
+        }
+    }
+
+    fn unindex_model(&mut self, model_id: &str, order_id: &str) {
+        if let Some(ids) = self.model_orders.get_mut(model_id) {
+            ids.remove(order_id);
+            if ids.is_empty() {
+                self.model_orders.remove(model_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodels::id_generator::IdSource;
+    use crate::datamodels::order::{OrderStatus, OrderType};
+
+    fn order(sym: &str, side: Side, price: f64, amount: f64, model_id: &str) -> Order {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        let mut order = Order::new(
+            now,
+            sym,
+            side,
+            Price::from_f64(price),
+            Qty::from_f64(amount),
+            OrderType::Limit,
+            "bitflyer",
+            model_id,
+            &IdSource::default(),
+        );
+        order.transition(OrderStatus::Submitted, now).unwrap();
+        order
+    }
+
+    #[test]
+    fn reconcile_adopts_unknown_reports_missing_and_takes_over_diverged_orders() {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        let mut orders = OrderManager::new();
+        let kept = order("FXBTCJPY", Side::Buy, 100.0, 1.0, "a");
+        let missing = order("FXBTCJPY", Side::Buy, 99.0, 1.0, "a");
+        let diverged = order("FXBTCJPY", Side::Sell, 101.0, 1.0, "b");
+        let other_sym = order("BTCJPY", Side::Buy, 100.0, 1.0, "a");
+        for o in [&kept, &missing, &diverged, &other_sym] {
+            orders.insert(o.clone());
+        }
+
+        let unknown = order("FXBTCJPY", Side::Sell, 102.0, 1.0, "");
+        let mut partially_filled = diverged.clone();
+        partially_filled.executed_amount = Qty::from_f64(0.4);
+        partially_filled.order_status = OrderStatus::PartiallyFilled;
+        let report = orders.reconcile(
+            "bitflyer",
+            "FXBTCJPY",
+            vec![kept.clone(), partially_filled, unknown.clone()],
+            now,
+        );
+
+        assert_eq!(report.unknown_orders, vec![unknown.order_id.clone()]);
+        assert_eq!(report.missing_orders, vec![missing.order_id.clone()]);
+        assert_eq!(report.updated_orders, vec![diverged.order_id.clone()]);
+        assert!(report.anomalies.is_empty());
+        assert!(!report.is_clean());
+
+        let taken_over = orders.get(&diverged.order_id).unwrap();
+        assert_eq!(taken_over.order_status, OrderStatus::PartiallyFilled);
+        assert_eq!(taken_over.executed_amount, Qty::from_f64(0.4));
+        assert!(orders.get(&unknown.order_id).unwrap().is_open());
+        // missing orders are only reported, the venue may have filled them
+        assert!(orders.get(&missing.order_id).unwrap().is_open());
+        assert_eq!(orders.len(), 5);
+
+        let again = orders.reconcile(
+            "bitflyer",
+            "FXBTCJPY",
+            vec![
+                kept,
+                orders.get(&diverged.order_id).unwrap().clone(),
+                unknown,
+                missing,
+            ],
+            now,
+        );
+        assert!(again.is_clean());
+    }
+
+    #[test]
+    fn reconcile_reports_illegal_venue_state_as_anomalies() {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        let mut orders = OrderManager::new();
+        let mut canceled = order("FXBTCJPY", Side::Buy, 100.0, 1.0, "a");
+        canceled.transition(OrderStatus::Canceled, now).unwrap();
+        orders.insert(canceled.clone());
+
+        let mut filled = canceled.clone();
+        filled.order_status = OrderStatus::Filled;
+        filled.executed_amount = Qty::from_f64(1.0);
+        let report = orders.reconcile("bitflyer", "FXBTCJPY", vec![filled], now);
+        assert_eq!(report.anomalies.len(), 2);
+        assert_eq!(
+            orders.get(&canceled.order_id).unwrap().order_status,
+            OrderStatus::Canceled
+        );
+    }
+
+    #[test]
+    fn open_orders_and_exposure_are_filtered_per_model_and_sym() {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        let mut orders = OrderManager::new();
+        let mut partial = order("FXBTCJPY", Side::Buy, 100.0, 1.0, "a");
+        partial.fill(Qty::from_f64(0.25), now).unwrap();
+        let mut filled = order("FXBTCJPY", Side::Buy, 100.0, 1.0, "a");
+        filled.fill(Qty::from_f64(1.0), now).unwrap();
+        for o in [
+            partial,
+            filled,
+            order("FXBTCJPY", Side::Buy, 100.0, 0.5, "b"),
+            order("FXBTCJPY", Side::Sell, 100.0, 0.25, "b"),
+            order("FXBTCJPY", Side::Sell, 101.0, 2.0, "a"),
+            order("BTCJPY", Side::Buy, 100.0, 3.0, "a"),
+        ] {
+            orders.insert(o);
+        }
+
+        assert_eq!(orders.open_orders(None, None, None).len(), 5);
+        assert_eq!(
+            orders.open_orders(Some("FXBTCJPY"), None, Some("a")).len(),
+            2
+        );
+        assert_eq!(
+            orders.open_orders(None, Some(Side::Sell), Some("b")).len(),
+            1
+        );
+        assert_eq!(orders.orders_of_model("a").len(), 4);
+
+        // bids and asks net at the same level across models
+        let all = orders.exposure_by_price("FXBTCJPY", None);
+        assert_eq!(all[&Price::from_f64(100.0)], Qty::from_f64(1.0));
+        assert_eq!(all[&Price::from_f64(101.0)], Qty::from_f64(-2.0));
+        let b = orders.exposure_by_price("FXBTCJPY", Some("b"));
+        assert_eq!(b.len(), 1);
+        assert_eq!(b[&Price::from_f64(100.0)], Qty::from_f64(0.25));
+
+        assert_eq!(
+            orders.open_amount("FXBTCJPY", Side::Buy, Some("a")),
+            Qty::from_f64(0.75)
+        );
+        assert_eq!(
+            orders.open_amount("FXBTCJPY", Side::Buy, None),
+            Qty::from_f64(1.25)
+        );
+        assert_eq!(orders.open_amount("BTCJPY", Side::Sell, None), Qty::ZERO);
+    }
+}
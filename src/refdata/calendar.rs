@@ -0,0 +1,336 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::Path;
+use time::{Duration, OffsetDateTime, Time, UtcOffset, Weekday};
+
+use crate::refdata::instrument::{deserialize_hh_mm, serialize_hh_mm, RefDataError};
+
+/// Maintenance every day, `"HH:MM"` in UTC. `end` before `start` wraps over midnight.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DailyWindow {
+    #[serde(
+        deserialize_with = "deserialize_hh_mm",
+        serialize_with = "serialize_hh_mm"
+    )]
+    pub start: Time,
+    #[serde(
+        deserialize_with = "deserialize_hh_mm",
+        serialize_with = "serialize_hh_mm"
+    )]
+    pub end: Time,
+}
+
+/// Maintenance once a week, starting on `weekday` at `start` in UTC.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct WeeklyWindow {
+    #[serde(
+        deserialize_with = "deserialize_weekday",
+        serialize_with = "serialize_weekday"
+    )]
+    pub weekday: Weekday,
+    #[serde(
+        deserialize_with = "deserialize_hh_mm",
+        serialize_with = "serialize_hh_mm"
+    )]
+    pub start: Time,
+    #[serde(
+        deserialize_with = "deserialize_hh_mm",
+        serialize_with = "serialize_hh_mm"
+    )]
+    pub end: Time,
+}
+
+/// `"Monday"` to `"Sunday"`, `time` only reads the weekday number without `serde-human-readable`.
+fn deserialize_weekday<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Weekday, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(|_| {
+        serde::de::Error::custom(format!("invalid weekday {}, expected e.g. Monday", value))
+    })
+}
+
+fn serialize_weekday<S: Serializer>(weekday: &Weekday, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&weekday.to_string())
+}
+
+/// One-off closure, e.g. an announced system upgrade.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AdHocWindow {
+    #[serde(with = "time::serde::rfc3339")]
+    pub start: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub end: OffsetDateTime,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// `[start, end)` of the window opening at `start` on `date`.
+fn window_on(date: time::Date, start: Time, end: Time) -> (OffsetDateTime, OffsetDateTime) {
+    let open = date.with_time(start).assume_utc();
+    let mut close = date.with_time(end).assume_utc();
+    if end <= start {
+        close += Duration::days(1);
+    }
+    (open, close)
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct VenueCalendar {
+    pub venue: String,
+    #[serde(default)]
+    pub daily: Vec<DailyWindow>,
+    #[serde(default)]
+    pub weekly: Vec<WeeklyWindow>,
+    #[serde(default)]
+    pub holidays: Vec<AdHocWindow>,
+}
+
+impl VenueCalendar {
+    /// End of the maintenance window containing `timestamp`, if any.
+    pub fn maintenance_end(&self, timestamp: OffsetDateTime) -> Option<OffsetDateTime> {
+        let timestamp = timestamp.to_offset(UtcOffset::UTC);
+        // windows opened the day before may still be running.
+        let dates = [timestamp.date().previous_day(), Some(timestamp.date())];
+        let mut windows = vec![];
+        for date in dates.into_iter().flatten() {
+            for window in self.daily.iter() {
+                windows.push(window_on(date, window.start, window.end));
+            }
+            for window in self.weekly.iter().filter(|w| w.weekday == date.weekday()) {
+                windows.push(window_on(date, window.start, window.end));
+            }
+        }
+        for window in self.holidays.iter() {
+            windows.push((window.start, window.end));
+        }
+        windows
+            .into_iter()
+            .filter(|(open, close)| *open <= timestamp && timestamp < *close)
+            .map(|(_, close)| close)
+            .max()
+    }
+
+    pub fn is_maintenance(&self, timestamp: OffsetDateTime) -> bool {
+        self.maintenance_end(timestamp).is_some()
+    }
+
+    /// `[open, close)` of the windows overlapping `[start, end)`, in opening order.
+    pub fn windows_between(
+        &self,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> Vec<(OffsetDateTime, OffsetDateTime)> {
+        let start = start.to_offset(UtcOffset::UTC);
+        let end = end.to_offset(UtcOffset::UTC);
+        let mut windows = vec![];
+        let mut date = start.date().previous_day();
+        while let Some(day) = date.filter(|day| *day <= end.date()) {
+            for window in self.daily.iter() {
+                windows.push(window_on(day, window.start, window.end));
+            }
+            for window in self.weekly.iter().filter(|w| w.weekday == day.weekday()) {
+                windows.push(window_on(day, window.start, window.end));
+            }
+            date = day.next_day();
+        }
+        for window in self.holidays.iter() {
+            windows.push((window.start, window.end));
+        }
+        windows.retain(|(open, close)| *open < end && start < *close);
+        windows.sort();
+        windows
+    }
+}
+
+/// Maintenance windows per venue, loaded from a config file such as `config/calendar.toml`.
+/// Venues without an entry are always open.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct MaintenanceCalendar {
+    #[serde(rename = "venue", default)]
+    venues: Vec<VenueCalendar>,
+}
+
+impl MaintenanceCalendar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, RefDataError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, RefDataError> {
+        Ok(toml::from_str(content)?)
+    }
+
+    pub fn get(&self, venue: &str) -> Option<&VenueCalendar> {
+        self.venues.iter().find(|v| v.venue == venue)
+    }
+
+    pub fn maintenance_end(
+        &self,
+        venue: &str,
+        timestamp: OffsetDateTime,
+    ) -> Option<OffsetDateTime> {
+        self.get(venue)?.maintenance_end(timestamp)
+    }
+
+    pub fn is_maintenance(&self, venue: &str, timestamp: OffsetDateTime) -> bool {
+        self.maintenance_end(venue, timestamp).is_some()
+    }
+
+    pub fn windows_between(
+        &self,
+        venue: &str,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> Vec<(OffsetDateTime, OffsetDateTime)> {
+        self.get(venue)
+            .map_or(vec![], |calendar| calendar.windows_between(start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{Date, Month};
+
+    /// `hour:minute` UTC on January `day` 1970, the 3rd is a Saturday.
+    fn at(day: u8, hour: u8, minute: u8) -> OffsetDateTime {
+        Date::from_calendar_date(1970, Month::January, day)
+            .unwrap()
+            .with_hms(hour, minute, 0)
+            .unwrap()
+            .assume_utc()
+    }
+
+    fn calendar() -> MaintenanceCalendar {
+        MaintenanceCalendar::from_toml(include_str!("../../config/calendar.toml")).unwrap()
+    }
+
+    #[test]
+    fn config_calendar_loads() {
+        let calendar = calendar();
+        let example = calendar.get("example").unwrap();
+        assert_eq!(example.weekly[0].weekday, Weekday::Saturday);
+        assert_eq!(example.holidays[0].reason, "new year");
+
+        assert!(calendar.is_maintenance("bitflyer", at(5, 19, 0)));
+        assert!(calendar.is_maintenance("bitflyer", at(5, 19, 9)));
+        assert!(!calendar.is_maintenance("bitflyer", at(5, 19, 10)));
+        assert_eq!(
+            calendar.maintenance_end("bitflyer", at(5, 19, 5)),
+            Some(at(5, 19, 10))
+        );
+        // venues without an entry are always open
+        assert!(!calendar.is_maintenance("binance", at(5, 19, 5)));
+        assert!(calendar
+            .windows_between("binance", at(1, 0, 0), at(9, 0, 0))
+            .is_empty());
+    }
+
+    #[test]
+    fn windows_wrap_over_midnight() {
+        let calendar = calendar();
+        // daily 23:30 - 00:15
+        assert_eq!(
+            calendar.maintenance_end("example", at(5, 23, 45)),
+            Some(at(6, 0, 15))
+        );
+        assert_eq!(
+            calendar.maintenance_end("example", at(6, 0, 10)),
+            Some(at(6, 0, 15))
+        );
+        assert!(!calendar.is_maintenance("example", at(6, 0, 15)));
+        // the offset of the timestamp does not matter
+        let tokyo = UtcOffset::from_hms(9, 0, 0).unwrap();
+        assert!(calendar.is_maintenance("example", at(6, 0, 10).to_offset(tokyo)));
+    }
+
+    #[test]
+    fn weekly_windows_open_on_their_weekday() {
+        let calendar = calendar();
+        // Saturday 22:00 - Sunday 02:00, overlapping the daily window
+        assert!(!calendar.is_maintenance("example", at(2, 22, 30)));
+        assert_eq!(
+            calendar.maintenance_end("example", at(3, 22, 30)),
+            Some(at(4, 2, 0))
+        );
+        assert_eq!(
+            calendar.maintenance_end("example", at(3, 23, 45)),
+            Some(at(4, 2, 0))
+        );
+        assert_eq!(
+            calendar.maintenance_end("example", at(4, 1, 0)),
+            Some(at(4, 2, 0))
+        );
+        assert!(!calendar.is_maintenance("example", at(4, 2, 0)));
+        // a week later
+        assert_eq!(
+            calendar.maintenance_end("example", at(10, 22, 0)),
+            Some(at(11, 2, 0))
+        );
+    }
+
+    #[test]
+    fn windows_between_crosses_midnight() {
+        let calendar = calendar();
+        assert_eq!(
+            calendar.windows_between("example", at(3, 20, 0), at(4, 1, 0)),
+            vec![(at(3, 22, 0), at(4, 2, 0)), (at(3, 23, 30), at(4, 0, 15))]
+        );
+        // the window opened the day before is included
+        assert_eq!(
+            calendar.windows_between("example", at(5, 0, 0), at(5, 12, 0)),
+            vec![(at(4, 23, 30), at(5, 0, 15))]
+        );
+        assert_eq!(
+            calendar.windows_between("bitflyer", at(5, 0, 0), at(7, 0, 0)),
+            vec![(at(5, 19, 0), at(5, 19, 10)), (at(6, 19, 0), at(6, 19, 10))]
+        );
+    }
+
+    #[test]
+    fn holidays_are_maintenance() {
+        let calendar = calendar();
+        let new_year = Date::from_calendar_date(2026, Month::December, 31)
+            .unwrap()
+            .with_hms(20, 0, 0)
+            .unwrap()
+            .assume_utc();
+        assert!(calendar.is_maintenance("example", new_year));
+        assert!(!calendar.is_maintenance("bitflyer", new_year));
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let calendar = calendar();
+        let written = toml::to_string(&calendar).unwrap();
+        assert!(written.contains("weekday = \"Saturday\""), "{}", written);
+        assert!(written.contains("start = \"23:30\""), "{}", written);
+        let again = MaintenanceCalendar::from_toml(&written).unwrap();
+        assert_eq!(toml::to_string(&again).unwrap(), written);
+    }
+
+    #[test]
+    fn loader_errors() {
+        for weekly in [
+            r#"{ weekday = "Sat", start = "22:00", end = "02:00" }"#,
+            r#"{ weekday = 6, start = "22:00", end = "02:00" }"#,
+            r#"{ weekday = "Saturday", start = "22:00" }"#,
+            r#"{ weekday = "Saturday", start = "22:60", end = "02:00" }"#,
+        ] {
+            let content = format!("[[venue]]\nvenue = \"x\"\nweekly = [{}]\n", weekly);
+            assert!(
+                matches!(
+                    MaintenanceCalendar::from_toml(&content),
+                    Err(RefDataError::Toml(_))
+                ),
+                "{}",
+                weekly
+            );
+        }
+        assert!(matches!(
+            MaintenanceCalendar::from_file("config/no-such-file.toml"),
+            Err(RefDataError::Io(_))
+        ));
+    }
+}
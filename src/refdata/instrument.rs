@@ -0,0 +1,496 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::path::Path;
+use time::{OffsetDateTime, Time};
+
+use crate::datamodels::order::OrderType;
+use crate::datamodels::types::{Price, Qty};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContractType {
+    Spot,
+    Fx,
+    Futures,
+}
+
+/// Daily UTC session written as `"HH:MM"`. `end` before `start` wraps over midnight.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TradingSession {
+    #[serde(
+        deserialize_with = "deserialize_hh_mm",
+        serialize_with = "serialize_hh_mm"
+    )]
+    pub start: Time,
+    #[serde(
+        deserialize_with = "deserialize_hh_mm",
+        serialize_with = "serialize_hh_mm"
+    )]
+    pub end: Time,
+}
+
+impl TradingSession {
+    pub fn contains(&self, time: Time) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+pub(crate) fn deserialize_hh_mm<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Time, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    let invalid = || serde::de::Error::custom(format!("invalid time {}, expected HH:MM", value));
+    let (hour, minute) = value.split_once(':').ok_or_else(invalid)?;
+    let hour = hour.parse::<u8>().map_err(|_| invalid())?;
+    let minute = minute.parse::<u8>().map_err(|_| invalid())?;
+    Time::from_hms(hour, minute, 0).map_err(|_| invalid())
+}
+
+pub(crate) fn serialize_hh_mm<S: Serializer>(
+    time: &Time,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:02}:{:02}", time.hour(), time.minute()))
+}
+
+/// Reference data of one tradable instrument.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Instrument {
+    pub venue: String,
+    #[serde(default)]
+    pub category: String,
+    /// Symbol used by the venue, e.g. `FX_BTC_JPY`.
+    pub native_symbol: String,
+    /// Symbol used across ralpha, e.g. `FXBTCJPY`.
+    pub sym: String,
+    pub base: String,
+    pub quote: String,
+    pub tick_size: Price,
+    pub lot_size: Qty,
+    pub min_size: Qty,
+    pub contract_type: ContractType,
+    /// Empty means the instrument trades around the clock.
+    #[serde(default)]
+    pub trading_hours: Vec<TradingSession>,
+}
+
+impl Instrument {
+    pub fn is_trading(&self, timestamp: OffsetDateTime) -> bool {
+        let time = timestamp.to_offset(time::UtcOffset::UTC).time();
+        self.trading_hours.is_empty() || self.trading_hours.iter().any(|s| s.contains(time))
+    }
+
+    pub fn validate_order(
+        &self,
+        timestamp: OffsetDateTime,
+        price: Price,
+        amount: Qty,
+        order_type: &OrderType,
+    ) -> Result<(), OrderValidationError> {
+        if !self.is_trading(timestamp) {
+            return Err(OrderValidationError::OutsideTradingHours {
+                sym: self.sym.clone(),
+            });
+        }
+        if amount < self.min_size {
+            return Err(OrderValidationError::MinSize {
+                amount,
+                min_size: self.min_size,
+            });
+        }
+        if !amount.is_multiple_of(self.lot_size) {
+            return Err(OrderValidationError::LotSize {
+                amount,
+                lot_size: self.lot_size,
+            });
+        }
+        // market orders carry no price.
+        if !matches!(order_type, OrderType::Market) && !price.is_multiple_of(self.tick_size) {
+            return Err(OrderValidationError::TickSize {
+                price,
+                tick_size: self.tick_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum OrderValidationError {
+    UnknownInstrument {
+        venue: String,
+        sym: String,
+    },
+    OutsideTradingHours {
+        sym: String,
+    },
+    MinSize {
+        amount: Qty,
+        min_size: Qty,
+    },
+    LotSize {
+        amount: Qty,
+        lot_size: Qty,
+    },
+    TickSize {
+        price: Price,
+        tick_size: Price,
+    },
+    Maintenance {
+        venue: String,
+        until: OffsetDateTime,
+    },
+}
+
+impl fmt::Display for OrderValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderValidationError::UnknownInstrument { venue, sym } => {
+                write!(f, "unknown instrument {} on {}", sym, venue)
+            }
+            OrderValidationError::OutsideTradingHours { sym } => {
+                write!(f, "{} is outside trading hours", sym)
+            }
+            OrderValidationError::MinSize { amount, min_size } => {
+                write!(f, "amount {} is below min size {}", amount, min_size)
+            }
+            OrderValidationError::LotSize { amount, lot_size } => {
+                write!(
+                    f,
+                    "amount {} is not a multiple of lot size {}",
+                    amount, lot_size
+                )
+            }
+            OrderValidationError::Maintenance { venue, until } => {
+                write!(f, "{} is in maintenance until {}", venue, until)
+            }
+            OrderValidationError::TickSize { price, tick_size } => {
+                write!(
+                    f,
+                    "price {} is not a multiple of tick size {}",
+                    price, tick_size
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for OrderValidationError {}
+
+#[derive(Debug)]
+pub enum RefDataError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+}
+
+impl fmt::Display for RefDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RefDataError::Io(e) => write!(f, "io error: {}", e),
+            RefDataError::Toml(e) => write!(f, "toml error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RefDataError {}
+
+impl From<std::io::Error> for RefDataError {
+    fn from(e: std::io::Error) -> Self {
+        RefDataError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for RefDataError {
+    fn from(e: toml::de::Error) -> Self {
+        RefDataError::Toml(e)
+    }
+}
+
+/// Instruments loaded from a config file such as `config/instruments.toml`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct InstrumentRegistry {
+    #[serde(rename = "instrument", default)]
+    instruments: Vec<Instrument>,
+}
+
+impl InstrumentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, RefDataError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, RefDataError> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Insert or replace the instrument with the same venue and sym.
+    pub fn insert(&mut self, instrument: Instrument) {
+        self.instruments
+            .retain(|i| !(i.venue == instrument.venue && i.sym == instrument.sym));
+        self.instruments.push(instrument);
+    }
+
+    pub fn instruments(&self) -> &[Instrument] {
+        &self.instruments
+    }
+
+    pub fn get(&self, venue: &str, sym: &str) -> Option<&Instrument> {
+        self.instruments
+            .iter()
+            .find(|i| i.venue == venue && i.sym == sym)
+    }
+
+    pub fn by_native_symbol(&self, venue: &str, native_symbol: &str) -> Option<&Instrument> {
+        self.instruments
+            .iter()
+            .find(|i| i.venue == venue && i.native_symbol == native_symbol)
+    }
+
+    /// Instrument whose native symbol ends `channel`, e.g. `lightning_board_FX_BTC_JPY`.
+    /// The longest match wins, so `FX_BTC_JPY` is not taken for `BTC_JPY`.
+    pub fn by_channel(&self, venue: &str, channel: &str) -> Option<&Instrument> {
+        self.instruments
+            .iter()
+            .filter(|i| i.venue == venue && channel.ends_with(&i.native_symbol))
+            .max_by_key(|i| i.native_symbol.len())
+    }
+
+    /// Normalized symbol of `native_symbol`, falling back to stripping the underscores.
+    pub fn sym(&self, venue: &str, native_symbol: &str) -> String {
+        match self.by_native_symbol(venue, native_symbol) {
+            Some(instrument) => instrument.sym.clone(),
+            None => native_symbol.replace('_', ""),
+        }
+    }
+
+    /// Category of `native_symbol`, `None` when the instrument is unknown or has none.
+    pub fn category(&self, venue: &str, native_symbol: &str) -> Option<String> {
+        self.by_native_symbol(venue, native_symbol)
+            .map(|i| i.category.clone())
+            .filter(|category| !category.is_empty())
+    }
+
+    pub fn native_symbol(&self, venue: &str, sym: &str) -> Option<String> {
+        self.get(venue, sym).map(|i| i.native_symbol.clone())
+    }
+
+    pub fn validate_order(
+        &self,
+        timestamp: OffsetDateTime,
+        venue: &str,
+        sym: &str,
+        price: Price,
+        amount: Qty,
+        order_type: &OrderType,
+    ) -> Result<(), OrderValidationError> {
+        match self.get(venue, sym) {
+            Some(instrument) => instrument.validate_order(timestamp, price, amount, order_type),
+            None => Err(OrderValidationError::UnknownInstrument {
+                venue: venue.to_string(),
+                sym: sym.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u8, minute: u8) -> OffsetDateTime {
+        OffsetDateTime::UNIX_EPOCH.replace_time(Time::from_hms(hour, minute, 0).unwrap())
+    }
+
+    fn registry() -> InstrumentRegistry {
+        InstrumentRegistry::from_toml(include_str!("../../config/instruments.toml")).unwrap()
+    }
+
+    #[test]
+    fn config_instruments_load() {
+        let instruments = registry();
+        let fx = instruments.get("bitflyer", "FXBTCJPY").unwrap();
+        assert_eq!(fx.native_symbol, "FX_BTC_JPY");
+        assert_eq!(fx.category, "lightning");
+        assert_eq!(fx.contract_type, ContractType::Fx);
+        assert_eq!(fx.tick_size, Price::from_f64(1.0));
+        assert_eq!(fx.lot_size, Qty::from_units(1));
+        assert_eq!(fx.min_size, Qty::from_f64(0.01));
+        assert!(instruments.get("binance", "FXBTCJPY").is_none());
+    }
+
+    #[test]
+    fn lookups_by_native_symbol_and_channel() {
+        let instruments = registry();
+        assert_eq!(instruments.sym("bitflyer", "FX_BTC_JPY"), "FXBTCJPY");
+        assert_eq!(instruments.sym("bitflyer", "ETH_BTC_X"), "ETHBTCX");
+        assert_eq!(
+            instruments.category("bitflyer", "BTC_JPY").as_deref(),
+            Some("lightning")
+        );
+        assert_eq!(instruments.category("bitflyer", "ETH_BTC_X"), None);
+        assert_eq!(
+            instruments.native_symbol("bitflyer", "BTCJPY").as_deref(),
+            Some("BTC_JPY")
+        );
+        // the longest native symbol wins
+        assert_eq!(
+            instruments
+                .by_channel("bitflyer", "lightning_board_FX_BTC_JPY")
+                .unwrap()
+                .sym,
+            "FXBTCJPY"
+        );
+        assert_eq!(
+            instruments
+                .by_channel("bitflyer", "lightning_board_BTC_JPY")
+                .unwrap()
+                .sym,
+            "BTCJPY"
+        );
+    }
+
+    #[test]
+    fn insert_replaces_the_same_venue_and_sym() {
+        let mut instruments = registry();
+        let count = instruments.instruments().len();
+        let mut fx = instruments.get("bitflyer", "FXBTCJPY").unwrap().clone();
+        fx.min_size = Qty::from_f64(0.1);
+        instruments.insert(fx.clone());
+        assert_eq!(instruments.instruments().len(), count);
+        assert_eq!(
+            instruments.get("bitflyer", "FXBTCJPY").unwrap().min_size,
+            Qty::from_f64(0.1)
+        );
+        fx.venue = "other".to_string();
+        instruments.insert(fx);
+        assert_eq!(instruments.instruments().len(), count + 1);
+    }
+
+    #[test]
+    fn orders_are_validated_against_the_instrument() {
+        let mut instruments = registry();
+        let mut fx = instruments.get("bitflyer", "FXBTCJPY").unwrap().clone();
+        fx.lot_size = Qty::from_f64(0.001);
+        instruments.insert(fx);
+        let now = at(12, 0);
+        let validate = |sym: &str, price: f64, amount: f64, order_type: OrderType| {
+            instruments.validate_order(
+                now,
+                "bitflyer",
+                sym,
+                Price::from_f64(price),
+                Qty::from_f64(amount),
+                &order_type,
+            )
+        };
+        assert!(validate("FXBTCJPY", 5_000_000.0, 0.01, OrderType::Limit).is_ok());
+        assert!(matches!(
+            validate("FXBTCJPY", 5_000_000.0, 0.009, OrderType::Limit),
+            Err(OrderValidationError::MinSize { .. })
+        ));
+        assert!(matches!(
+            validate("FXBTCJPY", 5_000_000.0, 0.0105, OrderType::Limit),
+            Err(OrderValidationError::LotSize { .. })
+        ));
+        assert!(matches!(
+            validate("FXBTCJPY", 5_000_000.5, 0.01, OrderType::Limit),
+            Err(OrderValidationError::TickSize { .. })
+        ));
+        // market orders carry no price
+        assert!(validate("FXBTCJPY", 5_000_000.5, 0.01, OrderType::Market).is_ok());
+        assert!(matches!(
+            validate("XXXJPY", 1.0, 1.0, OrderType::Limit),
+            Err(OrderValidationError::UnknownInstrument { .. })
+        ));
+    }
+
+    #[test]
+    fn trading_sessions_wrap_over_midnight() {
+        let mut instruments = registry();
+        let mut fx = instruments.get("bitflyer", "FXBTCJPY").unwrap().clone();
+        fx.trading_hours = toml::from_str::<InstrumentRegistry>(
+            r#"
+            [[instrument]]
+            venue = "bitflyer"
+            native_symbol = "FX_BTC_JPY"
+            sym = "FXBTCJPY"
+            base = "BTC"
+            quote = "JPY"
+            tick_size = 1.0
+            lot_size = 0.00000001
+            min_size = 0.01
+            contract_type = "Fx"
+            trading_hours = [{ start = "22:00", end = "06:00" }]
+            "#,
+        )
+        .unwrap()
+        .instruments()[0]
+            .trading_hours
+            .clone();
+        assert_eq!(
+            toml::to_string(&fx.trading_hours[0]).unwrap(),
+            "start = \"22:00\"\nend = \"06:00\"\n"
+        );
+        instruments.insert(fx);
+        let fx = instruments.get("bitflyer", "FXBTCJPY").unwrap();
+        assert!(fx.is_trading(at(23, 0)));
+        assert!(fx.is_trading(at(5, 59)));
+        assert!(!fx.is_trading(at(6, 0)));
+        // sessions are UTC whatever the offset of the timestamp
+        assert!(fx.is_trading(at(23, 0).to_offset(time::UtcOffset::from_hms(9, 0, 0).unwrap())));
+        assert!(matches!(
+            fx.validate_order(
+                at(12, 0),
+                Price::from_f64(5_000_000.0),
+                Qty::from_f64(0.01),
+                &OrderType::Limit
+            ),
+            Err(OrderValidationError::OutsideTradingHours { .. })
+        ));
+    }
+
+    #[test]
+    fn loader_errors() {
+        assert!(matches!(
+            InstrumentRegistry::from_file("config/no-such-file.toml"),
+            Err(RefDataError::Io(_))
+        ));
+        // missing field
+        assert!(matches!(
+            InstrumentRegistry::from_toml("[[instrument]]\nvenue = \"bitflyer\"\n"),
+            Err(RefDataError::Toml(_))
+        ));
+        // invalid session time
+        let error = InstrumentRegistry::from_toml(
+            r#"
+            [[instrument]]
+            venue = "bitflyer"
+            native_symbol = "FX_BTC_JPY"
+            sym = "FXBTCJPY"
+            base = "BTC"
+            quote = "JPY"
+            tick_size = 1.0
+            lot_size = 0.00000001
+            min_size = 0.01
+            contract_type = "Fx"
+            trading_hours = [{ start = "25:00", end = "06:00" }]
+            "#,
+        )
+        .unwrap_err();
+        assert!(
+            error.to_string().contains("invalid time 25:00"),
+            "{}",
+            error
+        );
+        assert!(InstrumentRegistry::from_toml("")
+            .unwrap()
+            .instruments()
+            .is_empty());
+    }
+}
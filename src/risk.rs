@@ -0,0 +1,3 @@
+pub mod kill_switch;
+pub mod risk_client;
+pub mod risk_limits;
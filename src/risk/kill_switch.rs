@@ -0,0 +1,312 @@
+use log::{info, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Polling thread of `KillSwitch::watch_file`.
+pub struct FileWatcher {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl FileWatcher {
+    /// Stop polling and wait for the thread. Kills already triggered stay active.
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+}
+
+/// Emergency stop for one model or for everything.
+#[derive(Clone, Debug)]
+pub struct KillRequest {
+    /// `None` stops every model.
+    pub model_id: Option<String>,
+    /// Close positions with market orders after canceling open orders.
+    pub flatten: bool,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+struct KillSwitchState {
+    /// Reasons of the active kills per model, `None` stops every model.
+    kills: BTreeMap<Option<String>, BTreeSet<String>>,
+    pending: Vec<KillRequest>,
+}
+
+/// Blocks order submission until re-armed.
+///
+/// Triggering only records the request; cancel and flatten are executed by `RiskClient`
+/// on the thread owning the `ExecutionClient`. Clones share the same state, so a handle can be
+/// passed to a file watcher or any other trigger.
+#[derive(Clone, Debug, Default)]
+pub struct KillSwitch {
+    state: Arc<Mutex<KillSwitchState>>,
+}
+
+impl KillSwitch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn trigger(&self, request: KillRequest) {
+        warn!(
+            "Kill switch triggered model={} flatten={} : {}",
+            request.model_id.as_deref().unwrap_or("*"),
+            request.flatten,
+            request.reason
+        );
+        let mut state = self.state.lock().unwrap();
+        state
+            .kills
+            .entry(request.model_id.clone())
+            .or_default()
+            .insert(request.reason.clone());
+        state.pending.push(request);
+    }
+
+    /// Allow submissions again. `None` re-arms every model.
+    pub fn rearm(&self, model_id: Option<&str>) {
+        info!("Kill switch re-armed model={}", model_id.unwrap_or("*"));
+        let mut state = self.state.lock().unwrap();
+        match model_id {
+            Some(model_id) => {
+                state.kills.remove(&Some(model_id.to_string()));
+            }
+            None => state.kills.clear(),
+        }
+    }
+
+    /// Re-arm the kills triggered for `reason` only, kills with other reasons stay active.
+    pub fn rearm_reason(&self, reason: &str) {
+        info!("Kill switch re-armed for {}", reason);
+        let mut state = self.state.lock().unwrap();
+        state.kills.retain(|_, reasons| {
+            reasons.remove(reason);
+            !reasons.is_empty()
+        });
+    }
+
+    pub fn is_killed(&self, model_id: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        state.kills.contains_key(&None) || state.kills.contains_key(&Some(model_id.to_string()))
+    }
+
+    /// Stopped for every model, not only for some.
+    pub fn is_killed_all(&self) -> bool {
+        self.state.lock().unwrap().kills.contains_key(&None)
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.state.lock().unwrap().pending.is_empty()
+    }
+
+    /// Requests not yet executed.
+    pub fn take_pending(&self) -> Vec<KillRequest> {
+        std::mem::take(&mut self.state.lock().unwrap().pending)
+    }
+
+    /// Trigger the lines of `path` while it exists, until the returned watcher is stopped.
+    ///
+    /// Each line of the file is `<model_id|*> [flatten]`, an empty file stops every model.
+    /// Only added lines are triggered; a model whose lines are removed, or the whole file,
+    /// is re-armed from the kills of this file.
+    pub fn watch_file(&self, path: PathBuf, interval: Duration) -> FileWatcher {
+        let kill_switch = self.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let mut last_content: Option<String> = None;
+            let mut lines = BTreeSet::new();
+            while !stopped.load(Ordering::Relaxed) {
+                let content = std::fs::read_to_string(&path).ok();
+                if content != last_content {
+                    let current = content
+                        .as_deref()
+                        .map(Self::parse_lines)
+                        .unwrap_or_default();
+                    kill_switch.apply_lines(&lines, &current, &Self::file_reason(&path));
+                    lines = current;
+                    last_content = content;
+                }
+                std::thread::sleep(interval);
+            }
+        });
+        FileWatcher { stop, handle }
+    }
+
+    /// `(model_id, flatten)` of each line, an empty file is `*`.
+    fn parse_lines(content: &str) -> BTreeSet<(Option<String>, bool)> {
+        let mut lines: BTreeSet<(Option<String>, bool)> = content
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<&str>>())
+            .filter(|words| !words.is_empty())
+            .map(|words| {
+                let model_id = match words[0] {
+                    "*" => None,
+                    model_id => Some(model_id.to_string()),
+                };
+                (model_id, words.get(1) == Some(&"flatten"))
+            })
+            .collect();
+        if lines.is_empty() {
+            lines.insert((None, false));
+        }
+        lines
+    }
+
+    /// Trigger the lines added since `previous`, re-arm the models no line refers to anymore.
+    fn apply_lines(
+        &self,
+        previous: &BTreeSet<(Option<String>, bool)>,
+        current: &BTreeSet<(Option<String>, bool)>,
+        reason: &str,
+    ) {
+        for (model_id, flatten) in current.difference(previous) {
+            self.trigger(KillRequest {
+                model_id: model_id.clone(),
+                flatten: *flatten,
+                reason: reason.to_string(),
+            });
+        }
+        let removed: BTreeSet<&Option<String>> = previous
+            .difference(current)
+            .map(|(model_id, _)| model_id)
+            .filter(|model_id| !current.iter().any(|(m, _)| m == *model_id))
+            .collect();
+        if removed.is_empty() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        for model_id in removed {
+            info!(
+                "Kill switch re-armed model={} for {}",
+                model_id.as_deref().unwrap_or("*"),
+                reason
+            );
+            if let Some(reasons) = state.kills.get_mut(model_id) {
+                reasons.remove(reason);
+                if reasons.is_empty() {
+                    state.kills.remove(model_id);
+                }
+            }
+        }
+    }
+
+    fn file_reason(path: &std::path::Path) -> String {
+        format!("flag file {}", path.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REASON: &str = "flag file ralpha.kill";
+
+    fn request(model_id: Option<&str>, reason: &str) -> KillRequest {
+        KillRequest {
+            model_id: model_id.map(str::to_string),
+            flatten: false,
+            reason: reason.to_string(),
+        }
+    }
+
+    fn apply(kill_switch: &KillSwitch, previous: &str, current: Option<&str>) -> Vec<KillRequest> {
+        kill_switch.apply_lines(
+            &KillSwitch::parse_lines(previous),
+            &current.map(KillSwitch::parse_lines).unwrap_or_default(),
+            REASON,
+        );
+        kill_switch.take_pending()
+    }
+
+    #[test]
+    fn only_added_lines_are_triggered() {
+        let kill_switch = KillSwitch::new();
+        let pending = apply(&kill_switch, "a", Some("a\nb flatten\n"));
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].model_id.as_deref(), Some("b"));
+        assert!(pending[0].flatten);
+        assert!(!kill_switch.is_killed("a"));
+        assert!(kill_switch.is_killed("b"));
+
+        // rewriting the same lines triggers nothing
+        assert!(apply(&kill_switch, "a\nb flatten", Some("b  flatten\na\n")).is_empty());
+    }
+
+    #[test]
+    fn removed_lines_are_re_armed() {
+        let kill_switch = KillSwitch::new();
+        kill_switch.trigger(request(Some("a"), "daily loss"));
+        apply(&kill_switch, "", None);
+        apply(&kill_switch, "x", Some("a\nb\nc"));
+        assert!(kill_switch.is_killed("b") && kill_switch.is_killed("c"));
+
+        apply(&kill_switch, "a\nb\nc", Some("a\nc flatten"));
+        assert!(!kill_switch.is_killed("b"));
+        assert!(kill_switch.is_killed("c"));
+
+        // the whole file removed, kills of other reasons stay
+        apply(&kill_switch, "a\nc flatten", None);
+        assert!(kill_switch.is_killed("a"));
+        assert!(!kill_switch.is_killed("c"));
+    }
+
+    #[test]
+    fn empty_flag_file_stops_every_model() {
+        let kill_switch = KillSwitch::new();
+        let pending = apply(&kill_switch, "x", Some(""));
+        assert!(pending[0].model_id.is_none());
+        assert!(kill_switch.is_killed("any"));
+        apply(&kill_switch, "", None);
+        assert!(!kill_switch.is_killed("any"));
+    }
+
+    #[test]
+    fn rearm_reason_keeps_other_kills() {
+        let kill_switch = KillSwitch::new();
+        kill_switch.trigger(request(Some("a"), "daily loss"));
+        kill_switch.trigger(request(Some("a"), REASON));
+        kill_switch.trigger(request(Some("b"), REASON));
+        kill_switch.rearm_reason(REASON);
+        assert!(kill_switch.is_killed("a"));
+        assert!(!kill_switch.is_killed("b"));
+        assert_eq!(kill_switch.take_pending().len(), 3);
+        assert!(!kill_switch.has_pending());
+    }
+
+    #[test]
+    fn watcher_follows_the_file_until_stopped() {
+        let path = std::env::temp_dir().join(format!("ralpha-{}.kill", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let kill_switch = KillSwitch::new();
+        let watcher = kill_switch.watch_file(path.clone(), Duration::from_millis(5));
+        let wait_for = |killed: bool| {
+            for _ in 0..200 {
+                if kill_switch.is_killed("a") == killed {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            panic!(
+                "kill switch not {}",
+                if killed { "killed" } else { "re-armed" }
+            );
+        };
+
+        std::fs::write(&path, "a\n").unwrap();
+        wait_for(true);
+        std::fs::remove_file(&path).unwrap();
+        wait_for(false);
+
+        watcher.stop();
+        std::fs::write(&path, "a\n").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!kill_switch.is_killed("a"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,827 @@
+use log::{info, warn};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+use crate::api_client::execution_client::ExecutionClient;
+use crate::datamodels::enums::MarketData;
+use crate::datamodels::id_generator::IdSource;
+use crate::datamodels::latency::{Hop, LatencyTracker};
+use crate::datamodels::marketdata::Rate;
+use crate::datamodels::order::{Order, OrderStatus, OrderType};
+use crate::datamodels::position::Position;
+use crate::datamodels::types::{Price, Qty, Side};
+use crate::monitor::metrics;
+use crate::risk::kill_switch::{KillRequest, KillSwitch};
+use crate::risk::risk_limits::{RiskLimits, RiskRejection};
+
+/// `ExecutionClient` decorator running pre-trade checks before orders reach `client`.
+///
+/// Rejected orders are returned with `OrderStatus::Rejected` and the reason in `misc`,
+/// without being sent. Feed it market data through `callback` to enable price collars,
+/// the daily loss limit, the execution of kill switch requests and the latency stamps of
+/// orders submitted while a tick is dispatched.
+pub struct RiskClient<C: ExecutionClient> {
+    client: C,
+    limits: RiskLimits,
+    kill_switch: KillSwitch,
+    latest_rates: HashMap<String, Rate>,
+    order_times: HashMap<String, VecDeque<OffsetDateTime>>,
+    day_start_pnl: HashMap<String, (time::Date, f64)>,
+    last_pnl_check: Option<OffsetDateTime>,
+    last_metrics_update: Option<OffsetDateTime>,
+    last_rejection: Option<RiskRejection>,
+    rejection_count: usize,
+    dispatched: LatencyTracker,
+    ids: IdSource,
+    simulated_time: bool,
+}
+
+impl<C: ExecutionClient> RiskClient<C> {
+    pub fn new(client: C, limits: RiskLimits) -> Self {
+        Self {
+            client,
+            limits,
+            kill_switch: KillSwitch::new(),
+            latest_rates: HashMap::new(),
+            order_times: HashMap::new(),
+            day_start_pnl: HashMap::new(),
+            last_pnl_check: None,
+            last_metrics_update: None,
+            last_rejection: None,
+            rejection_count: 0,
+            dispatched: LatencyTracker::new(),
+            ids: IdSource::default(),
+            simulated_time: false,
+        }
+    }
+
+    /// Source of the ids of rejected orders, random by default.
+    pub fn set_ids(&mut self, ids: IdSource) {
+        self.ids = ids;
+    }
+
+    /// Stamp the dispatch and decision hops with the time of the replayed data instead of the
+    /// wall clock, so that two runs of a backtest give the same latency trackers.
+    pub fn set_simulated_time(&mut self, simulated_time: bool) {
+        self.simulated_time = simulated_time;
+    }
+
+    pub fn client(&mut self) -> &mut C {
+        &mut self.client
+    }
+
+    pub fn limits(&self) -> &RiskLimits {
+        &self.limits
+    }
+
+    /// Handle sharing the state of this client's kill switch.
+    pub fn kill_switch(&self) -> KillSwitch {
+        self.kill_switch.clone()
+    }
+
+    pub fn last_rejection(&self) -> Option<&RiskRejection> {
+        self.last_rejection.as_ref()
+    }
+
+    pub fn rejection_count(&self) -> usize {
+        self.rejection_count
+    }
+
+    /// Feed callback keeping the reference price per sym. Call it before handing `data`
+    /// to the strategies, orders they submit continue the stamps of `data`.
+    pub fn callback(&mut self, data: &MarketData) {
+        self.dispatched = LatencyTracker::of(data);
+        let dispatched = self.now(data.market_created_timestamp());
+        self.dispatched.stamp(Hop::Dispatch, dispatched);
+        let timestamp = match data {
+            MarketData::Rate(rate) => {
+                self.latest_rates.insert(rate.sym.clone(), rate.clone());
+                rate.market_created_timestamp
+            }
+            MarketData::Ticker(ticker) => {
+                self.latest_rates
+                    .insert(ticker.sym.clone(), ticker.to_rate(&self.ids));
+                ticker.market_created_timestamp
+            }
+            _ => return,
+        };
+        self.check_daily_loss(timestamp);
+        self.update_metrics(timestamp);
+        self.execute_kill_requests(timestamp);
+    }
+
+    /// Run the pending kill switch requests. Also call it from a timer, so that a kill
+    /// goes out while the feed is silent.
+    pub fn execute_kill_requests(&mut self, timestamp: OffsetDateTime) {
+        for request in self.kill_switch.take_pending() {
+            self.execute_kill_request(timestamp, &request);
+        }
+    }
+
+    /// `simulated` in a backtest, the wall clock otherwise.
+    fn now(&self, simulated: OffsetDateTime) -> OffsetDateTime {
+        if self.simulated_time {
+            simulated
+        } else {
+            OffsetDateTime::now_utc()
+        }
+    }
+
+    /// Mark-to-mid PnL per model, `""` for positions the client does not attribute to a model.
+    fn pnl_by_model(&mut self) -> HashMap<String, f64> {
+        let mut pnl = HashMap::new();
+        for position in self.client.get_positions() {
+            if let Some(rate) = self.latest_rates.get(&position.sym) {
+                *pnl.entry(position.model_id.clone()).or_insert(0.0) +=
+                    position.amount.to_f64() * rate.mid_price - position.cost;
+            }
+        }
+        pnl
+    }
+
+    /// Position and PnL gauges, at most every `pnl_check_interval_ms` and only when metrics
+    /// are served.
+    fn update_metrics(&mut self, timestamp: OffsetDateTime) {
+        let interval = Duration::from_millis(self.limits.pnl_check_interval_ms);
+        if !metrics::enabled()
+            || self
+                .last_metrics_update
+                .is_some_and(|t| timestamp < t + interval)
+        {
+            return;
+        }
+        self.last_metrics_update = Some(timestamp);
+
+        for position in self.client.get_positions() {
+            metrics::POSITION
+                .with_label_values(&[&position.model_id, &position.sym])
+                .set(position.amount.to_f64());
+        }
+        for (model_id, pnl) in self.pnl_by_model() {
+            metrics::PNL.with_label_values(&[&model_id]).set(pnl);
+        }
+    }
+
+    fn check_daily_loss(&mut self, timestamp: OffsetDateTime) {
+        let limit = match self.limits.max_daily_loss {
+            Some(limit) => limit,
+            None => return,
+        };
+        let interval = Duration::from_millis(self.limits.pnl_check_interval_ms);
+        if self
+            .last_pnl_check
+            .is_some_and(|t| timestamp < t + interval)
+        {
+            return;
+        }
+        self.last_pnl_check = Some(timestamp);
+
+        for (model_id, pnl) in self.pnl_by_model() {
+            let (date, start_pnl) = *self
+                .day_start_pnl
+                .entry(model_id.clone())
+                .or_insert((timestamp.date(), pnl));
+            if date != timestamp.date() {
+                self.day_start_pnl
+                    .insert(model_id.clone(), (timestamp.date(), pnl));
+                continue;
+            }
+            let daily_pnl = pnl - start_pnl;
+            // venue positions not attributed to a model, e.g. from `BitFlyerClient`, stop every model.
+            let target = (!model_id.is_empty()).then_some(model_id);
+            let killed = match &target {
+                Some(model_id) => self.kill_switch.is_killed(model_id),
+                None => self.kill_switch.is_killed_all(),
+            };
+            if daily_pnl < -limit && !killed {
+                self.kill_switch.trigger(KillRequest {
+                    model_id: target,
+                    flatten: self.limits.flatten_on_daily_loss,
+                    reason: format!("daily loss {} exceeds {}", -daily_pnl, limit),
+                });
+            }
+        }
+    }
+
+    /// Cancel open orders and optionally flatten positions, bypassing the pre-trade checks.
+    fn execute_kill_request(&mut self, timestamp: OffsetDateTime, request: &KillRequest) {
+        let model_id = request.model_id.as_deref();
+        for order in self.client.get_open_orders(model_id) {
+            info!("Kill switch cancels order {}", order.order_id);
+            self.cancel_order(timestamp, &order.order_id);
+        }
+        if !request.flatten {
+            return;
+        }
+        for position in self.client.get_positions() {
+            let side = match position.side() {
+                Some(side) if model_id.is_none_or(|m| m == position.model_id) => side,
+                _ => continue,
+            };
+            // market orders still carry the touch they are expected to take, or the average cost.
+            let price = match self.latest_rates.get(&position.sym) {
+                Some(rate) => match side.opposite() {
+                    Side::Buy => rate.best_ask,
+                    Side::Sell => rate.best_bid,
+                },
+                None => (position.cost / position.amount.to_f64()).abs(),
+            };
+            info!(
+                "Kill switch flattens {} {} of model {} at {}",
+                position.amount, position.sym, position.model_id, price
+            );
+            let order = self.client.submit_order(
+                timestamp,
+                &position.sym,
+                side.opposite(),
+                Price::from_f64(price),
+                position.amount.abs(),
+                OrderType::Market,
+                &position.model_id,
+            );
+            self.record_order(&position.model_id, timestamp);
+            if order.order_status == OrderStatus::Rejected {
+                warn!(
+                    "Kill switch failed to flatten {} : {}",
+                    position.sym, order.misc
+                );
+            }
+        }
+    }
+
+    /// Send times of the orders of `model_id` within `order_rate_window_ms` of `timestamp`.
+    fn order_times(
+        &mut self,
+        model_id: &str,
+        timestamp: OffsetDateTime,
+    ) -> &mut VecDeque<OffsetDateTime> {
+        let window = Duration::from_millis(self.limits.order_rate_window_ms);
+        let times = self.order_times.entry(model_id.to_string()).or_default();
+        while times.front().is_some_and(|t| *t + window <= timestamp) {
+            times.pop_front();
+        }
+        times
+    }
+
+    /// Only kept for `max_order_rate`, otherwise the times of a live process pile up.
+    fn record_order(&mut self, model_id: &str, timestamp: OffsetDateTime) {
+        if self.limits.max_order_rate.is_some() {
+            self.order_times(model_id, timestamp).push_back(timestamp);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn check(
+        &mut self,
+        timestamp: OffsetDateTime,
+        sym: &str,
+        side: Side,
+        price: Price,
+        amount: Qty,
+        order_type: &OrderType,
+        model_id: &str,
+    ) -> Result<(), RiskRejection> {
+        let (price, amount) = (price.to_f64(), amount.to_f64());
+        if self.kill_switch.is_killed(model_id) {
+            return Err(RiskRejection::KillSwitch {
+                model_id: model_id.to_string(),
+            });
+        }
+
+        if let Some(limit) = self.limits.max_order_amount {
+            if amount > limit {
+                return Err(RiskRejection::OrderAmount { amount, limit });
+            }
+        }
+
+        // market orders are valued at the touch they would take.
+        let rate = self.latest_rates.get(sym);
+        let reference = match order_type {
+            OrderType::Market => rate.map(|r| match side {
+                Side::Buy => r.best_ask,
+                Side::Sell => r.best_bid,
+            }),
+            _ => Some(price),
+        };
+        if let Some(limit) = self.limits.max_order_notional {
+            match reference {
+                Some(reference) if (amount * reference).abs() > limit => {
+                    return Err(RiskRejection::OrderNotional {
+                        notional: amount * reference,
+                        limit,
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    return Err(RiskRejection::NoReferencePrice {
+                        sym: sym.to_string(),
+                    })
+                }
+            }
+        }
+
+        if let (Some(limit), false) = (
+            self.limits.price_collar_bps,
+            matches!(order_type, OrderType::Market),
+        ) {
+            let mid = match rate {
+                Some(rate) if rate.mid_price.is_finite() && rate.mid_price > 0.0 => rate.mid_price,
+                _ => {
+                    return Err(RiskRejection::NoReferencePrice {
+                        sym: sym.to_string(),
+                    })
+                }
+            };
+            let bps = (price - mid).abs() / mid * 10000.0;
+            if bps > limit {
+                return Err(RiskRejection::PriceCollar {
+                    price,
+                    reference: mid,
+                    bps,
+                    limit,
+                });
+            }
+        }
+
+        let open_orders = self.client.get_open_orders(Some(model_id));
+        if let Some(limit) = self.limits.max_open_orders {
+            if open_orders.len() >= limit {
+                return Err(RiskRejection::OpenOrders {
+                    count: open_orders.len(),
+                    limit,
+                });
+            }
+        }
+
+        if let Some(limit) = self.limits.max_position {
+            // worst case: every open order on the same side gets filled.
+            let position: Qty = self
+                .client
+                .get_positions()
+                .iter()
+                .filter(|p| p.sym == sym && (p.model_id == model_id || p.model_id.is_empty()))
+                .map(|p| p.amount)
+                .sum();
+            let working: Qty = open_orders
+                .iter()
+                .filter(|o| o.sym == sym && o.side == side)
+                .map(|o| (o.amount - o.executed_amount).signed(side))
+                .sum();
+            let projected = (position + working).to_f64() + side.sign() as f64 * amount;
+            if projected.abs() > limit {
+                return Err(RiskRejection::Position { projected, limit });
+            }
+        }
+
+        if let Some(limit) = self.limits.max_order_rate {
+            let times = self.order_times(model_id, timestamp);
+            if times.len() >= limit {
+                return Err(RiskRejection::OrderRate {
+                    count: times.len(),
+                    limit,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<C: ExecutionClient> ExecutionClient for RiskClient<C> {
+    fn submit_order(
+        &mut self,
+        timestamp: OffsetDateTime,
+        sym: &str,
+        side: Side,
+        price: Price,
+        amount: Qty,
+        order_type: OrderType,
+        model_id: &str,
+    ) -> Order {
+        let mut tracker = self.dispatched.clone();
+        tracker.stamp(Hop::Decision, self.now(timestamp));
+        self.submit_order_traced(
+            timestamp, sym, side, price, amount, order_type, model_id, tracker,
+        )
+    }
+
+    fn submit_order_traced(
+        &mut self,
+        timestamp: OffsetDateTime,
+        sym: &str,
+        side: Side,
+        price: Price,
+        amount: Qty,
+        order_type: OrderType,
+        model_id: &str,
+        mut tracker: LatencyTracker,
+    ) -> Order {
+        match self.check(timestamp, sym, side, price, amount, &order_type, model_id) {
+            Ok(()) => {
+                self.record_order(model_id, timestamp);
+                let order = self.client.submit_order_traced(
+                    timestamp, sym, side, price, amount, order_type, model_id, tracker,
+                );
+                let event = match order.order_status {
+                    OrderStatus::Rejected => "rejected",
+                    _ => "sent",
+                };
+                metrics::ORDERS.with_label_values(&[model_id, event]).inc();
+                order
+            }
+            Err(rejection) => {
+                warn!(
+                    "Risk rejected order model={} sym={} side={} price={} amount={} : {}",
+                    model_id, sym, side, price, amount, rejection
+                );
+                let venue = self
+                    .latest_rates
+                    .get(sym)
+                    .map(|r| r.venue.clone())
+                    .unwrap_or_default();
+                let mut order = Order::new(
+                    timestamp, sym, side, price, amount, order_type, &venue, model_id, &self.ids,
+                );
+                let _ = order.transition(OrderStatus::Rejected, timestamp);
+                order.misc = rejection.to_string();
+                tracker.stamp(Hop::Ack, self.now(timestamp));
+                order.latency_tracker = tracker.to_string();
+                self.last_rejection = Some(rejection);
+                self.rejection_count += 1;
+                metrics::ORDERS
+                    .with_label_values(&[model_id, "rejected"])
+                    .inc();
+                order
+            }
+        }
+    }
+
+    fn cancel_order(&mut self, timestamp: OffsetDateTime, order_id: &str) -> Option<Order> {
+        let order = self.client.cancel_order(timestamp, order_id)?;
+        metrics::ORDERS
+            .with_label_values(&[&order.model_id, "canceled"])
+            .inc();
+        Some(order)
+    }
+
+    fn get_order_status(&mut self, order_id: &str) -> Option<Order> {
+        self.client.get_order_status(order_id)
+    }
+
+    fn get_open_orders(&mut self, model_id: Option<&str>) -> Vec<Order> {
+        self.client.get_open_orders(model_id)
+    }
+
+    fn get_positions(&mut self) -> Vec<Position> {
+        self.client.get_positions()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::backtest::backtest_client::BacktestClient;
+    use crate::risk::kill_switch::KillRequest;
+
+    /// Holds one position and records the orders it is sent.
+    #[derive(Default)]
+    struct StubClient {
+        positions: Vec<Position>,
+        orders: Vec<Order>,
+    }
+
+    impl ExecutionClient for StubClient {
+        fn submit_order(
+            &mut self,
+            timestamp: OffsetDateTime,
+            sym: &str,
+            side: Side,
+            price: Price,
+            amount: Qty,
+            order_type: OrderType,
+            model_id: &str,
+        ) -> Order {
+            let order = Order::new(
+                timestamp,
+                sym,
+                side,
+                price,
+                amount,
+                order_type,
+                "stub",
+                model_id,
+                &IdSource::default(),
+            );
+            self.orders.push(order.clone());
+            order
+        }
+
+        fn cancel_order(&mut self, _: OffsetDateTime, _: &str) -> Option<Order> {
+            None
+        }
+
+        fn get_order_status(&mut self, _: &str) -> Option<Order> {
+            None
+        }
+
+        fn get_open_orders(&mut self, _: Option<&str>) -> Vec<Order> {
+            vec![]
+        }
+
+        fn get_positions(&mut self) -> Vec<Position> {
+            self.positions.clone()
+        }
+    }
+
+    fn long(amount: f64, cost: f64) -> StubClient {
+        long_of("model", amount, cost)
+    }
+
+    fn long_of(model_id: &str, amount: f64, cost: f64) -> StubClient {
+        let mut position = Position::new("FXBTCJPY", "stub", model_id, &IdSource::default());
+        position.amount = Qty::from_f64(amount);
+        position.cost = cost;
+        StubClient {
+            positions: vec![position],
+            orders: vec![],
+        }
+    }
+
+    fn rate(best_bid: f64, best_ask: f64) -> MarketData {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        MarketData::Rate(Rate {
+            timestamp: now,
+            market_created_timestamp: now,
+            received_timestamp: now,
+            sym: "FXBTCJPY".to_string(),
+            venue: "stub".to_string(),
+            category: "".to_string(),
+            best_bid,
+            best_ask,
+            mid_price: (best_bid + best_ask) / 2.0,
+            misc: "".to_string(),
+            universal_id: "".to_string(),
+            data_center: "".to_string(),
+            process_id: "".to_string(),
+        })
+    }
+
+    fn flatten(model_id: Option<&str>) -> KillRequest {
+        KillRequest {
+            model_id: model_id.map(str::to_string),
+            flatten: true,
+            reason: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn kill_requests_run_without_market_data() {
+        let mut risk = RiskClient::new(long(0.5, 2_500_000.0), RiskLimits::default());
+        risk.kill_switch().trigger(flatten(None));
+        risk.execute_kill_requests(OffsetDateTime::UNIX_EPOCH);
+
+        let order = &risk.client().orders[0];
+        assert_eq!(order.side, Side::Sell);
+        assert_eq!(order.amount, Qty::from_f64(0.5));
+        // no rate seen, priced at the average cost
+        assert_eq!(order.price, Price::from_f64(5_000_000.0));
+        assert!(!risk.kill_switch().has_pending());
+    }
+
+    #[test]
+    fn flatten_is_priced_at_the_touch() {
+        let mut risk = RiskClient::new(long(0.5, 2_500_000.0), RiskLimits::default());
+        risk.callback(&rate(4_900_000.0, 4_910_000.0));
+        risk.kill_switch().trigger(flatten(Some("model")));
+        risk.callback(&rate(4_800_000.0, 4_810_000.0));
+
+        let orders = &risk.client().orders;
+        assert_eq!(orders.len(), 1);
+        assert!(matches!(orders[0].order_type, OrderType::Market));
+        assert_eq!(orders[0].price, Price::from_f64(4_800_000.0));
+        assert!(risk.kill_switch().is_killed("model"));
+    }
+
+    #[test]
+    fn daily_loss_on_unattributed_positions_stops_every_model() {
+        let limits = RiskLimits {
+            max_daily_loss: Some(10_000.0),
+            flatten_on_daily_loss: true,
+            pnl_check_interval_ms: 0,
+            ..RiskLimits::default()
+        };
+        let mut risk = RiskClient::new(long_of("", 0.5, 2_500_000.0), limits);
+        risk.callback(&rate(4_990_000.0, 5_010_000.0));
+        assert!(!risk.kill_switch().is_killed("model"));
+        // mid 4_970_000, 15_000 lost since the start of the day
+        risk.callback(&rate(4_960_000.0, 4_980_000.0));
+
+        assert!(risk.kill_switch().is_killed_all());
+        assert!(risk.kill_switch().is_killed("model"));
+        let orders = &risk.client().orders;
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].side, Side::Sell);
+        assert_eq!(orders[0].amount, Qty::from_f64(0.5));
+
+        let rejected = risk.submit_order(
+            OffsetDateTime::UNIX_EPOCH,
+            "FXBTCJPY",
+            Side::Buy,
+            Price::from_f64(4_960_000.0),
+            Qty::from_f64(0.1),
+            OrderType::Limit,
+            "model",
+        );
+        assert_eq!(rejected.order_status, OrderStatus::Rejected);
+    }
+
+    #[test]
+    fn daily_loss_of_a_model_stops_only_that_model() {
+        let limits = RiskLimits {
+            max_daily_loss: Some(10_000.0),
+            pnl_check_interval_ms: 0,
+            ..RiskLimits::default()
+        };
+        let mut risk = RiskClient::new(long(0.5, 2_500_000.0), limits);
+        risk.callback(&rate(4_990_000.0, 5_010_000.0));
+        risk.callback(&rate(4_960_000.0, 4_980_000.0));
+        assert!(risk.kill_switch().is_killed("model"));
+        assert!(!risk.kill_switch().is_killed("other"));
+    }
+
+    fn backtest(limits: RiskLimits) -> RiskClient<BacktestClient<'static>> {
+        RiskClient::new(BacktestClient::new("stub"), limits)
+    }
+
+    fn submit(
+        risk: &mut RiskClient<BacktestClient<'static>>,
+        side: Side,
+        price: f64,
+        amount: f64,
+        order_type: OrderType,
+    ) -> Result<Order, RiskRejection> {
+        let order = risk.submit_order(
+            OffsetDateTime::UNIX_EPOCH,
+            "FXBTCJPY",
+            side,
+            Price::from_f64(price),
+            Qty::from_f64(amount),
+            order_type,
+            "model",
+        );
+        match order.order_status {
+            OrderStatus::Rejected => Err(risk.last_rejection().unwrap().clone()),
+            _ => Ok(order),
+        }
+    }
+
+    fn buy(
+        risk: &mut RiskClient<BacktestClient<'static>>,
+        price: f64,
+        amount: f64,
+    ) -> Result<Order, RiskRejection> {
+        submit(risk, Side::Buy, price, amount, OrderType::Limit)
+    }
+
+    #[test]
+    fn max_order_amount() {
+        let mut risk = backtest(RiskLimits {
+            max_order_amount: Some(1.0),
+            ..RiskLimits::default()
+        });
+        assert!(buy(&mut risk, 5_000_000.0, 1.0).is_ok());
+        assert_eq!(
+            buy(&mut risk, 5_000_000.0, 1.5).unwrap_err(),
+            RiskRejection::OrderAmount {
+                amount: 1.5,
+                limit: 1.0
+            }
+        );
+        assert_eq!(risk.rejection_count(), 1);
+        // rejected orders never reach the client
+        assert_eq!(risk.client().orders().len(), 1);
+    }
+
+    #[test]
+    fn max_order_notional() {
+        let mut risk = backtest(RiskLimits {
+            max_order_notional: Some(1_000_000.0),
+            ..RiskLimits::default()
+        });
+        assert!(buy(&mut risk, 5_000_000.0, 0.2).is_ok());
+        assert!(matches!(
+            buy(&mut risk, 5_000_000.0, 0.3),
+            Err(RiskRejection::OrderNotional { .. })
+        ));
+    }
+
+    #[test]
+    fn market_orders_need_a_reference_price() {
+        let mut risk = backtest(RiskLimits {
+            max_order_notional: Some(1_000_000.0),
+            ..RiskLimits::default()
+        });
+        assert_eq!(
+            submit(&mut risk, Side::Buy, 0.0, 0.1, OrderType::Market).unwrap_err(),
+            RiskRejection::NoReferencePrice {
+                sym: "FXBTCJPY".to_string()
+            }
+        );
+        risk.callback(&rate(4_990_000.0, 5_010_000.0));
+        assert!(submit(&mut risk, Side::Buy, 0.0, 0.1, OrderType::Market).is_ok());
+        // valued at the ask it would take, not at its own price
+        assert_eq!(
+            submit(&mut risk, Side::Buy, 0.0, 0.2, OrderType::Market).unwrap_err(),
+            RiskRejection::OrderNotional {
+                notional: 0.2 * 5_010_000.0,
+                limit: 1_000_000.0
+            }
+        );
+    }
+
+    #[test]
+    fn price_collar() {
+        let mut risk = backtest(RiskLimits {
+            price_collar_bps: Some(100.0),
+            ..RiskLimits::default()
+        });
+        assert!(matches!(
+            buy(&mut risk, 5_000_000.0, 0.1),
+            Err(RiskRejection::NoReferencePrice { .. })
+        ));
+        risk.callback(&rate(4_990_000.0, 5_010_000.0));
+        assert!(buy(&mut risk, 4_960_000.0, 0.1).is_ok());
+        assert!(matches!(
+            buy(&mut risk, 4_940_000.0, 0.1),
+            Err(RiskRejection::PriceCollar { .. })
+        ));
+        // market orders take the touch, the collar does not apply
+        assert!(submit(&mut risk, Side::Sell, 0.0, 0.1, OrderType::Market).is_ok());
+    }
+
+    #[test]
+    fn max_position_counts_working_orders_on_the_same_side() {
+        let mut risk = backtest(RiskLimits {
+            max_position: Some(1.0),
+            ..RiskLimits::default()
+        });
+        assert!(buy(&mut risk, 5_000_000.0, 0.6).is_ok());
+        assert!(matches!(
+            buy(&mut risk, 5_000_000.0, 0.6),
+            Err(RiskRejection::Position { .. })
+        ));
+        assert!(submit(&mut risk, Side::Sell, 5_100_000.0, 0.6, OrderType::Limit).is_ok());
+        assert!(buy(&mut risk, 5_000_000.0, 0.4).is_ok());
+    }
+
+    #[test]
+    fn max_open_orders() {
+        let mut risk = backtest(RiskLimits {
+            max_open_orders: Some(2),
+            ..RiskLimits::default()
+        });
+        let first = buy(&mut risk, 5_000_000.0, 0.1).unwrap();
+        assert!(buy(&mut risk, 5_000_000.0, 0.1).is_ok());
+        assert_eq!(
+            buy(&mut risk, 5_000_000.0, 0.1).unwrap_err(),
+            RiskRejection::OpenOrders { count: 2, limit: 2 }
+        );
+        risk.cancel_order(OffsetDateTime::UNIX_EPOCH, &first.order_id);
+        assert!(buy(&mut risk, 5_000_000.0, 0.1).is_ok());
+    }
+
+    #[test]
+    fn max_order_rate() {
+        let mut risk = backtest(RiskLimits {
+            max_order_rate: Some(2),
+            order_rate_window_ms: 1000,
+            ..RiskLimits::default()
+        });
+        assert!(buy(&mut risk, 5_000_000.0, 0.1).is_ok());
+        assert!(buy(&mut risk, 5_000_000.0, 0.1).is_ok());
+        assert_eq!(
+            buy(&mut risk, 5_000_000.0, 0.1).unwrap_err(),
+            RiskRejection::OrderRate { count: 2, limit: 2 }
+        );
+        let later = risk.submit_order(
+            OffsetDateTime::UNIX_EPOCH + time::Duration::SECOND,
+            "FXBTCJPY",
+            Side::Buy,
+            Price::from_f64(5_000_000.0),
+            Qty::from_f64(0.1),
+            OrderType::Limit,
+            "model",
+        );
+        assert_eq!(later.order_status, OrderStatus::Submitted);
+        assert_eq!(risk.order_times["model"].len(), 1);
+    }
+
+    #[test]
+    fn order_times_are_not_kept_without_a_rate_limit() {
+        let mut risk = backtest(RiskLimits::default());
+        for _ in 0..10 {
+            assert!(buy(&mut risk, 5_000_000.0, 0.1).is_ok());
+        }
+        assert!(risk.order_times.is_empty());
+    }
+}
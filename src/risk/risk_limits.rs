@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Pre-trade limits enforced by `RiskClient`. `None` disables a check.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RiskLimits {
+    /// Max amount of a single order.
+    #[serde(default)]
+    pub max_order_amount: Option<f64>,
+    /// Max `amount * price` of a single order, in quote currency.
+    #[serde(default)]
+    pub max_order_notional: Option<f64>,
+    /// Max absolute position per sym and model, including open orders on the same side.
+    #[serde(default)]
+    pub max_position: Option<f64>,
+    /// Max distance of a limit price from the latest mid, in bps.
+    #[serde(default)]
+    pub price_collar_bps: Option<f64>,
+    /// Max open orders per model.
+    #[serde(default)]
+    pub max_open_orders: Option<usize>,
+    /// Max orders per model within `order_rate_window_ms`.
+    #[serde(default)]
+    pub max_order_rate: Option<usize>,
+    #[serde(default = "default_order_rate_window_ms")]
+    pub order_rate_window_ms: u64,
+    /// Loss per model since 00:00 UTC that triggers the kill switch, in quote currency.
+    /// Positions the client does not attribute to a model, e.g. live ones, stop every model.
+    #[serde(default)]
+    pub max_daily_loss: Option<f64>,
+    /// Flatten positions when the daily loss limit is hit.
+    #[serde(default)]
+    pub flatten_on_daily_loss: bool,
+    /// Min interval between PnL evaluations, positions may come from a REST API.
+    #[serde(default = "default_pnl_check_interval_ms")]
+    pub pnl_check_interval_ms: u64,
+    /// Flag file of the `paper` and `live` kill switch, see `KillSwitch::watch_file`.
+    #[serde(default)]
+    pub kill_switch_file: Option<PathBuf>,
+    /// Poll interval of the flag file and of pending kill requests.
+    #[serde(default = "default_kill_switch_poll_ms")]
+    pub kill_switch_poll_ms: u64,
+}
+
+impl Default for RiskLimits {
+    fn default() -> Self {
+        Self {
+            max_order_amount: None,
+            max_order_notional: None,
+            max_position: None,
+            price_collar_bps: None,
+            max_open_orders: None,
+            max_order_rate: None,
+            order_rate_window_ms: default_order_rate_window_ms(),
+            max_daily_loss: None,
+            flatten_on_daily_loss: false,
+            pnl_check_interval_ms: default_pnl_check_interval_ms(),
+            kill_switch_file: None,
+            kill_switch_poll_ms: default_kill_switch_poll_ms(),
+        }
+    }
+}
+
+fn default_order_rate_window_ms() -> u64 {
+    1000
+}
+
+fn default_pnl_check_interval_ms() -> u64 {
+    5000
+}
+
+fn default_kill_switch_poll_ms() -> u64 {
+    1000
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RiskRejection {
+    OrderAmount {
+        amount: f64,
+        limit: f64,
+    },
+    OrderNotional {
+        notional: f64,
+        limit: f64,
+    },
+    Position {
+        projected: f64,
+        limit: f64,
+    },
+    PriceCollar {
+        price: f64,
+        reference: f64,
+        bps: f64,
+        limit: f64,
+    },
+    NoReferencePrice {
+        sym: String,
+    },
+    OpenOrders {
+        count: usize,
+        limit: usize,
+    },
+    OrderRate {
+        count: usize,
+        limit: usize,
+    },
+    KillSwitch {
+        model_id: String,
+    },
+}
+
+impl fmt::Display for RiskRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskRejection::OrderAmount { amount, limit } => {
+                write!(f, "order amount {} exceeds {}", amount, limit)
+            }
+            RiskRejection::OrderNotional { notional, limit } => {
+                write!(f, "order notional {} exceeds {}", notional, limit)
+            }
+            RiskRejection::Position { projected, limit } => {
+                write!(f, "projected position {} exceeds {}", projected, limit)
+            }
+            RiskRejection::PriceCollar {
+                price,
+                reference,
+                bps,
+                limit,
+            } => write!(
+                f,
+                "price {} is {:.1}bps away from {} (limit {}bps)",
+                price, bps, reference, limit
+            ),
+            RiskRejection::NoReferencePrice { sym } => {
+                write!(f, "no reference price for {}", sym)
+            }
+            RiskRejection::OpenOrders { count, limit } => {
+                write!(f, "{} open orders reach the limit {}", count, limit)
+            }
+            RiskRejection::OrderRate { count, limit } => {
+                write!(f, "{} orders in window reach the limit {}", count, limit)
+            }
+            RiskRejection::KillSwitch { model_id } => {
+                write!(f, "kill switch is active for model {}", model_id)
+            }
+        }
+    }
+}
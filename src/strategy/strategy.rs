@@ -1,5 +1,19 @@
-use crate::execution_client::execution_client::ExecutionClient;
+use crate::api_client::execution_client::ExecutionClient;
+use crate::config::app_config::{ConfigError, StrategyConfig};
+use crate::datamodels::enums::MarketData;
 
-pub trait Strategy {
-    fn client(&self) -> ExecutionClient;
+/// A trading model. The same implementation runs in backtest, paper and live,
+/// only the `ExecutionClient` differs.
+pub trait Strategy: Send {
+    fn model_id(&self) -> &str;
+    fn on_market_data(&mut self, data: &MarketData, client: &mut dyn ExecutionClient);
+}
+
+/// Strategy implementing `config.kind`.
+pub fn from_config(config: &StrategyConfig) -> Result<Box<dyn Strategy>, ConfigError> {
+    // kinds are registered here as they are implemented.
+    Err(ConfigError::Invalid(format!(
+        "unknown strategy kind {} for model {}",
+        config.kind, config.model_id
+    )))
 }